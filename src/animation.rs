@@ -0,0 +1,1017 @@
+use std::collections::{
+    HashMap,
+    HashSet,
+};
+use std::mem;
+
+use crate::color::{
+    self,
+    HueInterpolation,
+};
+use crate::dom::ElementId;
+use crate::css::{
+    CalcNode,
+    Color,
+    ColorSpace,
+    EasingFunction,
+    KeyframesRule,
+    StepPosition,
+    TransformFunction,
+    Unit,
+    Value,
+};
+
+// The clock animations and transitions run on. It only moves when the caller
+// moves it, so the same times always give the same styles.
+#[derive(Default, Clone)]
+pub struct Timeline {
+    // In seconds.
+    time: f32,
+    elements: HashMap<ElementId, ElementAnimations>,
+    // The elements updated since the tree was last styled.
+    visited: HashSet<ElementId>,
+}
+
+#[derive(Default, Clone)]
+struct ElementAnimations {
+    animations: Vec<RunningAnimation>,
+    transitions: HashMap<String, RunningTransition>,
+    // The styles from the last update, before and after animations and
+    // transitions were applied.
+    before: Option<HashMap<String, Value>>,
+    after: HashMap<String, Value>,
+}
+
+#[derive(Clone)]
+struct RunningAnimation {
+    name: String,
+    start_time: f32,
+    // How far a paused animation had got when it was paused.
+    paused_at: Option<f32>,
+}
+
+#[derive(Clone)]
+struct RunningTransition {
+    from: Value,
+    to: Value,
+    start_time: f32,
+    delay: f32,
+    duration: f32,
+    easing: EasingFunction,
+    // For transitions that reverse a running one, which are shortened by how
+    // far the running one got.
+    reversing_adjusted_start: Value,
+    shortening_factor: f32,
+}
+
+// The parameters the animation properties give the animation at `index`.
+struct AnimationParameters {
+    duration: f32,
+    easing: EasingFunction,
+    delay: f32,
+    iterations: f32,
+    direction: String,
+    fill_mode: String,
+    paused: bool,
+}
+
+impl Timeline {
+    pub fn new() -> Self {
+        return Timeline::default()
+    }
+
+    pub fn time(&self) -> f32 {
+        return self.time
+    }
+
+    pub fn set_time(&mut self, seconds: f32) {
+        self.time = seconds;
+    }
+
+    pub fn advance(&mut self, seconds: f32) {
+        self.time += seconds;
+    }
+
+    // Whether a transition is still running, so the caller knows another
+    // frame would look different. Animations can run forever, so they
+    // aren't counted.
+    pub fn has_running_transitions(&self) -> bool {
+        let time = self.time;
+
+        return self.elements.values().any(|e| e.transitions.values().any(|t| time < t.start_time + t.delay + t.duration))
+    }
+
+    // Applies the animations and transitions of the element `id` to its
+    // computed `styles`, starting and stopping them as the styles change.
+    // `important` properties aren't animated, since the cascade puts
+    // important declarations above animations. `compute` turns keyframe
+    // values into computed values.
+    pub(crate) fn update(
+        &mut self,
+        id: ElementId,
+        styles: &mut HashMap<String, Value>,
+        keyframes: &HashMap<&str, &KeyframesRule>,
+        important: &HashSet<&str>,
+        compute: &dyn Fn(&str, &Value) -> Option<Value>,
+    ) {
+        let time = self.time;
+        self.visited.insert(id);
+
+        let state = self.elements.entry(id).or_insert_with(ElementAnimations::default);
+        let base = styles.clone();
+
+        state.update_animations(time, styles, keyframes, important, compute);
+        state.update_transitions(time, &base, styles);
+
+        state.before = Some(base);
+        state.after = styles.clone();
+    }
+
+    // Called once the whole tree is styled. Elements that weren't in it
+    // are gone, and so are their animations and transitions.
+    pub(crate) fn finish(&mut self) {
+        let visited = mem::take(&mut self.visited);
+
+        self.elements.retain(|id, _| visited.contains(id));
+    }
+}
+
+impl ElementAnimations {
+    fn update_animations(
+        &mut self,
+        time: f32,
+        styles: &mut HashMap<String, Value>,
+        keyframes: &HashMap<&str, &KeyframesRule>,
+        important: &HashSet<&str>,
+        compute: &dyn Fn(&str, &Value) -> Option<Value>,
+    ) {
+        let names: Vec<String> = list(styles, "animation-name")
+            .iter()
+            .filter_map(|v| match *v {
+                Value::Other(ref name) if name != "none" => Some(name.clone()),
+                _ => None,
+            })
+            .collect();
+
+        // Animations that are no longer listed stop, and new ones start now.
+        self.animations.retain(|a| names.contains(&a.name) && keyframes.contains_key(a.name.as_str()));
+
+        for name in &names {
+            if keyframes.contains_key(name.as_str()) && !self.animations.iter().any(|a| a.name == *name) {
+                self.animations.push(RunningAnimation { name: name.clone(), start_time: time, paused_at: None });
+            }
+        }
+
+        let base = styles.clone();
+
+        // Later animations in the list override earlier ones.
+        for (index, name) in names.iter().enumerate() {
+            let rule = match keyframes.get(name.as_str()) {
+                Some(rule) => rule,
+                None => continue,
+            };
+            let animation = match self.animations.iter_mut().find(|a| a.name == *name) {
+                Some(animation) => animation,
+                None => continue,
+            };
+
+            let parameters = AnimationParameters::new(&base, index);
+
+            // A paused animation holds its progress until it's resumed.
+            match (parameters.paused, animation.paused_at) {
+                (true, None) => animation.paused_at = Some(time - animation.start_time),
+                (false, Some(elapsed)) => {
+                    animation.start_time = time - elapsed;
+                    animation.paused_at = None;
+                },
+                _ => {},
+            }
+
+            let elapsed = animation.paused_at.unwrap_or(time - animation.start_time);
+
+            let (progress, before) = match parameters.progress(elapsed) {
+                Some(progress) => progress,
+                None => continue,
+            };
+
+            for property in animated_properties(rule) {
+                if important.contains(property) {
+                    continue
+                }
+
+                if let Some(value) = keyframe_value(rule, property, progress, before, &parameters, &base, compute) {
+                    styles.insert(property.to_string(), value);
+                }
+            }
+        }
+    }
+
+    // Starts transitions for the properties whose value changed since the
+    // last update, and applies the running ones on top of everything else.
+    fn update_transitions(&mut self, time: f32, base: &HashMap<String, Value>, styles: &mut HashMap<String, Value>) {
+        let before = match self.before {
+            Some(ref before) => before,
+            // Nothing transitions the first time an element is styled.
+            None => return,
+        };
+
+        let properties: HashSet<&String> = before.keys().chain(base.keys()).collect();
+
+        for property in properties {
+            let (duration, delay, easing) = match transition_parameters(base, property) {
+                Some(parameters) => parameters,
+                None => {
+                    self.transitions.remove(property);
+
+                    continue
+                },
+            };
+
+            let (old, new) = match (before.get(property), base.get(property)) {
+                (Some(old), Some(new)) => (old, new),
+                _ => {
+                    self.transitions.remove(property);
+
+                    continue
+                },
+            };
+
+            if old == new {
+                continue
+            }
+
+            if self.transitions.get(property).map_or(false, |t| t.to == *new) {
+                continue
+            }
+
+            // The transition starts from what was on screen, which may be
+            // partway through another transition.
+            let from = self.after.get(property).unwrap_or(old).clone();
+
+            if duration.max(0.0) + delay <= 0.0 || interpolate(&from, new, 0.5).is_none() {
+                self.transitions.remove(property);
+
+                continue
+            }
+
+            let mut transition = RunningTransition {
+                from: from.clone(),
+                to: new.clone(),
+                start_time: time,
+                delay,
+                duration,
+                easing,
+                reversing_adjusted_start: from,
+                shortening_factor: 1.0,
+            };
+
+            // Going back to where a running transition came from takes as
+            // long as getting to where it is took.
+            if let Some(running) = self.transitions.get(property) {
+                if running.reversing_adjusted_start == *new {
+                    let progress = running.progress(time).unwrap_or(0.0);
+                    let factor = (progress * running.shortening_factor + 1.0 - running.shortening_factor).max(0.0).min(1.0);
+
+                    transition.duration *= factor;
+
+                    if transition.delay < 0.0 {
+                        transition.delay *= factor;
+                    }
+
+                    transition.reversing_adjusted_start = running.to.clone();
+                    transition.shortening_factor = factor;
+                }
+            }
+
+            self.transitions.insert(property.clone(), transition);
+        }
+
+        self.transitions.retain(|_, t| time < t.start_time + t.delay + t.duration);
+
+        // Transitions override every other declaration, important or not.
+        for (property, transition) in &self.transitions {
+            let progress = transition.progress(time).unwrap_or(0.0);
+
+            if let Some(value) = interpolate(&transition.from, &transition.to, progress) {
+                styles.insert(property.clone(), value);
+            }
+        }
+    }
+}
+
+impl RunningTransition {
+    // The eased progress, or `None` during the delay, when the start value
+    // is used.
+    fn progress(&self, time: f32) -> Option<f32> {
+        let elapsed = time - self.start_time - self.delay;
+
+        if elapsed < 0.0 {
+            return None
+        }
+
+        let progress = match self.duration > 0.0 {
+            true => (elapsed / self.duration).min(1.0),
+            false => 1.0,
+        };
+
+        return Some(self.easing.apply(progress, false))
+    }
+}
+
+impl AnimationParameters {
+    // Lists shorter than animation-name repeat.
+    fn new(styles: &HashMap<String, Value>, index: usize) -> Self {
+        let item = |name: &str| {
+            let values = list(styles, name);
+
+            match values.is_empty() {
+                true => None,
+                false => Some(values[index % values.len()].clone()),
+            }
+        };
+
+        let keyword = |name: &str, default: &str| match item(name) {
+            Some(Value::Other(keyword)) => keyword,
+            _ => default.to_string(),
+        };
+
+        return AnimationParameters {
+            duration: match item("animation-duration") {
+                Some(Value::Time(t)) => t.max(0.0),
+                _ => 0.0,
+            },
+            easing: match item("animation-timing-function") {
+                Some(Value::Easing(e)) => e,
+                _ => EasingFunction::CubicBezier(0.25, 0.1, 0.25, 1.0),
+            },
+            delay: match item("animation-delay") {
+                Some(Value::Time(t)) => t,
+                _ => 0.0,
+            },
+            iterations: match item("animation-iteration-count") {
+                Some(Value::Number(n)) => n.max(0.0),
+                Some(Value::Other(_)) => f32::INFINITY,
+                _ => 1.0,
+            },
+            direction: keyword("animation-direction", "normal"),
+            fill_mode: keyword("animation-fill-mode", "none"),
+            paused: keyword("animation-play-state", "running") == "paused",
+        }
+    }
+
+    // The progress through the current iteration, taking the direction into
+    // account, and whether the animation hasn't started yet. `None` when
+    // the animation has no effect at this time.
+    fn progress(&self, elapsed: f32) -> Option<(f32, bool)> {
+        let local = elapsed - self.delay;
+        let active_duration = match self.duration > 0.0 {
+            true => self.duration * self.iterations,
+            false => 0.0,
+        };
+
+        let fills = |fill: &str| self.fill_mode == fill || self.fill_mode == "both";
+
+        let (overall, before) = if local < 0.0 {
+            match fills("backwards") {
+                true => (0.0, true),
+                false => return None,
+            }
+        } else if local >= active_duration {
+            match fills("forwards") {
+                true => (self.iterations, false),
+                false => return None,
+            }
+        } else {
+            (local / self.duration, false)
+        };
+
+        // A zero-duration animation that repeats forever has gone through
+        // every iteration at once, and ends going forwards unless it's
+        // reversed.
+        if overall.is_infinite() {
+            return match self.direction.as_ref() {
+                "reverse" => Some((0.0, false)),
+                _ => Some((1.0, false)),
+            }
+        }
+
+        let mut iteration = overall.floor();
+        let mut progress = overall - iteration;
+
+        // An animation that has ended stays at the end of its last iteration.
+        if !before && local >= active_duration && progress == 0.0 && self.iterations > 0.0 {
+            iteration -= 1.0;
+            progress = 1.0;
+        }
+
+        let odd = iteration % 2.0 == 1.0;
+
+        let reversed = match self.direction.as_ref() {
+            "reverse" => true,
+            "alternate" => odd,
+            "alternate-reverse" => !odd,
+            _ => false,
+        };
+
+        return match reversed {
+            true => Some((1.0 - progress, before)),
+            false => Some((progress, before)),
+        }
+    }
+}
+
+impl EasingFunction {
+    // Maps the progress through an interval to the progress of the value.
+    // `before` is set before an animation starts, which moves steps() that
+    // jump at the start back a step.
+    pub fn apply(&self, progress: f32, before: bool) -> f32 {
+        return match *self {
+            EasingFunction::Linear => progress,
+            EasingFunction::CubicBezier(x1, y1, x2, y2) => cubic_bezier(x1, y1, x2, y2, progress),
+            EasingFunction::Steps(steps, position) => {
+                let steps = steps as f32;
+                let jumps = match position {
+                    StepPosition::JumpStart | StepPosition::JumpEnd => steps,
+                    StepPosition::JumpNone => steps - 1.0,
+                    StepPosition::JumpBoth => steps + 1.0,
+                };
+
+                let mut step = (progress * steps).floor();
+
+                if position == StepPosition::JumpStart || position == StepPosition::JumpBoth {
+                    step += 1.0;
+                }
+
+                if before && (progress * steps).fract() == 0.0 {
+                    step -= 1.0;
+                }
+
+                if progress >= 0.0 && step < 0.0 {
+                    step = 0.0;
+                }
+
+                if progress <= 1.0 && step > jumps {
+                    step = jumps;
+                }
+
+                step / jumps
+            },
+        }
+    }
+}
+
+// Finds the curve parameter whose x is `x` with Newton's method, falling
+// back to bisection where the slope is too flat.
+fn cubic_bezier(x1: f32, y1: f32, x2: f32, y2: f32, x: f32) -> f32 {
+    if x <= 0.0 || x >= 1.0 {
+        return x.max(0.0).min(1.0)
+    }
+
+    let curve = |a: f32, b: f32, t: f32| 3.0 * a * (1.0 - t) * (1.0 - t) * t + 3.0 * b * (1.0 - t) * t * t + t * t * t;
+    let slope = |a: f32, b: f32, t: f32| 3.0 * a * (1.0 - t) * (1.0 - t) + 6.0 * (b - a) * (1.0 - t) * t + 3.0 * (1.0 - b) * t * t;
+
+    let mut t = x;
+
+    for _ in 0..8 {
+        let error = curve(x1, x2, t) - x;
+
+        if error.abs() < 1e-6 {
+            return curve(y1, y2, t)
+        }
+
+        let d = slope(x1, x2, t);
+
+        if d.abs() < 1e-6 {
+            break;
+        }
+
+        t -= error / d;
+    }
+
+    let (mut low, mut high) = (0.0, 1.0);
+
+    t = x;
+
+    for _ in 0..32 {
+        let value = curve(x1, x2, t);
+
+        if (value - x).abs() < 1e-6 {
+            break;
+        }
+
+        if value < x {
+            low = t;
+        } else {
+            high = t;
+        }
+
+        t = (low + high) / 2.0;
+    }
+
+    return curve(y1, y2, t)
+}
+
+fn list<'a>(styles: &'a HashMap<String, Value>, name: &str) -> &'a [Value] {
+    return match styles.get(name) {
+        Some(&Value::List(ref values)) => values,
+        _ => &[],
+    }
+}
+
+// The duration, delay and timing function of the transition-property entry
+// that covers `property`. The last matching entry wins.
+fn transition_parameters(styles: &HashMap<String, Value>, property: &str) -> Option<(f32, f32, EasingFunction)> {
+    let index = list(styles, "transition-property").iter().rposition(|v| match *v {
+        Value::Other(ref name) => name == property || name == "all",
+        _ => false,
+    })?;
+
+    // Custom properties and the transition and animation properties
+    // themselves don't transition with `all`.
+    if property.starts_with("--") || property.starts_with("transition-") || property.starts_with("animation-") {
+        return None
+    }
+
+    let item = |name: &str| {
+        let values = list(styles, name);
+
+        match values.is_empty() {
+            true => None,
+            false => Some(values[index % values.len()].clone()),
+        }
+    };
+
+    let duration = match item("transition-duration") {
+        Some(Value::Time(t)) => t,
+        _ => 0.0,
+    };
+    let delay = match item("transition-delay") {
+        Some(Value::Time(t)) => t,
+        _ => 0.0,
+    };
+    let easing = match item("transition-timing-function") {
+        Some(Value::Easing(e)) => e,
+        _ => EasingFunction::CubicBezier(0.25, 0.1, 0.25, 1.0),
+    };
+
+    return Some((duration, delay, easing))
+}
+
+fn animated_properties(rule: &KeyframesRule) -> Vec<&str> {
+    let mut properties = Vec::new();
+
+    for keyframe in &rule.keyframes {
+        for declaration in &keyframe.declarations {
+            let property = declaration.property.as_str();
+
+            if property != "animation-timing-function" && !properties.contains(&property) {
+                properties.push(property);
+            }
+        }
+    }
+
+    return properties
+}
+
+// The value of `property` at `progress` through the keyframes. Keyframes
+// without the property are skipped, and missing 0% and 100% keyframes take
+// the element's own value.
+fn keyframe_value(
+    rule: &KeyframesRule,
+    property: &str,
+    progress: f32,
+    before: bool,
+    parameters: &AnimationParameters,
+    base: &HashMap<String, Value>,
+    compute: &dyn Fn(&str, &Value) -> Option<Value>,
+) -> Option<Value> {
+    let mut frames: Vec<(f32, Value, EasingFunction)> = Vec::new();
+
+    for keyframe in &rule.keyframes {
+        let easing = keyframe.declarations
+            .iter()
+            .rev()
+            .find(|d| d.property == "animation-timing-function")
+            .and_then(|d| match d.value {
+                Value::List(ref values) => values.first().cloned(),
+                ref value => Some(value.clone()),
+            })
+            .and_then(|v| match v {
+                Value::Easing(e) => Some(e),
+                _ => None,
+            })
+            .unwrap_or(parameters.easing);
+
+        let value = match keyframe.declarations.iter().rev().find(|d| d.property == property) {
+            Some(declaration) => compute(property, &declaration.value),
+            None => continue,
+        };
+
+        let value = match value {
+            Some(value) => value,
+            None => continue,
+        };
+
+        // A later keyframe with the same offset replaces an earlier one.
+        for &offset in &keyframe.offsets {
+            frames.retain(|f| f.0 != offset);
+            frames.push((offset, value.clone(), easing));
+        }
+    }
+
+    frames.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+
+    if frames.first().map_or(true, |f| f.0 > 0.0) {
+        frames.insert(0, (0.0, base.get(property)?.clone(), parameters.easing));
+    }
+
+    if frames.last().map_or(true, |f| f.0 < 1.0) {
+        frames.push((1.0, base.get(property)?.clone(), parameters.easing));
+    }
+
+    let start = frames.iter().rposition(|f| f.0 <= progress).unwrap_or(0);
+    let end = (start + 1).min(frames.len() - 1);
+
+    let (ref from_offset, ref from, easing) = frames[start];
+    let (ref to_offset, ref to, _) = frames[end];
+
+    if start == end || to_offset <= from_offset {
+        return Some(from.clone())
+    }
+
+    let local = easing.apply((progress - from_offset) / (to_offset - from_offset), before);
+
+    // Values that can't be interpolated flip halfway.
+    return interpolate(from, to, local).or_else(|| match local < 0.5 {
+        true => Some(from.clone()),
+        false => Some(to.clone()),
+    })
+}
+
+// The value `progress` of the way from `from` to `to`, or `None` if the
+// values can't be interpolated, like keywords.
+pub fn interpolate(from: &Value, to: &Value, progress: f32) -> Option<Value> {
+    let lerp = |a: f32, b: f32| a + (b - a) * progress;
+
+    return match (from, to) {
+        // The ends are exact, so finished transitions don't leave calc()s.
+        _ if progress == 0.0 && interpolate(from, to, 0.5).is_some() => Some(from.clone()),
+        _ if progress == 1.0 && interpolate(from, to, 0.5).is_some() => Some(to.clone()),
+        (&Value::Length(a, from_unit), &Value::Length(b, to_unit)) if from_unit == to_unit || a == 0.0 || b == 0.0 => {
+            let unit = if a == 0.0 { to_unit } else { from_unit };
+
+            Some(Value::Length(lerp(a, b), unit))
+        },
+        (&Value::Length(..), &Value::Calc(_)) | (&Value::Calc(_), &Value::Length(..))
+            | (&Value::Calc(_), &Value::Calc(_)) | (&Value::Length(..), &Value::Length(..)) =>
+        {
+            Some(Value::Calc(CalcNode::Sum(vec![
+                CalcNode::Scale(1.0 - progress, Box::new(calc_node(from)?)),
+                CalcNode::Scale(progress, Box::new(calc_node(to)?)),
+            ])))
+        },
+        (&Value::Number(a), &Value::Number(b)) => Some(Value::Number(lerp(a, b))),
+        (&Value::Time(a), &Value::Time(b)) => Some(Value::Time(lerp(a, b))),
+        (&Value::Color(ref a), &Value::Color(ref b)) => {
+            // Legacy colors mix in sRGB, and the others in Oklab.
+            let legacy = |space| match space {
+                ColorSpace::Srgb | ColorSpace::Hsl | ColorSpace::Hwb => true,
+                _ => false,
+            };
+            let space = match legacy(a.space()) && legacy(b.space()) {
+                true => ColorSpace::Srgb,
+                false => ColorSpace::Oklab,
+            };
+
+            let mixed = color::mix(space, HueInterpolation::Shorter, a, b, progress);
+
+            // Legacy colors stay legacy, so they keep serializing as rgb().
+            Some(Value::Color(match a.is_legacy() && b.is_legacy() {
+                true => {
+                    let [r, g, b] = mixed.channels();
+
+                    Color::new(r, g, b, mixed.alpha())
+                },
+                false => mixed,
+            }))
+        },
+        (&Value::Transform(ref a), &Value::Transform(ref b)) => interpolate_transforms(a, b, progress).map(Value::Transform),
+        (&Value::List(ref a), &Value::List(ref b)) if a.len() == b.len() => a.iter()
+            .zip(b)
+            .map(|(a, b)| interpolate(a, b, progress))
+            .collect::<Option<Vec<Value>>>()
+            .map(Value::List),
+        _ if from == to => Some(from.clone()),
+        _ => None,
+    }
+}
+
+fn calc_node(value: &Value) -> Option<CalcNode> {
+    return match *value {
+        Value::Length(n, unit) => Some(CalcNode::Length(n, unit)),
+        Value::Calc(ref node) => Some(node.clone()),
+        _ => None,
+    }
+}
+
+// Lists of the same functions interpolate function by function, with the
+// shorter list padded with identity functions. Anything else goes through
+// matrices.
+fn interpolate_transforms(from: &[TransformFunction], to: &[TransformFunction], progress: f32) -> Option<Vec<TransformFunction>> {
+    let mut from = from.to_vec();
+    let mut to = to.to_vec();
+
+    if from.len() < to.len() {
+        from.extend(to[from.len()..].iter().map(identity));
+    } else {
+        to.extend(from[to.len()..].iter().map(identity));
+    }
+
+    let lerp = |a: f32, b: f32| a + (b - a) * progress;
+
+    let pairwise: Option<Vec<TransformFunction>> = from.iter().zip(&to).map(|pair| match pair {
+            (&TransformFunction::Translate(ref x1, ref y1), &TransformFunction::Translate(ref x2, ref y2)) => {
+                Some(TransformFunction::Translate(interpolate(x1, x2, progress)?, interpolate(y1, y2, progress)?))
+            },
+            (&TransformFunction::Scale(x1, y1), &TransformFunction::Scale(x2, y2)) => {
+                Some(TransformFunction::Scale(lerp(x1, x2), lerp(y1, y2)))
+            },
+            (&TransformFunction::Rotate(a), &TransformFunction::Rotate(b)) => Some(TransformFunction::Rotate(lerp(a, b))),
+            (&TransformFunction::Skew(x1, y1), &TransformFunction::Skew(x2, y2)) => {
+                Some(TransformFunction::Skew(lerp(x1, x2), lerp(y1, y2)))
+            },
+            (&TransformFunction::Matrix(a), &TransformFunction::Matrix(b)) => {
+                Some(TransformFunction::Matrix(interpolate_matrices(a, b, progress)))
+            },
+            _ => None,
+        }).collect();
+
+    if pairwise.is_some() {
+        return pairwise
+    }
+
+    let a = to_matrix(&from)?;
+    let b = to_matrix(&to)?;
+
+    return Some(vec![TransformFunction::Matrix(interpolate_matrices(a, b, progress))])
+}
+
+fn identity(function: &TransformFunction) -> TransformFunction {
+    return match *function {
+        TransformFunction::Translate(..) => TransformFunction::Translate(Value::Length(0.0, Unit::Px), Value::Length(0.0, Unit::Px)),
+        TransformFunction::Scale(..) => TransformFunction::Scale(1.0, 1.0),
+        TransformFunction::Rotate(_) => TransformFunction::Rotate(0.0),
+        TransformFunction::Skew(..) => TransformFunction::Skew(0.0, 0.0),
+        TransformFunction::Matrix(_) => TransformFunction::Matrix([1.0, 0.0, 0.0, 1.0, 0.0, 0.0]),
+    }
+}
+
+// The product of the functions as `[a, b, c, d, e, f]`, like matrix().
+// Translations that aren't in px depend on the box, so have no matrix yet.
+fn to_matrix(functions: &[TransformFunction]) -> Option<[f32; 6]> {
+    let mut result = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+    for function in functions {
+        let matrix = match *function {
+            TransformFunction::Translate(Value::Length(x, Unit::Px), Value::Length(y, Unit::Px)) => [1.0, 0.0, 0.0, 1.0, x, y],
+            TransformFunction::Translate(..) => return None,
+            TransformFunction::Scale(x, y) => [x, 0.0, 0.0, y, 0.0, 0.0],
+            TransformFunction::Rotate(angle) => {
+                let (sin, cos) = angle.to_radians().sin_cos();
+
+                [cos, sin, -sin, cos, 0.0, 0.0]
+            },
+            TransformFunction::Skew(x, y) => [1.0, y.to_radians().tan(), x.to_radians().tan(), 1.0, 0.0, 0.0],
+            TransformFunction::Matrix(m) => m,
+        };
+
+        result = multiply(result, matrix);
+    }
+
+    return Some(result)
+}
+
+fn multiply(m: [f32; 6], n: [f32; 6]) -> [f32; 6] {
+    return [
+        m[0] * n[0] + m[2] * n[1],
+        m[1] * n[0] + m[3] * n[1],
+        m[0] * n[2] + m[2] * n[3],
+        m[1] * n[2] + m[3] * n[3],
+        m[0] * n[4] + m[2] * n[5] + m[4],
+        m[1] * n[4] + m[3] * n[5] + m[5],
+    ]
+}
+
+// Splits a matrix into a translation, a rotation in degrees, a skew factor
+// and a scale, applied in that order.
+fn decompose(m: [f32; 6]) -> ([f32; 2], f32, f32, [f32; 2]) {
+    let [a, b, c, d, e, f] = m;
+    let scale_x = (a * a + b * b).sqrt();
+    let angle = b.atan2(a);
+    let (sin, cos) = angle.sin_cos();
+
+    // What's left once the rotation is undone is [[scale_x, p], [0, q]].
+    let p = c * cos + d * sin;
+    let q = -c * sin + d * cos;
+    let skew = if q != 0.0 { p / q } else { 0.0 };
+
+    return ([e, f], angle.to_degrees(), skew, [scale_x, q])
+}
+
+fn recompose(translate: [f32; 2], angle: f32, skew: f32, scale: [f32; 2]) -> [f32; 6] {
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let rotation = [cos, sin, -sin, cos, translate[0], translate[1]];
+
+    return multiply(rotation, [scale[0], 0.0, skew * scale[1], scale[1], 0.0, 0.0])
+}
+
+fn interpolate_matrices(from: [f32; 6], to: [f32; 6], progress: f32) -> [f32; 6] {
+    let lerp = |a: f32, b: f32| a + (b - a) * progress;
+
+    let (t1, mut a1, k1, s1) = decompose(from);
+    let (t2, mut a2, k2, s2) = decompose(to);
+
+    // Rotates the short way around.
+    if (a1 - a2).abs() > 180.0 {
+        if a1 > a2 {
+            a1 -= 360.0;
+        } else {
+            a2 -= 360.0;
+        }
+    }
+
+    return recompose(
+        [lerp(t1[0], t2[0]), lerp(t1[1], t2[1])],
+        lerp(a1, a2),
+        lerp(k1, k2),
+        [lerp(s1[0], s2[0]), lerp(s1[1], s2[1])],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css_parser::CssParser;
+    use crate::html_parser::HtmlParser;
+    use crate::style::{
+        StyledNode,
+        Viewport,
+    };
+
+    fn parameters(duration: f32, delay: f32, iterations: f32, direction: &str, fill_mode: &str) -> AnimationParameters {
+        return AnimationParameters {
+            duration,
+            easing: EasingFunction::Linear,
+            delay,
+            iterations,
+            direction: direction.to_string(),
+            fill_mode: fill_mode.to_string(),
+            paused: false,
+        }
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-4, "{} is not {}", actual, expected);
+    }
+
+    #[test]
+    fn progress_goes_through_each_iteration() {
+        let animation = parameters(2.0, 1.0, 2.0, "normal", "none");
+
+        assert_eq!(animation.progress(0.5), None);
+        assert_eq!(animation.progress(2.0), Some((0.5, false)));
+        assert_eq!(animation.progress(4.0), Some((0.5, false)));
+        assert_eq!(animation.progress(5.0), None);
+    }
+
+    #[test]
+    fn directions_reverse_iterations() {
+        assert_eq!(parameters(1.0, 0.0, 3.0, "reverse", "none").progress(0.25), Some((0.75, false)));
+        assert_eq!(parameters(1.0, 0.0, 3.0, "alternate", "none").progress(1.25), Some((0.75, false)));
+        assert_eq!(parameters(1.0, 0.0, 3.0, "alternate-reverse", "none").progress(1.25), Some((0.25, false)));
+    }
+
+    #[test]
+    fn fill_modes_hold_the_ends() {
+        assert_eq!(parameters(1.0, 1.0, 1.0, "normal", "backwards").progress(0.5), Some((0.0, true)));
+        assert_eq!(parameters(1.0, 1.0, 1.0, "normal", "forwards").progress(0.5), None);
+        assert_eq!(parameters(1.0, 0.0, 1.0, "normal", "forwards").progress(3.0), Some((1.0, false)));
+        assert_eq!(parameters(1.0, 0.0, 2.0, "alternate", "both").progress(3.0), Some((0.0, false)));
+        assert_eq!(parameters(1.0, 0.0, 1.5, "normal", "forwards").progress(3.0), Some((0.5, false)));
+    }
+
+    #[test]
+    fn zero_duration_infinite_animations_end_at_a_number() {
+        assert_eq!(parameters(0.0, 0.0, f32::INFINITY, "normal", "forwards").progress(1.0), Some((1.0, false)));
+        assert_eq!(parameters(0.0, 0.0, f32::INFINITY, "alternate", "both").progress(1.0), Some((1.0, false)));
+        assert_eq!(parameters(0.0, 0.0, f32::INFINITY, "reverse", "forwards").progress(1.0), Some((0.0, false)));
+        assert_eq!(parameters(0.0, 0.0, f32::INFINITY, "normal", "none").progress(1.0), None);
+    }
+
+    #[test]
+    fn steps_jump_at_their_positions() {
+        let steps = |n, position, progress, before| EasingFunction::Steps(n, position).apply(progress, before);
+
+        assert_close(steps(4, StepPosition::JumpEnd, 0.3, false), 0.25);
+        assert_close(steps(4, StepPosition::JumpEnd, 1.0, false), 1.0);
+        assert_close(steps(4, StepPosition::JumpStart, 0.0, false), 0.25);
+        assert_close(steps(4, StepPosition::JumpStart, 0.3, false), 0.5);
+        assert_close(steps(4, StepPosition::JumpStart, 0.0, true), 0.0);
+        assert_close(steps(4, StepPosition::JumpNone, 0.5, false), 2.0 / 3.0);
+        assert_close(steps(3, StepPosition::JumpBoth, 0.0, false), 0.25);
+        assert_close(steps(3, StepPosition::JumpBoth, 1.0, false), 1.0);
+    }
+
+    #[test]
+    fn cubic_bezier_solves_for_x() {
+        assert_close(cubic_bezier(0.25, 0.1, 0.25, 1.0, 0.5), 0.8024);
+        assert_close(cubic_bezier(0.42, 0.0, 0.58, 1.0, 0.5), 0.5);
+        assert_close(cubic_bezier(0.0, 0.0, 1.0, 1.0, 0.3), 0.3);
+        assert_close(cubic_bezier(0.42, 0.0, 1.0, 1.0, 1.0), 1.0);
+    }
+
+    #[test]
+    fn values_interpolate() {
+        assert_eq!(interpolate(&Value::Length(10.0, Unit::Px), &Value::Length(20.0, Unit::Px), 0.25), Some(Value::Length(12.5, Unit::Px)));
+        assert_eq!(interpolate(&Value::Number(0.0), &Value::Number(1.0), 0.5), Some(Value::Number(0.5)));
+        assert_eq!(interpolate(&Value::Length(1.0, Unit::Em), &Value::Length(10.0, Unit::Px), 0.0), Some(Value::Length(1.0, Unit::Em)));
+        assert_eq!(
+            interpolate(&Value::Length(1.0, Unit::Em), &Value::Length(10.0, Unit::Px), 0.5).map(|v| v.to_string()).as_deref(),
+            Some("calc(0.5 * 1em + 0.5 * 10px)"),
+        );
+        assert_eq!(interpolate(&Value::Other(String::from("a")), &Value::Other(String::from("b")), 0.5), None);
+    }
+
+    // The computed width of the first element in `html` at `time`.
+    fn width_at(timeline: &mut Timeline, html: &[crate::dom::Node], css: &str, time: f32) -> Option<String> {
+        let sheet = CssParser::new(css).parse_stylesheet();
+
+        timeline.set_time(time);
+
+        return StyledNode::with_timeline(&html[0], &sheet, &Viewport::default(), timeline).value("width").map(Value::to_string)
+    }
+
+    #[test]
+    fn timeline_runs_animations() {
+        let html = HtmlParser::new("<p></p>").parse_nodes();
+        let css = "@keyframes grow { from { width: 0px } to { width: 100px } } p { animation-name: grow; animation-duration: 2s; animation-timing-function: linear }";
+        let mut timeline = Timeline::new();
+
+        assert_eq!(width_at(&mut timeline, &html, css, 1.0).as_deref(), Some("0px"));
+        assert_eq!(width_at(&mut timeline, &html, css, 1.5).as_deref(), Some("25px"));
+        assert_eq!(width_at(&mut timeline, &html, css, 4.0), None);
+    }
+
+    #[test]
+    fn timeline_runs_transitions() {
+        let html = HtmlParser::new("<p></p>").parse_nodes();
+        let transition = "p { transition-property: width; transition-duration: 1s; transition-timing-function: linear; width: ";
+        let mut timeline = Timeline::new();
+
+        assert_eq!(width_at(&mut timeline, &html, &format!("{}0px }}", transition), 0.0).as_deref(), Some("0px"));
+        assert_eq!(width_at(&mut timeline, &html, &format!("{}100px }}", transition), 1.0).as_deref(), Some("0px"));
+        assert!(timeline.has_running_transitions());
+        assert_eq!(width_at(&mut timeline, &html, &format!("{}100px }}", transition), 1.25).as_deref(), Some("25px"));
+        assert_eq!(width_at(&mut timeline, &html, &format!("{}100px }}", transition), 2.0).as_deref(), Some("100px"));
+        assert!(!timeline.has_running_transitions());
+    }
+
+    #[test]
+    fn shorthands_run_animations_and_transitions() {
+        let html = HtmlParser::new("<p></p>").parse_nodes();
+        let css = "@keyframes grow { from { width: 0px } to { width: 100px } } p { animation: grow 2s linear 1s }";
+        let mut timeline = Timeline::new();
+
+        assert_eq!(width_at(&mut timeline, &html, css, 0.0), None);
+        assert_eq!(width_at(&mut timeline, &html, css, 2.0).as_deref(), Some("50px"));
+
+        let transition = "p { --t: width 1s linear; transition: var(--t); width: ";
+        let mut timeline = Timeline::new();
+
+        assert_eq!(width_at(&mut timeline, &html, &format!("{}0px }}", transition), 0.0).as_deref(), Some("0px"));
+        assert_eq!(width_at(&mut timeline, &html, &format!("{}100px }}", transition), 1.0).as_deref(), Some("0px"));
+        assert_eq!(width_at(&mut timeline, &html, &format!("{}100px }}", transition), 1.5).as_deref(), Some("50px"));
+    }
+
+    #[test]
+    fn timelines_follow_elements_not_addresses() {
+        let transition = "p { transition: width 1s linear; width: ";
+        let mut timeline = Timeline::new();
+        let html = HtmlParser::new("<p></p>").parse_nodes();
+
+        width_at(&mut timeline, &html, &format!("{}0px }}", transition), 0.0);
+        width_at(&mut timeline, &html, &format!("{}100px }}", transition), 1.0);
+
+        // The same element carries on after it moves.
+        let moved = html.clone();
+        drop(html);
+
+        assert_eq!(width_at(&mut timeline, &moved, &format!("{}100px }}", transition), 1.5).as_deref(), Some("50px"));
+
+        // A new element doesn't, and the old one's transition is dropped.
+        let other = HtmlParser::new("<p></p>").parse_nodes();
+
+        assert_eq!(width_at(&mut timeline, &other, &format!("{}100px }}", transition), 1.5).as_deref(), Some("100px"));
+        assert!(!timeline.has_running_transitions());
+    }
+}
@@ -0,0 +1,480 @@
+use crate::css::{
+    Color,
+    ColorSpace,
+};
+
+type Matrix = [[f32; 3]; 3];
+
+const LIN_SRGB_TO_XYZ: Matrix = [
+    [0.41239079926595934, 0.357584339383878, 0.1804807884018343],
+    [0.21263900587151027, 0.715168678767756, 0.07219231536073371],
+    [0.01933081871559182, 0.11919477979462598, 0.9505321522496607],
+];
+
+const XYZ_TO_LIN_SRGB: Matrix = [
+    [3.2409699419045226, -1.537383177570094, -0.4986107602930034],
+    [-0.9692436362808796, 1.8759675015077202, 0.04155505740717559],
+    [0.05563007969699366, -0.20397695888897652, 1.0569715142428786],
+];
+
+const LIN_P3_TO_XYZ: Matrix = [
+    [0.4865709486482162, 0.26566769316909306, 0.1982172852343625],
+    [0.2289745640697488, 0.6917385218365064, 0.079286914093745],
+    [0.0, 0.04511338185890264, 1.043944368900976],
+];
+
+const XYZ_TO_LIN_P3: Matrix = [
+    [2.493496911941425, -0.9313836179191239, -0.40271078445071684],
+    [-0.8294889695615747, 1.7626640603183463, 0.023624685841943577],
+    [0.03584583024378447, -0.07617238926804182, 0.9568845240076872],
+];
+
+const LIN_2020_TO_XYZ: Matrix = [
+    [0.6369580483012914, 0.14461690358620832, 0.1688809751641721],
+    [0.2627002120112671, 0.6779980715188708, 0.05930171646986196],
+    [0.0, 0.028072693049087428, 1.060985057710791],
+];
+
+const XYZ_TO_LIN_2020: Matrix = [
+    [1.7166511879712674, -0.35567078377639233, -0.25336628137365974],
+    [-0.6666843518324892, 1.6164812366349395, 0.01576854581391113],
+    [0.017639857445310783, -0.042770613257808524, 0.9421031212354738],
+];
+
+const D65_TO_D50: Matrix = [
+    [1.0479298208405488, 0.022946793341019088, -0.05019222954313557],
+    [0.029627815688159344, 0.990434484573249, -0.01707382502938514],
+    [-0.009243058152591178, 0.015055144896577895, 0.7518742899580008],
+];
+
+const D50_TO_D65: Matrix = [
+    [0.9554734527042182, -0.023098536874261423, 0.0632593086610217],
+    [-0.028369706963208136, 1.0099954580058226, 0.021041398966943008],
+    [0.012314001688319899, -0.020507696433477912, 1.3303659366080753],
+];
+
+const XYZ_TO_LMS: Matrix = [
+    [0.8190224379967030, 0.3619062600528904, -0.1288737815209879],
+    [0.0329836539323885, 0.9292868615863434, 0.0361446663506424],
+    [0.0481771893596242, 0.2642395317527308, 0.6335478284694309],
+];
+
+const LMS_TO_OKLAB: Matrix = [
+    [0.2104542683093140, 0.7936177747023054, -0.0040720430116193],
+    [1.9779985324311684, -2.4285922420485799, 0.4505937096174110],
+    [0.0259040424655478, 0.7827717124575296, -0.8086757549230774],
+];
+
+const OKLAB_TO_LMS: Matrix = [
+    [1.0, 0.3963377773761749, 0.2158037573099136],
+    [1.0, -0.1055613458156586, -0.0638541728258133],
+    [1.0, -0.0894841775298119, -1.2914855480194092],
+];
+
+const LMS_TO_XYZ: Matrix = [
+    [1.2268798758459243, -0.5578149944602171, 0.2813910456659647],
+    [-0.0405757452148008, 1.1122868032803170, -0.0717110580655164],
+    [-0.0763729366746601, -0.4214933324022432, 1.5869240198367816],
+];
+
+const D50_WHITE: [f32; 3] = [0.3457 / 0.3585, 1.0, (1.0 - 0.3457 - 0.3585) / 0.3585];
+
+const LAB_KAPPA: f32 = 24389.0 / 27.0;
+const LAB_EPSILON: f32 = 216.0 / 24389.0;
+
+// Just noticeable difference used by the CSS gamut mapping algorithm.
+const GAMUT_JND: f32 = 0.02;
+const GAMUT_EPSILON: f32 = 0.0001;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum HueInterpolation {
+    Shorter,
+    Longer,
+    Increasing,
+    Decreasing,
+}
+
+impl Color {
+    pub fn convert_to(&self, space: ColorSpace) -> Color {
+        if self.space() == space {
+            return self.clone()
+        }
+
+        let xyz = to_xyz_d65(self.space(), self.channels());
+
+        return Color::in_space(space, from_xyz_d65(space, xyz), self.alpha())
+    }
+
+    // The color to paint with: converted to sRGB and, if it falls outside
+    // of it, mapped into gamut by reducing its oklch chroma.
+    pub fn to_srgb(&self) -> Color {
+        let srgb = self.convert_to(ColorSpace::Srgb);
+
+        if in_srgb_gamut(srgb.channels()) {
+            return clip(&srgb)
+        }
+
+        let origin = self.convert_to(ColorSpace::Oklch);
+        let [lightness, chroma, hue] = origin.channels();
+
+        if lightness >= 1.0 {
+            return Color::new(1.0, 1.0, 1.0, self.alpha())
+        } else if lightness <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0, self.alpha())
+        }
+
+        let mut current = origin.clone();
+        let mut clipped = clip(&current.convert_to(ColorSpace::Srgb));
+
+        if delta_e_ok(&clipped, &current) < GAMUT_JND {
+            return clipped
+        }
+
+        let mut min = 0.0;
+        let mut max = chroma;
+        let mut min_in_gamut = true;
+
+        while max - min > GAMUT_EPSILON {
+            let mid = (min + max) / 2.0;
+
+            current = Color::in_space(ColorSpace::Oklch, [lightness, mid, hue], self.alpha());
+
+            let srgb = current.convert_to(ColorSpace::Srgb);
+
+            if min_in_gamut && in_srgb_gamut(srgb.channels()) {
+                min = mid;
+
+                continue;
+            }
+
+            clipped = clip(&srgb);
+
+            let error = delta_e_ok(&clipped, &current);
+
+            if error < GAMUT_JND {
+                if GAMUT_JND - error < GAMUT_EPSILON {
+                    return clipped
+                }
+
+                min_in_gamut = false;
+                min = mid;
+            } else {
+                max = mid;
+            }
+        }
+
+        return clipped
+    }
+}
+
+// Interpolates `weight` of the way from `from` to `to` in `space` using
+// premultiplied alpha, as `color-mix()` does.
+pub fn mix(
+    space: ColorSpace,
+    hue_interpolation: HueInterpolation,
+    from: &Color,
+    to: &Color,
+    weight: f32,
+) -> Color {
+    let from = from.convert_to(space);
+    let to = to.convert_to(space);
+    let mut start = from.channels();
+    let mut end = to.channels();
+
+    let hue_index = hue_index(space);
+
+    if let Some(h) = hue_index {
+        // An achromatic color has a powerless hue, so take the other one's.
+        if is_achromatic(space, start) {
+            start[h] = end[h];
+        } else if is_achromatic(space, end) {
+            end[h] = start[h];
+        }
+
+        let (a, b) = fix_hues(start[h], end[h], hue_interpolation);
+
+        start[h] = a;
+        end[h] = b;
+    }
+
+    let alpha = from.alpha() * (1.0 - weight) + to.alpha() * weight;
+    let mut channels = [0.0; 3];
+
+    for i in 0..3 {
+        channels[i] = if Some(i) == hue_index {
+            (start[i] * (1.0 - weight) + end[i] * weight).rem_euclid(360.0)
+        } else {
+            let mixed = start[i] * from.alpha() * (1.0 - weight) + end[i] * to.alpha() * weight;
+
+            if alpha == 0.0 { mixed } else { mixed / alpha }
+        };
+    }
+
+    let result = Color::in_space(space, channels, alpha);
+
+    return match space {
+        ColorSpace::Hsl | ColorSpace::Hwb => result.convert_to(ColorSpace::Srgb),
+        _ => result,
+    }
+}
+
+pub fn hsl_to_rgb(hue: f32, saturation: f32, lightness: f32) -> (f32, f32, f32) {
+    let channel = |n: f32| {
+        let k = (n + hue / 30.0) % 12.0;
+        let a = saturation * lightness.min(1.0 - lightness);
+
+        lightness - a * (k - 3.0).min(9.0 - k).min(1.0).max(-1.0)
+    };
+
+    return (channel(0.0), channel(8.0), channel(4.0))
+}
+
+pub fn hwb_to_rgb(hue: f32, whiteness: f32, blackness: f32) -> (f32, f32, f32) {
+    if whiteness + blackness >= 1.0 {
+        let gray = whiteness / (whiteness + blackness);
+
+        return (gray, gray, gray)
+    }
+
+    let (r, g, b) = hsl_to_rgb(hue, 1.0, 0.5);
+    let scale = 1.0 - whiteness - blackness;
+
+    return (r * scale + whiteness, g * scale + whiteness, b * scale + whiteness)
+}
+
+fn rgb_to_hsl(rgb: [f32; 3]) -> [f32; 3] {
+    let max = rgb[0].max(rgb[1]).max(rgb[2]);
+    let min = rgb[0].min(rgb[1]).min(rgb[2]);
+    let lightness = (min + max) / 2.0;
+    let delta = max - min;
+
+    if delta == 0.0 {
+        return [0.0, 0.0, lightness]
+    }
+
+    let saturation = if lightness == 0.0 || lightness == 1.0 {
+        0.0
+    } else {
+        (max - lightness) / lightness.min(1.0 - lightness)
+    };
+
+    let hue = if max == rgb[0] {
+        (rgb[1] - rgb[2]) / delta + if rgb[1] < rgb[2] { 6.0 } else { 0.0 }
+    } else if max == rgb[1] {
+        (rgb[2] - rgb[0]) / delta + 2.0
+    } else {
+        (rgb[0] - rgb[1]) / delta + 4.0
+    };
+
+    return [hue * 60.0, saturation, lightness]
+}
+
+fn rgb_to_hwb(rgb: [f32; 3]) -> [f32; 3] {
+    let hsl = rgb_to_hsl(rgb);
+    let whiteness = rgb[0].min(rgb[1]).min(rgb[2]);
+    let blackness = 1.0 - rgb[0].max(rgb[1]).max(rgb[2]);
+
+    return [hsl[0], whiteness, blackness]
+}
+
+fn to_xyz_d65(space: ColorSpace, c: [f32; 3]) -> [f32; 3] {
+    return match space {
+        ColorSpace::Srgb => multiply(&LIN_SRGB_TO_XYZ, map(c, srgb_to_linear)),
+        ColorSpace::SrgbLinear => multiply(&LIN_SRGB_TO_XYZ, c),
+        ColorSpace::DisplayP3 => multiply(&LIN_P3_TO_XYZ, map(c, srgb_to_linear)),
+        ColorSpace::Rec2020 => multiply(&LIN_2020_TO_XYZ, map(c, rec2020_to_linear)),
+        ColorSpace::Lab => multiply(&D50_TO_D65, lab_to_xyz_d50(c)),
+        ColorSpace::Lch => multiply(&D50_TO_D65, lab_to_xyz_d50(polar_to_rectangular(c))),
+        ColorSpace::Oklab => oklab_to_xyz(c),
+        ColorSpace::Oklch => oklab_to_xyz(polar_to_rectangular(c)),
+        ColorSpace::XyzD50 => multiply(&D50_TO_D65, c),
+        ColorSpace::XyzD65 => c,
+        ColorSpace::Hsl => {
+            let (r, g, b) = hsl_to_rgb(c[0], c[1], c[2]);
+
+            to_xyz_d65(ColorSpace::Srgb, [r, g, b])
+        },
+        ColorSpace::Hwb => {
+            let (r, g, b) = hwb_to_rgb(c[0], c[1], c[2]);
+
+            to_xyz_d65(ColorSpace::Srgb, [r, g, b])
+        },
+    }
+}
+
+fn from_xyz_d65(space: ColorSpace, xyz: [f32; 3]) -> [f32; 3] {
+    return match space {
+        ColorSpace::Srgb => map(multiply(&XYZ_TO_LIN_SRGB, xyz), linear_to_srgb),
+        ColorSpace::SrgbLinear => multiply(&XYZ_TO_LIN_SRGB, xyz),
+        ColorSpace::DisplayP3 => map(multiply(&XYZ_TO_LIN_P3, xyz), linear_to_srgb),
+        ColorSpace::Rec2020 => map(multiply(&XYZ_TO_LIN_2020, xyz), linear_to_rec2020),
+        ColorSpace::Lab => xyz_d50_to_lab(multiply(&D65_TO_D50, xyz)),
+        ColorSpace::Lch => rectangular_to_polar(xyz_d50_to_lab(multiply(&D65_TO_D50, xyz))),
+        ColorSpace::Oklab => xyz_to_oklab(xyz),
+        ColorSpace::Oklch => rectangular_to_polar(xyz_to_oklab(xyz)),
+        ColorSpace::XyzD50 => multiply(&D65_TO_D50, xyz),
+        ColorSpace::XyzD65 => xyz,
+        ColorSpace::Hsl => rgb_to_hsl(from_xyz_d65(ColorSpace::Srgb, xyz)),
+        ColorSpace::Hwb => rgb_to_hwb(from_xyz_d65(ColorSpace::Srgb, xyz)),
+    }
+}
+
+fn hue_index(space: ColorSpace) -> Option<usize> {
+    return match space {
+        ColorSpace::Lch | ColorSpace::Oklch => Some(2),
+        ColorSpace::Hsl | ColorSpace::Hwb => Some(0),
+        _ => None,
+    }
+}
+
+fn is_achromatic(space: ColorSpace, c: [f32; 3]) -> bool {
+    return match space {
+        ColorSpace::Lch | ColorSpace::Oklch | ColorSpace::Hsl => c[1].abs() < GAMUT_EPSILON,
+        ColorSpace::Hwb => c[1] + c[2] >= 1.0 - GAMUT_EPSILON,
+        _ => false,
+    }
+}
+
+fn fix_hues(mut a: f32, mut b: f32, method: HueInterpolation) -> (f32, f32) {
+    let diff = b - a;
+
+    match method {
+        HueInterpolation::Shorter => if diff > 180.0 {
+            a += 360.0;
+        } else if diff < -180.0 {
+            b += 360.0;
+        },
+        HueInterpolation::Longer => if diff > 0.0 && diff < 180.0 {
+            a += 360.0;
+        } else if diff > -180.0 && diff <= 0.0 {
+            b += 360.0;
+        },
+        HueInterpolation::Increasing => if b < a {
+            b += 360.0;
+        },
+        HueInterpolation::Decreasing => if a < b {
+            a += 360.0;
+        },
+    }
+
+    return (a, b)
+}
+
+fn in_srgb_gamut(c: [f32; 3]) -> bool {
+    return c.iter().all(|v| *v >= -GAMUT_EPSILON && *v <= 1.0 + GAMUT_EPSILON)
+}
+
+fn clip(color: &Color) -> Color {
+    return Color::in_space(
+        color.space(),
+        map(color.channels(), |v| v.max(0.0).min(1.0)),
+        color.alpha(),
+    )
+}
+
+fn delta_e_ok(a: &Color, b: &Color) -> f32 {
+    let a = a.convert_to(ColorSpace::Oklab).channels();
+    let b = b.convert_to(ColorSpace::Oklab).channels();
+
+    return ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+}
+
+fn srgb_to_linear(v: f32) -> f32 {
+    if v.abs() <= 0.04045 {
+        return v / 12.92
+    }
+
+    return v.signum() * ((v.abs() + 0.055) / 1.055).powf(2.4)
+}
+
+fn linear_to_srgb(v: f32) -> f32 {
+    if v.abs() <= 0.0031308 {
+        return v * 12.92
+    }
+
+    return v.signum() * (1.055 * v.abs().powf(1.0 / 2.4) - 0.055)
+}
+
+fn rec2020_to_linear(v: f32) -> f32 {
+    let alpha = 1.09929682680944;
+    let beta = 0.018053968510807;
+
+    if v.abs() < beta * 4.5 {
+        return v / 4.5
+    }
+
+    return v.signum() * ((v.abs() + alpha - 1.0) / alpha).powf(1.0 / 0.45)
+}
+
+fn linear_to_rec2020(v: f32) -> f32 {
+    let alpha = 1.09929682680944;
+    let beta = 0.018053968510807;
+
+    if v.abs() <= beta {
+        return v * 4.5
+    }
+
+    return v.signum() * (alpha * v.abs().powf(0.45) - (alpha - 1.0))
+}
+
+fn lab_to_xyz_d50(lab: [f32; 3]) -> [f32; 3] {
+    let f1 = (lab[0] + 16.0) / 116.0;
+    let f0 = lab[1] / 500.0 + f1;
+    let f2 = f1 - lab[2] / 200.0;
+
+    let x = if f0.powi(3) > LAB_EPSILON { f0.powi(3) } else { (116.0 * f0 - 16.0) / LAB_KAPPA };
+    let y = if lab[0] > LAB_KAPPA * LAB_EPSILON { f1.powi(3) } else { lab[0] / LAB_KAPPA };
+    let z = if f2.powi(3) > LAB_EPSILON { f2.powi(3) } else { (116.0 * f2 - 16.0) / LAB_KAPPA };
+
+    return [x * D50_WHITE[0], y * D50_WHITE[1], z * D50_WHITE[2]]
+}
+
+fn xyz_d50_to_lab(xyz: [f32; 3]) -> [f32; 3] {
+    let f = |v: f32| if v > LAB_EPSILON { v.cbrt() } else { (LAB_KAPPA * v + 16.0) / 116.0 };
+
+    let f0 = f(xyz[0] / D50_WHITE[0]);
+    let f1 = f(xyz[1] / D50_WHITE[1]);
+    let f2 = f(xyz[2] / D50_WHITE[2]);
+
+    return [116.0 * f1 - 16.0, 500.0 * (f0 - f1), 200.0 * (f1 - f2)]
+}
+
+fn oklab_to_xyz(oklab: [f32; 3]) -> [f32; 3] {
+    let lms = map(multiply(&OKLAB_TO_LMS, oklab), |v| v.powi(3));
+
+    return multiply(&LMS_TO_XYZ, lms)
+}
+
+fn xyz_to_oklab(xyz: [f32; 3]) -> [f32; 3] {
+    let lms = map(multiply(&XYZ_TO_LMS, xyz), f32::cbrt);
+
+    return multiply(&LMS_TO_OKLAB, lms)
+}
+
+fn polar_to_rectangular(lch: [f32; 3]) -> [f32; 3] {
+    let hue = lch[2].to_radians();
+
+    return [lch[0], lch[1] * hue.cos(), lch[1] * hue.sin()]
+}
+
+fn rectangular_to_polar(lab: [f32; 3]) -> [f32; 3] {
+    let chroma = (lab[1] * lab[1] + lab[2] * lab[2]).sqrt();
+    let hue = lab[2].atan2(lab[1]).to_degrees().rem_euclid(360.0);
+
+    return [lab[0], chroma, hue]
+}
+
+fn multiply(m: &Matrix, v: [f32; 3]) -> [f32; 3] {
+    return [
+        m[0][0] * v[0] + m[0][1] * v[1] + m[0][2] * v[2],
+        m[1][0] * v[0] + m[1][1] * v[1] + m[1][2] * v[2],
+        m[2][0] * v[0] + m[2][1] * v[1] + m[2][2] * v[2],
+    ]
+}
+
+fn map<F>(v: [f32; 3], f: F) -> [f32; 3]
+where
+    F: Fn(f32) -> f32,
+{
+    return [f(v[0]), f(v[1]), f(v[2])]
+}
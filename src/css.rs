@@ -3,42 +3,422 @@ use std::default::Default;
 
 #[derive(PartialEq)]
 pub struct Stylesheet {
-    pub rules: Vec<Rule>,
+    pub rules: Vec<CssRule>,
+    pub origin: Origin,
+}
+
+// In order of precedence for normal declarations.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+pub enum Origin {
+    UserAgent,
+    User,
+    Author,
+}
+
+#[derive(PartialEq)]
+pub enum CssRule {
+    Style(Rule),
+    Media(MediaRule),
+    Import(ImportRule),
+    Supports(SupportsRule),
+    Layer(LayerRule),
+    // `@layer a, b;`, which only declares the order of the layers.
+    LayerStatement(Vec<String>),
+    FontFace(FontFaceRule),
+    Keyframes(KeyframesRule),
+    Container(ContainerRule),
 }
 
 #[derive(PartialEq)]
 pub struct Rule {
     pub selectors: Vec<Selector>,
     pub declarations: Vec<Declaration>,
+    // Nested style rules and conditional rules. Their selectors contain `&`
+    // for this rule's selectors.
+    pub rules: Vec<CssRule>,
+}
+
+#[derive(PartialEq)]
+pub struct ImportRule {
+    pub url: String,
+    pub layer: Option<ImportLayer>,
+    pub supports: Option<SupportsCondition>,
+    pub media: Vec<MediaQuery>,
+    // Filled in once the import has been loaded.
+    pub stylesheet: Option<Stylesheet>,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum ImportLayer {
+    Anonymous,
+    Named(String),
+}
+
+#[derive(PartialEq, Debug)]
+pub struct LayerRule {
+    // `None` for an anonymous layer.
+    pub name: Option<String>,
+    pub rules: Vec<CssRule>,
+}
+
+// The descriptors of an @font-face rule. Ranges are `(min, max)`, and equal
+// when a single value is given.
+#[derive(PartialEq, Clone, Debug)]
+pub struct FontFaceRule {
+    pub family: String,
+    // Tried in order until one loads.
+    pub sources: Vec<FontSource>,
+    pub weight: (f32, f32),
+    pub style: FontStyle,
+    // In percent of the normal width.
+    pub stretch: (f32, f32),
+    // Inclusive ranges of code points.
+    pub unicode_range: Vec<(u32, u32)>,
+    pub display: FontDisplay,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum FontSource {
+    // The full or PostScript name of an installed font.
+    Local(String),
+    Url {
+        url: String,
+        format: Option<String>,
+    },
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum FontStyle {
+    Normal,
+    Italic,
+    // A range of angles in degrees.
+    Oblique(f32, f32),
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum FontDisplay {
+    Auto,
+    Block,
+    Swap,
+    Fallback,
+    Optional,
+}
+
+#[derive(PartialEq)]
+pub struct KeyframesRule {
+    pub name: String,
+    pub keyframes: Vec<Keyframe>,
+}
+
+#[derive(PartialEq)]
+pub struct Keyframe {
+    // Between 0 and 1, for `from`, `to` and percentages.
+    pub offsets: Vec<f32>,
+    pub declarations: Vec<Declaration>,
+}
+
+#[derive(PartialEq, Debug)]
+pub struct SupportsRule {
+    pub condition: SupportsCondition,
+    pub rules: Vec<CssRule>,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum SupportsCondition {
+    Declaration(String, String),
+    Selector(String),
+    Not(Box<SupportsCondition>),
+    And(Vec<SupportsCondition>),
+    Or(Vec<SupportsCondition>),
+    // Anything else in parentheses, which is never supported.
+    Unknown(String),
+}
+
+// `@container name (condition)`, where either the name or the condition may
+// be missing. Size queries share the grammar of media conditions.
+#[derive(PartialEq, Debug)]
+pub struct ContainerRule {
+    pub name: Option<String>,
+    pub condition: Option<MediaCondition>,
+    pub rules: Vec<CssRule>,
+}
+
+#[derive(PartialEq, Debug)]
+pub struct MediaRule {
+    pub queries: Vec<MediaQuery>,
+    pub rules: Vec<CssRule>,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct MediaQuery {
+    pub qualifier: Option<MediaQualifier>,
+    pub media_type: Option<String>,
+    pub condition: Option<MediaCondition>,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum MediaQualifier {
+    Not,
+    Only,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum MediaCondition {
+    Feature(MediaFeature),
+    Not(Box<MediaCondition>),
+    And(Vec<MediaCondition>),
+    Or(Vec<MediaCondition>),
+    // Anything else in parentheses, which never matches.
+    Unknown(String),
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum MediaFeature {
+    Boolean(String),
+    Plain(String, MediaValue),
+    // `(start op name op end)`, where either side may be missing.
+    Range {
+        name: String,
+        start: Option<(MediaValue, RangeOp)>,
+        end: Option<(RangeOp, MediaValue)>,
+    },
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum MediaValue {
+    Number(f32),
+    Length(f32, Unit),
+    Ratio(f32, f32),
+    // In dots per px.
+    Resolution(f32),
+    Ident(String),
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum RangeOp {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
 }
 
-#[derive(PartialEq, Eq)]
+pub type Specificity = (u32, u32, u32);
+
+// `simple` holds the compound selectors from left to right, and
+// `combinators[i]` joins `simple[i]` to `simple[i + 1]`.
+#[derive(PartialEq, Eq, Clone)]
 pub struct Selector {
     pub simple: Vec<SimpleSelector>,
     pub combinators: Vec<char>,
+    // Only allowed at the end, so it applies to the last compound.
+    pub pseudo_element: Option<PseudoElement>,
+}
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+pub enum PseudoElement {
+    Before,
+    After,
+    Marker,
+    FirstLine,
+    FirstLetter,
+    Placeholder,
 }
 
-#[derive(PartialEq, Eq)]
+#[derive(PartialEq, Eq, Clone)]
 pub struct SimpleSelector {
     pub tag_name: Option<String>,
     pub id: Option<String>,
     pub classes: Vec<String>,
+    pub attributes: Vec<AttributeSelector>,
+    pub pseudo_classes: Vec<PseudoClass>,
+}
+
+#[derive(PartialEq, Eq, Clone)]
+pub enum PseudoClass {
+    // `&`, the parent rule's selectors in a nested rule. It's replaced by
+    // `:is()` of those selectors before matching; in a rule that isn't
+    // nested it matches the root.
+    Nesting,
+    Root,
+    Empty,
+    FirstChild,
+    LastChild,
+    OnlyChild,
+    FirstOfType,
+    LastOfType,
+    OnlyOfType,
+    // `:nth-child(An+B of S)`; the list is empty without `of`.
+    NthChild(Nth, Vec<Selector>),
+    NthLastChild(Nth, Vec<Selector>),
+    NthOfType(Nth),
+    NthLastOfType(Nth),
+    Not(Vec<Selector>),
+    Is(Vec<Selector>),
+    Where(Vec<Selector>),
+    Has(Vec<RelativeSelector>),
+    Hover,
+    Active,
+    Focus,
+    Checked,
+    Disabled,
+    Enabled,
+    Link,
+    Visited,
+}
+
+// The `An+B` of `:nth-child()` and friends.
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub struct Nth {
+    pub a: i32,
+    pub b: i32,
+}
+
+// A selector inside `:has()`, which starts with a combinator relative to the
+// element being matched (' ' when none is written).
+#[derive(PartialEq, Eq, Clone)]
+pub struct RelativeSelector {
+    pub combinator: char,
+    pub selector: Selector,
+}
+
+#[derive(PartialEq, Eq, Clone)]
+pub struct AttributeSelector {
+    // `None` when there's no `|`, `Some("")` for `[|attr]` and `Some("*")`
+    // for any namespace.
+    pub namespace: Option<String>,
+    pub name: String,
+    pub operator: AttrOperator,
+    pub value: String,
+    pub case: CaseSensitivity,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum AttrOperator {
+    Exists,
+    Equals,
+    // `~=`, one of a whitespace-separated list.
+    Includes,
+    // `|=`, equal or followed by a `-`.
+    DashMatch,
+    Prefix,
+    Suffix,
+    Substring,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum CaseSensitivity {
+    // Up to the document language: HTML compares a few attributes, like
+    // `type`, case-insensitively.
+    Default,
+    // The `i` flag.
+    Insensitive,
+    // The `s` flag.
+    Sensitive,
 }
 
 #[derive(PartialEq)]
 pub struct Declaration {
     pub property: String,
-    pub value: Value
+    pub value: Value,
+    pub important: bool,
 }
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone)]
 pub enum Value {
     Color(Color),
     Length(f32, Unit),
-    Other(String)
+    // A math function that couldn't be reduced to one length when parsed,
+    // like `calc(100% - 2em)`.
+    Calc(CalcNode),
+    Other(String),
+    // The `content` property.
+    Content(Vec<ContentItem>),
+    // `counter-reset`, `counter-set` and `counter-increment`.
+    Counters(Vec<(String, i32)>),
+    // Pairs of open and close quotes.
+    Quotes(Vec<(String, String)>),
+    Number(f32),
+    // In seconds.
+    Time(f32),
+    Easing(EasingFunction),
+    // Empty for `none`.
+    Transform(Vec<TransformFunction>),
+    // A comma-separated list, like the values of the animation properties.
+    List(Vec<Value>),
+    // Kept unparsed until computed-value time: the value of a custom property,
+    // or any value that uses var().
+    Tokens(Vec<Token>),
 }
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone, Debug)]
+pub enum ContentItem {
+    String(String),
+    Attr(String),
+    // counter(name, style)
+    Counter(String, String),
+    // counters(name, separator, style)
+    Counters(String, String, String),
+    OpenQuote,
+    CloseQuote,
+    NoOpenQuote,
+    NoCloseQuote,
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum EasingFunction {
+    Linear,
+    // The x coordinates of both control points are between 0 and 1.
+    CubicBezier(f32, f32, f32, f32),
+    Steps(u32, StepPosition),
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum StepPosition {
+    JumpStart,
+    JumpEnd,
+    JumpNone,
+    JumpBoth,
+}
+
+// The 2D transform functions, with the single-axis forms like translateX()
+// stored as their two-axis equivalents. Angles are in degrees.
+#[derive(PartialEq, Clone, Debug)]
+pub enum TransformFunction {
+    // Lengths, percentages or math functions.
+    Translate(Value, Value),
+    Scale(f32, f32),
+    Rotate(f32),
+    Skew(f32, f32),
+    Matrix([f32; 6]),
+}
+
+// A simplified math expression. Absolute units are converted to px, and
+// numbers never appear next to lengths except as a `Scale` factor.
+#[derive(PartialEq, Clone, Debug)]
+pub enum CalcNode {
+    Number(f32),
+    Length(f32, Unit),
+    Sum(Vec<CalcNode>),
+    Scale(f32, Box<CalcNode>),
+    Min(Vec<CalcNode>),
+    Max(Vec<CalcNode>),
+    Clamp(Box<CalcNode>, Box<CalcNode>, Box<CalcNode>),
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum Token {
+    Ident(String),
+    // A function name, or empty for a plain `(` block.
+    Function(String),
+    CloseParen,
+    Comma,
+    Whitespace,
+    // Numbers, dimensions, strings, hashes and delimiters, as written.
+    Other(String),
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
 pub enum Unit {
     Em,
     Ex,
@@ -58,25 +438,51 @@ pub enum Unit {
     Pct,
 }
 
+// For colors outside sRGB, `r`, `g` and `b` hold the three channels of
+// `space` (e.g. L, C and H for oklch).
 #[derive(PartialEq, Clone)]
 pub struct Color {
     r: f32,
     g: f32,
     b: f32,
     a: f32,
+    space: ColorSpace,
+    // Written with legacy syntax, like a keyword, hex or rgb(), so it's
+    // serialized as rgb().
+    legacy: bool,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum ColorSpace {
+    Srgb,
+    SrgbLinear,
+    DisplayP3,
+    Rec2020,
+    Lab,
+    Lch,
+    Oklab,
+    Oklch,
+    XyzD50,
+    XyzD65,
+    Hsl,
+    Hwb,
 }
 
 
 
 impl Stylesheet {
-    pub fn new(rules: Vec<Rule>) -> Self {
-        return Stylesheet { rules }
+    pub fn new(rules: Vec<CssRule>) -> Self {
+        return Stylesheet { rules, origin: Origin::Author }
+    }
+
+    pub fn with_origin(rules: Vec<CssRule>, origin: Origin) -> Self {
+        return Stylesheet { rules, origin }
     }
 }
 
 impl Default for Stylesheet {
     fn default() -> Self {
-        return Stylesheet { rules: Vec::new() }
+        return Stylesheet::new(Vec::new())
     }
 }
 
@@ -96,9 +502,81 @@ impl fmt::Debug for Stylesheet {
     }
 }
 
+impl fmt::Debug for CssRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CssRule::Style(ref r) => write!(f, "{:?}", r),
+            CssRule::Media(ref m) => {
+                write!(f, "@media {:?} {{\n{}}}", m.queries, indented_rules(&m.rules))
+            },
+            CssRule::Supports(ref s) => {
+                write!(f, "@supports {:?} {{\n{}}}", s.condition, indented_rules(&s.rules))
+            },
+            CssRule::Layer(ref l) => match l.name {
+                Some(ref n) => write!(f, "@layer {} {{\n{}}}", n, indented_rules(&l.rules)),
+                None => write!(f, "@layer {{\n{}}}", indented_rules(&l.rules)),
+            },
+            CssRule::LayerStatement(ref names) => write!(f, "@layer {};", names.join(", ")),
+            CssRule::FontFace(ref font_face) => write!(f, "{:#}", font_face),
+            CssRule::Keyframes(ref keyframes) => write!(f, "{:#}", keyframes),
+            CssRule::Container(ref c) => {
+                write!(f, "@container {:?} {:?} {{\n{}}}", c.name, c.condition, indented_rules(&c.rules))
+            },
+            CssRule::Import(ref i) => {
+                write!(f, "@import {:?}", i.url)?;
+
+                if let Some(ref l) = i.layer {
+                    write!(f, " {:?}", l)?;
+                }
+
+                if let Some(ref s) = i.supports {
+                    write!(f, " supports({:?})", s)?;
+                }
+
+                if !i.media.is_empty() {
+                    write!(f, " {:?}", i.media)?;
+                }
+
+                return match i.stylesheet {
+                    Some(ref s) => {
+                        let mut rule_result = String::new();
+
+                        for line in format!("{:?}", s).lines() {
+                            rule_result.push_str("    ");
+                            rule_result.push_str(line);
+                            rule_result.push_str("\n");
+                        }
+
+                        write!(f, " {{\n{}}}", rule_result)
+                    },
+                    None => write!(f, ";"),
+                }
+            },
+        }
+    }
+}
+
+fn indented_rules(rules: &[CssRule]) -> String {
+    let mut rule_result = String::new();
+
+    for rule in rules {
+        for line in format!("{:?}", rule).lines() {
+            rule_result.push_str("    ");
+            rule_result.push_str(line);
+            rule_result.push_str("\n");
+        }
+    }
+
+    return rule_result
+}
+
 impl Rule {
     pub fn new(selectors: Vec<Selector>, declarations: Vec<Declaration>) -> Self {
-        return Rule { selectors, declarations }
+        return Rule {
+            selectors,
+            declarations,
+            rules: Vec::new(),
+        }
     }
 }
 
@@ -107,6 +585,7 @@ impl Default for Rule {
         return Rule {
             selectors: Vec::new(),
             declarations: Vec::new(),
+            rules: Vec::new(),
         }
     }
 }
@@ -131,7 +610,7 @@ impl fmt::Debug for Rule {
             decl_result.push_str("\n");            
         };
 
-        write!(f, "{} {{\n{}}}", sel_result, decl_result)
+        write!(f, "{} {{\n{}{}}}", sel_result, decl_result, indented_rules(&self.rules))
     }
 }
 
@@ -141,8 +620,38 @@ impl Selector {
         return Selector {
             simple,
             combinators,
+            pseudo_element: None,
+        }
+    }
+
+    pub fn contains_nesting(&self) -> bool {
+        return self.simple.iter().any(|s| s.pseudo_classes.iter().any(PseudoClass::contains_nesting))
+    }
+
+    // Replaces `&` with `:is()` of the parent rule's selectors, which gives
+    // both the matching and the specificity of the nested selector.
+    pub fn resolve_nesting(&self, parent: &[Selector]) -> Selector {
+        let simple = self.simple
+            .iter()
+            .map(|s| SimpleSelector {
+                pseudo_classes: s.pseudo_classes.iter().map(|p| p.resolve_nesting(parent)).collect(),
+                ..s.clone()
+            })
+            .collect();
+
+        return Selector {
+            simple,
+            combinators: self.combinators.clone(),
+            pseudo_element: self.pseudo_element,
         }
     }
+
+    // (ids, classes/attributes/pseudo-classes, types), compared in order.
+    pub fn specificity(&self) -> Specificity {
+        let pseudo_element = (0, 0, self.pseudo_element.iter().count() as u32);
+
+        return self.simple.iter().fold(pseudo_element, |total, simple| add_specificity(total, simple.specificity()))
+    }
 }
 
 
@@ -151,6 +660,7 @@ impl Default for Selector {
         return Selector {
             simple: Vec::new(),
             combinators: Vec::new(),
+            pseudo_element: None,
         }
     }
 }
@@ -158,27 +668,33 @@ impl Default for Selector {
 
 impl fmt::Debug for Selector {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut result = String::new();
-
-        for sel in &self.simple {
-            if result.len() > 0 {
-                result.push_str(", ");
-            }
-
-            result.push_str(&format!("{:?}", sel));
-        };
-
-        write!(f, "{}", result)
+        return write!(f, "{:#}", self)
     }
 }
 
 
 impl SimpleSelector {
+    pub fn specificity(&self) -> Specificity {
+        let mut specificity = (
+            self.id.iter().count() as u32,
+            (self.classes.len() + self.attributes.len()) as u32,
+            self.tag_name.iter().count() as u32,
+        );
+
+        for pseudo_class in &self.pseudo_classes {
+            specificity = add_specificity(specificity, pseudo_class.specificity());
+        }
+
+        return specificity
+    }
+
     pub fn new(tag_name: Option<String>, id: Option<String>, classes: Vec<String>) -> Self {
         return SimpleSelector {
             tag_name,
             id,
             classes,
+            attributes: Vec::new(),
+            pseudo_classes: Vec::new(),
         }
     }
 }
@@ -189,34 +705,105 @@ impl Default for SimpleSelector {
             tag_name: None,
             id: None,
             classes: Vec::new(),
+            attributes: Vec::new(),
+            pseudo_classes: Vec::new(),
         }
     }
 }
 
 
-impl fmt::Debug for SimpleSelector {
+impl fmt::Debug for AttributeSelector {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut result = String::new();
+        return write!(f, "{:#}", self)
+    }
+}
 
-        match self.tag_name {
-            Some(ref t) => result.push_str(t),
-            None => {},
-        };
-        
-        match self.id {
-            Some(ref s) => {
-                result.push('#');
-                result.push_str(s);
+
+impl PseudoClass {
+    // :is(), :not() and :has() count as their most specific argument and
+    // :where() counts for nothing; other pseudo-classes are like a class.
+    pub fn specificity(&self) -> Specificity {
+        return match *self {
+            PseudoClass::Where(_) | PseudoClass::Nesting => (0, 0, 0),
+            PseudoClass::Is(ref s) | PseudoClass::Not(ref s) => max_specificity(s.iter()),
+            PseudoClass::Has(ref r) => max_specificity(r.iter().map(|r| &r.selector)),
+            PseudoClass::NthChild(_, ref of) | PseudoClass::NthLastChild(_, ref of) => {
+                add_specificity((0, 1, 0), max_specificity(of.iter()))
             },
-            None => {},
-        };
+            _ => (0, 1, 0),
+        }
+    }
+}
+
+impl PseudoClass {
+    fn contains_nesting(&self) -> bool {
+        return match *self {
+            PseudoClass::Nesting => true,
+            PseudoClass::NthChild(_, ref s)
+            | PseudoClass::NthLastChild(_, ref s)
+            | PseudoClass::Not(ref s)
+            | PseudoClass::Is(ref s)
+            | PseudoClass::Where(ref s) => s.iter().any(Selector::contains_nesting),
+            PseudoClass::Has(ref r) => r.iter().any(|r| r.selector.contains_nesting()),
+            _ => false,
+        }
+    }
+
+    fn resolve_nesting(&self, parent: &[Selector]) -> PseudoClass {
+        let resolve = |selectors: &[Selector]| selectors.iter().map(|s| s.resolve_nesting(parent)).collect();
+
+        return match *self {
+            // Pseudo-elements can't be matched by `:is()`.
+            PseudoClass::Nesting => PseudoClass::Is(parent.iter().filter(|s| s.pseudo_element.is_none()).cloned().collect()),
+            PseudoClass::NthChild(nth, ref s) => PseudoClass::NthChild(nth, resolve(s)),
+            PseudoClass::NthLastChild(nth, ref s) => PseudoClass::NthLastChild(nth, resolve(s)),
+            PseudoClass::Not(ref s) => PseudoClass::Not(resolve(s)),
+            PseudoClass::Is(ref s) => PseudoClass::Is(resolve(s)),
+            PseudoClass::Where(ref s) => PseudoClass::Where(resolve(s)),
+            PseudoClass::Has(ref relative) => PseudoClass::Has(relative
+                .iter()
+                .map(|r| RelativeSelector {
+                    combinator: r.combinator,
+                    selector: r.selector.resolve_nesting(parent),
+                })
+                .collect()),
+            ref p => p.clone(),
+        }
+    }
+}
+
+fn max_specificity<'a, I: Iterator<Item = &'a Selector>>(selectors: I) -> Specificity {
+    return selectors.map(Selector::specificity).max().unwrap_or((0, 0, 0))
+}
+
+fn add_specificity(a: Specificity, b: Specificity) -> Specificity {
+    return (a.0 + b.0, a.1 + b.1, a.2 + b.2)
+}
+
+
+impl fmt::Debug for PseudoClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{:#}", self)
+    }
+}
+
+impl fmt::Debug for PseudoElement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{:#}", self)
+    }
+}
+
+impl fmt::Debug for Nth {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{:#}", self)
+    }
+}
 
-        for class in &self.classes {
-            result.push('.');
-            result.push_str(class);
-        };
 
-        write!(f, "{}", result)
+
+impl fmt::Debug for SimpleSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{:#}", self)
     }
 }
 
@@ -225,7 +812,8 @@ impl Declaration {
     pub fn new(property: String, value: Value) -> Self {
         return Declaration {
             property,
-            value
+            value,
+            important: false,
         }
     }
 }
@@ -236,6 +824,7 @@ impl Default for Declaration {
         return Declaration {
             property: String::from(""),
             value: Value::Other(String::from("")),
+            important: false,
         }
     }
 }
@@ -243,7 +832,13 @@ impl Default for Declaration {
 
 impl fmt::Debug for Declaration {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}: {:?}", self.property, self.value)
+        write!(f, "{}: {:?}", self.property, self.value)?;
+
+        if self.important {
+            write!(f, " !important")?;
+        }
+
+        return Ok(())
     }
 }
 
@@ -251,16 +846,67 @@ impl fmt::Debug for Value {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             Value::Color(ref c) => write!(f, "{:?}", c),
-            Value::Length(l, _) => write!(f, "{:?}", l),
+            Value::Length(..) => write!(f, "{}", self),
+            Value::Calc(ref c) => write!(f, "{:?}", c),
+            Value::Content(ref c) => write!(f, "{:?}", c),
+            Value::Counters(ref c) => write!(f, "{:?}", c),
+            Value::Quotes(ref q) => write!(f, "{:?}", q),
             Value::Other(ref o) => write!(f, "{:?}", o),
+            Value::Tokens(ref t) => write!(f, "{:?}", serialize_tokens(t)),
+            Value::Number(_) | Value::Time(_) | Value::Easing(_) | Value::Transform(_) => write!(f, "{}", self),
+            Value::List(ref values) => write!(f, "{:?}", values),
+        }
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match *self {
+            Token::Ident(ref s) | Token::Other(ref s) => write!(f, "{}", s),
+            Token::Function(ref name) => write!(f, "{}(", name),
+            Token::CloseParen => write!(f, ")"),
+            Token::Comma => write!(f, ","),
+            Token::Whitespace => write!(f, " "),
         }
     }
 }
 
+pub fn serialize_tokens(tokens: &[Token]) -> String {
+    return tokens.iter().map(|t| t.to_string()).collect()
+}
+
 
 impl Color {
+    // A color from legacy syntax.
     pub fn new(r: f32, g: f32, b: f32, a: f32) -> Self {
-        return Color{ r, g, b, a }
+        return Color{ r, g, b, a, space: ColorSpace::Srgb, legacy: true }
+    }
+
+    pub fn in_space(space: ColorSpace, channels: [f32; 3], a: f32) -> Self {
+        return Color {
+            r: channels[0],
+            g: channels[1],
+            b: channels[2],
+            a,
+            space,
+            legacy: false,
+        }
+    }
+
+    pub fn is_legacy(&self) -> bool {
+        return self.legacy
+    }
+
+    pub fn space(&self) -> ColorSpace {
+        return self.space
+    }
+
+    pub fn channels(&self) -> [f32; 3] {
+        return [self.r, self.g, self.b]
+    }
+
+    pub fn alpha(&self) -> f32 {
+        return self.a
     }
 }
 
@@ -272,6 +918,1018 @@ impl Default for Color {
 
 impl fmt::Debug for Color {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "r: {} g: {} b: {} a: {}", self.r, self.g, self.b, self.a)
+        match self.space {
+            ColorSpace::Srgb => write!(f, "r: {} g: {} b: {} a: {}", self.r, self.g, self.b, self.a),
+            s => write!(f, "{:?}: {} {} {} a: {}", s, self.r, self.g, self.b, self.a),
+        }
+    }
+}
+
+// Serialization follows CSSOM. `{}` gives the minified form and `{:#}` the
+// pretty one, with each declaration on its own line and nested rules
+// indented.
+
+impl fmt::Display for Stylesheet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write_rules(f, &self.rules)
+    }
+}
+
+fn write_rules(f: &mut fmt::Formatter, rules: &[CssRule]) -> fmt::Result {
+    for (i, rule) in rules.iter().enumerate() {
+        if f.alternate() {
+            if i > 0 {
+                write!(f, "\n\n")?;
+            }
+
+            write!(f, "{:#}", rule)?;
+        } else {
+            write!(f, "{}", rule)?;
+        }
+    }
+
+    return Ok(())
+}
+
+// The block of a grouping rule like @media, including its braces.
+fn write_block(f: &mut fmt::Formatter, rules: &[CssRule]) -> fmt::Result {
+    if !f.alternate() {
+        write!(f, "{{")?;
+
+        for rule in rules {
+            write!(f, "{}", rule)?;
+        }
+
+        return write!(f, "}}")
+    }
+
+    if rules.is_empty() {
+        return write!(f, " {{}}")
+    }
+
+    let mut block = String::new();
+
+    for (i, rule) in rules.iter().enumerate() {
+        if i > 0 {
+            block.push('\n');
+        }
+
+        for line in format!("{:#}", rule).lines() {
+            if !line.is_empty() {
+                block.push_str("    ");
+                block.push_str(line);
+            }
+
+            block.push('\n');
+        }
+    }
+
+    return write!(f, " {{\n{}}}", block)
+}
+
+impl fmt::Display for CssRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let separator = if f.alternate() { ", " } else { "," };
+
+        return match *self {
+            CssRule::Style(ref r) => r.fmt(f),
+            CssRule::Media(ref m) => {
+                write!(f, "@media {}", join(&m.queries, separator))?;
+
+                write_block(f, &m.rules)
+            },
+            CssRule::Supports(ref s) => {
+                write!(f, "@supports {}", s.condition)?;
+
+                write_block(f, &s.rules)
+            },
+            CssRule::Layer(ref l) => {
+                write!(f, "@layer")?;
+
+                if let Some(ref name) = l.name {
+                    write!(f, " {}", name)?;
+                }
+
+                write_block(f, &l.rules)
+            },
+            CssRule::LayerStatement(ref names) => write!(f, "@layer {};", names.join(separator)),
+            CssRule::FontFace(ref font_face) => font_face.fmt(f),
+            CssRule::Keyframes(ref keyframes) => keyframes.fmt(f),
+            CssRule::Container(ref c) => {
+                write!(f, "@container")?;
+
+                if let Some(ref name) = c.name {
+                    write!(f, " {}", name)?;
+                }
+
+                if let Some(ref condition) = c.condition {
+                    write!(f, " {}", condition)?;
+                }
+
+                write_block(f, &c.rules)
+            },
+            CssRule::Import(ref i) => {
+                write!(f, "@import url({})", serialize_string(&i.url))?;
+
+                match i.layer {
+                    Some(ImportLayer::Anonymous) => write!(f, " layer")?,
+                    Some(ImportLayer::Named(ref name)) => write!(f, " layer({})", name)?,
+                    None => {},
+                }
+
+                match i.supports {
+                    Some(SupportsCondition::Declaration(ref property, ref value)) => {
+                        write!(f, " supports({}: {})", property, value)?
+                    },
+                    Some(ref condition) => write!(f, " supports({})", condition)?,
+                    None => {},
+                }
+
+                if !i.media.is_empty() {
+                    write!(f, " {}", join(&i.media, separator))?;
+                }
+
+                write!(f, ";")
+            },
+        }
+    }
+}
+
+impl FontFaceRule {
+    pub fn new(family: String, sources: Vec<FontSource>) -> Self {
+        return FontFaceRule {
+            family,
+            sources,
+            weight: (400.0, 400.0),
+            style: FontStyle::Normal,
+            stretch: (100.0, 100.0),
+            unicode_range: Vec::new(),
+            display: FontDisplay::Auto,
+        }
     }
-}
\ No newline at end of file
+
+    pub fn covers(&self, c: char) -> bool {
+        let c = c as u32;
+
+        return self.unicode_range.is_empty() || self.unicode_range.iter().any(|&(start, end)| start <= c && c <= end)
+    }
+}
+
+// Descriptors with their initial value are left out.
+impl fmt::Display for FontFaceRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let range = |(min, max): (f32, f32), unit: &str| match min == max {
+            true => format!("{}{}", serialize_number(min), unit),
+            false => format!("{}{} {}{}", serialize_number(min), unit, serialize_number(max), unit),
+        };
+
+        let sources: Vec<String> = self.sources.iter().map(|source| match *source {
+            FontSource::Local(ref name) => format!("local({})", serialize_string(name)),
+            FontSource::Url { ref url, format: Some(ref format) } => {
+                format!("url({}) format({})", serialize_string(url), serialize_string(format))
+            },
+            FontSource::Url { ref url, format: None } => format!("url({})", serialize_string(url)),
+        }).collect();
+
+        let mut descriptors = vec![
+            (String::from("font-family"), serialize_string(&self.family)),
+            (String::from("src"), sources.join(", ")),
+        ];
+
+        if self.weight != (400.0, 400.0) {
+            descriptors.push((String::from("font-weight"), range(self.weight, "")));
+        }
+
+        match self.style {
+            FontStyle::Normal => {},
+            FontStyle::Italic => descriptors.push((String::from("font-style"), String::from("italic"))),
+            FontStyle::Oblique(min, max) => {
+                descriptors.push((String::from("font-style"), format!("oblique {}", range((min, max), "deg"))))
+            },
+        }
+
+        if self.stretch != (100.0, 100.0) {
+            descriptors.push((String::from("font-stretch"), range(self.stretch, "%")));
+        }
+
+        if !self.unicode_range.is_empty() {
+            let ranges: Vec<String> = self.unicode_range.iter().map(|&(start, end)| match start == end {
+                true => format!("U+{:X}", start),
+                false => format!("U+{:X}-{:X}", start, end),
+            }).collect();
+
+            descriptors.push((String::from("unicode-range"), ranges.join(", ")));
+        }
+
+        let display = match self.display {
+            FontDisplay::Auto => None,
+            FontDisplay::Block => Some("block"),
+            FontDisplay::Swap => Some("swap"),
+            FontDisplay::Fallback => Some("fallback"),
+            FontDisplay::Optional => Some("optional"),
+        };
+
+        if let Some(display) = display {
+            descriptors.push((String::from("font-display"), String::from(display)));
+        }
+
+        if !f.alternate() {
+            let descriptors: Vec<String> = descriptors.iter().map(|&(ref name, ref value)| format!("{}:{}", name, value)).collect();
+
+            return write!(f, "@font-face{{{}}}", descriptors.join(";"))
+        }
+
+        writeln!(f, "@font-face {{")?;
+
+        for (name, value) in descriptors {
+            writeln!(f, "    {}: {};", name, value)?;
+        }
+
+        return write!(f, "}}")
+    }
+}
+
+impl fmt::Display for KeyframesRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match is_identifier(&self.name) {
+            true => self.name.clone(),
+            false => serialize_string(&self.name),
+        };
+
+        write!(f, "@keyframes {}", name)?;
+
+        if !f.alternate() {
+            write!(f, "{{")?;
+
+            for keyframe in &self.keyframes {
+                write!(f, "{}", keyframe)?;
+            }
+
+            return write!(f, "}}")
+        }
+
+        if self.keyframes.is_empty() {
+            return write!(f, " {{}}")
+        }
+
+        writeln!(f, " {{")?;
+
+        for keyframe in &self.keyframes {
+            for line in format!("{:#}", keyframe).lines() {
+                writeln!(f, "    {}", line)?;
+            }
+        }
+
+        return write!(f, "}}")
+    }
+}
+
+impl fmt::Display for Keyframe {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let offsets: Vec<String> = self.offsets.iter().map(|&o| format!("{}%", serialize_number(o * 100.0))).collect();
+
+        if !f.alternate() {
+            return write!(f, "{}{{{}}}", offsets.join(","), join(&self.declarations, ";"))
+        }
+
+        if self.declarations.is_empty() {
+            return write!(f, "{} {{}}", offsets.join(", "))
+        }
+
+        writeln!(f, "{} {{", offsets.join(", "))?;
+
+        for declaration in &self.declarations {
+            writeln!(f, "    {:#};", declaration)?;
+        }
+
+        return write!(f, "}}")
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !f.alternate() {
+            write!(f, "{}{{", join(&self.selectors, ","))?;
+
+            for (i, declaration) in self.declarations.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ";")?;
+                }
+
+                write!(f, "{}", declaration)?;
+            }
+
+            if !self.declarations.is_empty() && !self.rules.is_empty() {
+                write!(f, ";")?;
+            }
+
+            for rule in &self.rules {
+                write!(f, "{}", rule)?;
+            }
+
+            return write!(f, "}}")
+        }
+
+        write!(f, "{:#}", join_alternate(&self.selectors, ", "))?;
+
+        if self.declarations.is_empty() && self.rules.is_empty() {
+            return write!(f, " {{}}")
+        }
+
+        writeln!(f, " {{")?;
+
+        for declaration in &self.declarations {
+            writeln!(f, "    {:#};", declaration)?;
+        }
+
+        for (i, rule) in self.rules.iter().enumerate() {
+            if i > 0 || !self.declarations.is_empty() {
+                writeln!(f)?;
+            }
+
+            for line in format!("{:#}", rule).lines() {
+                match line.is_empty() {
+                    true => writeln!(f)?,
+                    false => writeln!(f, "    {}", line)?,
+                }
+            }
+        }
+
+        return write!(f, "}}")
+    }
+}
+
+impl fmt::Display for Declaration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match f.alternate() {
+            true => write!(f, "{}: {}", self.property, self.value)?,
+            false => write!(f, "{}:{}", self.property, self.value)?,
+        }
+
+        if self.important {
+            match f.alternate() {
+                true => write!(f, " !important")?,
+                false => write!(f, "!important")?,
+            }
+        }
+
+        return Ok(())
+    }
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, simple) in self.simple.iter().enumerate() {
+            if i > 0 {
+                match (self.combinators[i - 1], f.alternate()) {
+                    (' ', _) => write!(f, " ")?,
+                    (c, true) => write!(f, " {} ", c)?,
+                    (c, false) => write!(f, "{}", c)?,
+                }
+            }
+
+            match f.alternate() {
+                true => write!(f, "{:#}", simple)?,
+                false => write!(f, "{}", simple)?,
+            }
+        }
+
+        if let Some(pseudo_element) = self.pseudo_element {
+            write!(f, "{}", pseudo_element)?;
+        }
+
+        return Ok(())
+    }
+}
+
+impl fmt::Display for SimpleSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.tag_name {
+            Some(ref t) => write!(f, "{}", t)?,
+            // A compound with nothing else in it still needs a selector.
+            None if self.id.is_none() && self.classes.is_empty() && self.attributes.is_empty()
+                && self.pseudo_classes.is_empty() => write!(f, "*")?,
+            None => {},
+        }
+
+        if let Some(ref id) = self.id {
+            write!(f, "#{}", id)?;
+        }
+
+        for class in &self.classes {
+            write!(f, ".{}", class)?;
+        }
+
+        for attribute in &self.attributes {
+            write!(f, "{}", attribute)?;
+        }
+
+        for pseudo_class in &self.pseudo_classes {
+            match f.alternate() {
+                true => write!(f, "{:#}", pseudo_class)?,
+                false => write!(f, "{}", pseudo_class)?,
+            }
+        }
+
+        return Ok(())
+    }
+}
+
+impl fmt::Display for AttributeSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[")?;
+
+        if let Some(ref ns) = self.namespace {
+            write!(f, "{}|", ns)?;
+        }
+
+        write!(f, "{}", self.name)?;
+
+        let operator = match self.operator {
+            AttrOperator::Exists => return write!(f, "]"),
+            AttrOperator::Equals => "=",
+            AttrOperator::Includes => "~=",
+            AttrOperator::DashMatch => "|=",
+            AttrOperator::Prefix => "^=",
+            AttrOperator::Suffix => "$=",
+            AttrOperator::Substring => "*=",
+        };
+
+        write!(f, "{}{}", operator, serialize_string(&self.value))?;
+
+        match self.case {
+            CaseSensitivity::Default => {},
+            CaseSensitivity::Insensitive => write!(f, " i")?,
+            CaseSensitivity::Sensitive => write!(f, " s")?,
+        }
+
+        return write!(f, "]")
+    }
+}
+
+impl fmt::Display for PseudoClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let separator = if f.alternate() { ", " } else { "," };
+
+        let name = match *self {
+            PseudoClass::Nesting => return write!(f, "&"),
+            PseudoClass::Root => "root",
+            PseudoClass::Empty => "empty",
+            PseudoClass::FirstChild => "first-child",
+            PseudoClass::LastChild => "last-child",
+            PseudoClass::OnlyChild => "only-child",
+            PseudoClass::FirstOfType => "first-of-type",
+            PseudoClass::LastOfType => "last-of-type",
+            PseudoClass::OnlyOfType => "only-of-type",
+            PseudoClass::NthChild(nth, ref of) | PseudoClass::NthLastChild(nth, ref of) => {
+                let name = match *self {
+                    PseudoClass::NthChild(..) => "nth-child",
+                    _ => "nth-last-child",
+                };
+
+                return match of.is_empty() {
+                    true => write!(f, ":{}({})", name, nth),
+                    false => write!(f, ":{}({} of {})", name, nth, join_alternate_if(f, of, separator)),
+                }
+            },
+            PseudoClass::NthOfType(nth) => return write!(f, ":nth-of-type({})", nth),
+            PseudoClass::NthLastOfType(nth) => return write!(f, ":nth-last-of-type({})", nth),
+            PseudoClass::Not(ref s) => return write!(f, ":not({})", join_alternate_if(f, s, separator)),
+            PseudoClass::Is(ref s) => return write!(f, ":is({})", join_alternate_if(f, s, separator)),
+            PseudoClass::Where(ref s) => return write!(f, ":where({})", join_alternate_if(f, s, separator)),
+            PseudoClass::Has(ref relative) => {
+                let list: Vec<String> = relative.iter().map(|r| match (r.combinator, f.alternate()) {
+                    (' ', true) => format!("{:#}", r.selector),
+                    (' ', false) => format!("{}", r.selector),
+                    (c, true) => format!("{} {:#}", c, r.selector),
+                    (c, false) => format!("{}{}", c, r.selector),
+                }).collect();
+
+                return write!(f, ":has({})", list.join(separator))
+            },
+            PseudoClass::Hover => "hover",
+            PseudoClass::Active => "active",
+            PseudoClass::Focus => "focus",
+            PseudoClass::Checked => "checked",
+            PseudoClass::Disabled => "disabled",
+            PseudoClass::Enabled => "enabled",
+            PseudoClass::Link => "link",
+            PseudoClass::Visited => "visited",
+        };
+
+        return write!(f, ":{}", name)
+    }
+}
+
+impl fmt::Display for PseudoElement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            PseudoElement::Before => "before",
+            PseudoElement::After => "after",
+            PseudoElement::Marker => "marker",
+            PseudoElement::FirstLine => "first-line",
+            PseudoElement::FirstLetter => "first-letter",
+            PseudoElement::Placeholder => "placeholder",
+        };
+
+        return write!(f, "::{}", name)
+    }
+}
+
+// `2n+1`, `n`, `-n+3` or just `B` when A is 0.
+impl fmt::Display for Nth {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.a {
+            0 => return write!(f, "{}", self.b),
+            1 => write!(f, "n")?,
+            -1 => write!(f, "-n")?,
+            a => write!(f, "{}n", a)?,
+        }
+
+        return match self.b {
+            0 => Ok(()),
+            b => write!(f, "{:+}", b),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match *self {
+            Value::Color(ref c) => write!(f, "{}", c),
+            Value::Length(n, unit) => write!(f, "{}{}", serialize_number(n), unit),
+            Value::Calc(ref c) => write!(f, "{}", c),
+            Value::Other(ref o) => write!(f, "{}", o),
+            Value::Tokens(ref t) => write!(f, "{}", serialize_tokens(t).trim()),
+            Value::Content(ref items) => {
+                let items: Vec<String> = items.iter().map(ContentItem::to_string).collect();
+
+                write!(f, "{}", items.join(" "))
+            },
+            Value::Counters(ref counters) => {
+                let counters: Vec<String> = counters.iter().map(|&(ref name, n)| format!("{} {}", name, n)).collect();
+
+                write!(f, "{}", counters.join(" "))
+            },
+            Value::Quotes(ref quotes) => {
+                let quotes: Vec<String> = quotes
+                    .iter()
+                    .map(|&(ref open, ref close)| format!("{} {}", serialize_string(open), serialize_string(close)))
+                    .collect();
+
+                write!(f, "{}", quotes.join(" "))
+            },
+            Value::Number(n) => write!(f, "{}", serialize_number(n)),
+            Value::Time(t) => write!(f, "{}s", serialize_number(t)),
+            Value::Easing(ref e) => write!(f, "{}", e),
+            Value::Transform(ref functions) if functions.is_empty() => write!(f, "none"),
+            Value::Transform(ref functions) => write!(f, "{}", join(functions, " ")),
+            Value::List(ref values) => write!(f, "{}", join(values, ", ")),
+        }
+    }
+}
+
+impl fmt::Display for EasingFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match *self {
+            EasingFunction::Linear => write!(f, "linear"),
+            EasingFunction::CubicBezier(x1, y1, x2, y2) => match (x1, y1, x2, y2) {
+                (0.25, 0.1, 0.25, 1.0) => write!(f, "ease"),
+                (0.42, 0.0, 1.0, 1.0) => write!(f, "ease-in"),
+                (0.0, 0.0, 0.58, 1.0) => write!(f, "ease-out"),
+                (0.42, 0.0, 0.58, 1.0) => write!(f, "ease-in-out"),
+                _ => write!(f, "cubic-bezier({}, {}, {}, {})", serialize_number(x1), serialize_number(y1), serialize_number(x2), serialize_number(y2)),
+            },
+            EasingFunction::Steps(1, StepPosition::JumpStart) => write!(f, "step-start"),
+            EasingFunction::Steps(1, StepPosition::JumpEnd) => write!(f, "step-end"),
+            EasingFunction::Steps(n, StepPosition::JumpEnd) => write!(f, "steps({})", n),
+            EasingFunction::Steps(n, position) => {
+                let position = match position {
+                    StepPosition::JumpStart => "jump-start",
+                    StepPosition::JumpEnd => "jump-end",
+                    StepPosition::JumpNone => "jump-none",
+                    StepPosition::JumpBoth => "jump-both",
+                };
+
+                write!(f, "steps({}, {})", n, position)
+            },
+        }
+    }
+}
+
+impl fmt::Display for TransformFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let n = serialize_number;
+
+        return match *self {
+            TransformFunction::Translate(ref x, Value::Length(y, _)) if y == 0.0 => write!(f, "translate({})", x),
+            TransformFunction::Translate(ref x, ref y) => write!(f, "translate({}, {})", x, y),
+            TransformFunction::Scale(x, y) if x == y => write!(f, "scale({})", n(x)),
+            TransformFunction::Scale(x, y) => write!(f, "scale({}, {})", n(x), n(y)),
+            TransformFunction::Rotate(angle) => write!(f, "rotate({}deg)", n(angle)),
+            TransformFunction::Skew(x, y) if y == 0.0 => write!(f, "skew({}deg)", n(x)),
+            TransformFunction::Skew(x, y) => write!(f, "skew({}deg, {}deg)", n(x), n(y)),
+            TransformFunction::Matrix(m) => {
+                let values: Vec<String> = m.iter().map(|&v| n(v)).collect();
+
+                write!(f, "matrix({})", values.join(", "))
+            },
+        }
+    }
+}
+
+impl fmt::Display for ContentItem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match *self {
+            ContentItem::String(ref s) => write!(f, "{}", serialize_string(s)),
+            ContentItem::Attr(ref name) => write!(f, "attr({})", name),
+            ContentItem::Counter(ref name, ref style) => match style.as_ref() {
+                "decimal" => write!(f, "counter({})", name),
+                _ => write!(f, "counter({}, {})", name, style),
+            },
+            ContentItem::Counters(ref name, ref separator, ref style) => match style.as_ref() {
+                "decimal" => write!(f, "counters({}, {})", name, serialize_string(separator)),
+                _ => write!(f, "counters({}, {}, {})", name, serialize_string(separator), style),
+            },
+            ContentItem::OpenQuote => write!(f, "open-quote"),
+            ContentItem::CloseQuote => write!(f, "close-quote"),
+            ContentItem::NoOpenQuote => write!(f, "no-open-quote"),
+            ContentItem::NoCloseQuote => write!(f, "no-close-quote"),
+        }
+    }
+}
+
+impl fmt::Display for CalcNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match *self {
+            CalcNode::Number(n) => write!(f, "{}", serialize_number(n)),
+            CalcNode::Length(n, unit) => write!(f, "{}{}", serialize_number(n), unit),
+            CalcNode::Sum(_) | CalcNode::Scale(..) => write!(f, "calc({})", CalcOperand(self)),
+            CalcNode::Min(ref nodes) => write!(f, "min({})", join(&nodes.iter().map(CalcOperand).collect::<Vec<_>>(), ", ")),
+            CalcNode::Max(ref nodes) => write!(f, "max({})", join(&nodes.iter().map(CalcOperand).collect::<Vec<_>>(), ", ")),
+            CalcNode::Clamp(ref min, ref value, ref max) => {
+                write!(f, "clamp({}, {}, {})", CalcOperand(min), CalcOperand(value), CalcOperand(max))
+            },
+        }
+    }
+}
+
+// A calc node inside a math function, where sums and products don't need
+// their own calc().
+struct CalcOperand<'a>(&'a CalcNode);
+
+impl<'a> fmt::Display for CalcOperand<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match *self.0 {
+            CalcNode::Sum(ref terms) => {
+                for (i, term) in terms.iter().enumerate() {
+                    // Negative terms are written as subtractions.
+                    let (negative, term) = match *term {
+                        CalcNode::Number(n) if i > 0 && n < 0.0 => (true, CalcNode::Number(-n)),
+                        CalcNode::Length(n, unit) if i > 0 && n < 0.0 => (true, CalcNode::Length(-n, unit)),
+                        CalcNode::Scale(n, ref node) if i > 0 && n < 0.0 => (true, CalcNode::Scale(-n, node.clone())),
+                        ref term => (false, term.clone()),
+                    };
+
+                    match (i, negative) {
+                        (0, _) => {},
+                        (_, true) => write!(f, " - ")?,
+                        (_, false) => write!(f, " + ")?,
+                    }
+
+                    match term {
+                        CalcNode::Sum(_) => write!(f, "({})", CalcOperand(&term))?,
+                        _ => write!(f, "{}", CalcOperand(&term))?,
+                    }
+                }
+
+                Ok(())
+            },
+            CalcNode::Scale(n, ref node) => match **node {
+                CalcNode::Sum(_) => write!(f, "{} * ({})", serialize_number(n), CalcOperand(node)),
+                _ => write!(f, "{} * {}", serialize_number(n), CalcOperand(node)),
+            },
+            ref node => write!(f, "{}", node),
+        }
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let unit = match *self {
+            Unit::Em => "em",
+            Unit::Ex => "ex",
+            Unit::Ch => "ch",
+            Unit::Rem => "rem",
+            Unit::Vh => "vh",
+            Unit::Vw => "vw",
+            Unit::Vmin => "vmin",
+            Unit::Vmax => "vmax",
+            Unit::Px => "px",
+            Unit::Mm => "mm",
+            Unit::Q => "q",
+            Unit::Cm => "cm",
+            Unit::In => "in",
+            Unit::Pt => "pt",
+            Unit::Pc => "pc",
+            Unit::Pct => "%",
+        };
+
+        return write!(f, "{}", unit)
+    }
+}
+
+// sRGB colors use the legacy rgb() and rgba() forms, with channels from 0 to
+// 255. Other spaces use their own function, or color().
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let [r, g, b] = self.channels();
+
+        let function = match self.space {
+            // Legacy colors are written with 8-bit channels, and the
+            // shortest alpha that gives the same 8-bit value.
+            ColorSpace::Srgb if self.legacy => {
+                let channels = [r, g, b].iter().map(|c| (c * 255.0).round().max(0.0).min(255.0).to_string()).collect::<Vec<_>>().join(", ");
+                let alpha = match (self.a * 100.0).round() / 100.0 {
+                    a if (a * 255.0).round() == (self.a * 255.0).round() => a,
+                    _ => (self.a * 1000.0).round() / 1000.0,
+                };
+
+                return match alpha {
+                    a if a == 1.0 => write!(f, "rgb({})", channels),
+                    a => write!(f, "rgba({}, {})", channels, serialize_number(a)),
+                }
+            },
+            ColorSpace::Srgb => "color(srgb ",
+            // Only used to interpolate in.
+            ColorSpace::Hsl | ColorSpace::Hwb => return self.to_srgb().fmt(f),
+            ColorSpace::Lab => "lab(",
+            ColorSpace::Lch => "lch(",
+            ColorSpace::Oklab => "oklab(",
+            ColorSpace::Oklch => "oklch(",
+            ColorSpace::SrgbLinear => "color(srgb-linear ",
+            ColorSpace::DisplayP3 => "color(display-p3 ",
+            ColorSpace::Rec2020 => "color(rec2020 ",
+            ColorSpace::XyzD50 => "color(xyz-d50 ",
+            ColorSpace::XyzD65 => "color(xyz-d65 ",
+        };
+
+        write!(f, "{}{} {} {}", function, serialize_number(r), serialize_number(g), serialize_number(b))?;
+
+        if self.a != 1.0 {
+            write!(f, " / {}", serialize_number(self.a))?;
+        }
+
+        return write!(f, ")")
+    }
+}
+
+impl fmt::Display for MediaQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.qualifier {
+            Some(MediaQualifier::Not) => write!(f, "not ")?,
+            Some(MediaQualifier::Only) => write!(f, "only ")?,
+            None => {},
+        }
+
+        return match (&self.media_type, &self.condition) {
+            (&Some(ref media_type), &Some(ref condition)) => {
+                write!(f, "{} and {}", media_type, MediaConditionInParens(condition, true))
+            },
+            (&Some(ref media_type), &None) => write!(f, "{}", media_type),
+            (&None, &Some(ref condition)) => write!(f, "{}", condition),
+            (&None, &None) => write!(f, "all"),
+        }
+    }
+}
+
+impl fmt::Display for MediaCondition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match *self {
+            MediaCondition::Feature(ref feature) => write!(f, "({})", feature),
+            MediaCondition::Not(ref c) => write!(f, "not {}", MediaConditionInParens(c, false)),
+            MediaCondition::And(ref cs) => {
+                write!(f, "{}", join(&cs.iter().map(|c| MediaConditionInParens(c, false)).collect::<Vec<_>>(), " and "))
+            },
+            MediaCondition::Or(ref cs) => {
+                write!(f, "{}", join(&cs.iter().map(|c| MediaConditionInParens(c, false)).collect::<Vec<_>>(), " or "))
+            },
+            MediaCondition::Unknown(ref s) => write!(f, "({})", s),
+        }
+    }
+}
+
+// A condition that has to be in parentheses, like an operand of `and`. After
+// a media type, only `or` needs them.
+struct MediaConditionInParens<'a>(&'a MediaCondition, bool);
+
+impl<'a> fmt::Display for MediaConditionInParens<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match *self.0 {
+            MediaCondition::And(_) | MediaCondition::Not(_) if self.1 => write!(f, "{}", self.0),
+            MediaCondition::Not(_) | MediaCondition::And(_) | MediaCondition::Or(_) => write!(f, "({})", self.0),
+            ref c => write!(f, "{}", c),
+        }
+    }
+}
+
+impl fmt::Display for MediaFeature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match *self {
+            MediaFeature::Boolean(ref name) => write!(f, "{}", name),
+            MediaFeature::Plain(ref name, ref value) => write!(f, "{}: {}", name, value),
+            MediaFeature::Range { ref name, ref start, ref end } => {
+                if let Some((ref value, op)) = *start {
+                    write!(f, "{} {} ", value, op)?;
+                }
+
+                write!(f, "{}", name)?;
+
+                if let Some((op, ref value)) = *end {
+                    write!(f, " {} {}", op, value)?;
+                }
+
+                Ok(())
+            },
+        }
+    }
+}
+
+impl fmt::Display for MediaValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match *self {
+            MediaValue::Number(n) => write!(f, "{}", serialize_number(n)),
+            MediaValue::Length(n, unit) => write!(f, "{}{}", serialize_number(n), unit),
+            MediaValue::Ratio(a, b) => write!(f, "{}/{}", serialize_number(a), serialize_number(b)),
+            MediaValue::Resolution(dppx) => write!(f, "{}dppx", serialize_number(dppx)),
+            MediaValue::Ident(ref s) => write!(f, "{}", s),
+        }
+    }
+}
+
+impl fmt::Display for RangeOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let op = match *self {
+            RangeOp::Lt => "<",
+            RangeOp::Le => "<=",
+            RangeOp::Gt => ">",
+            RangeOp::Ge => ">=",
+            RangeOp::Eq => "=",
+        };
+
+        return write!(f, "{}", op)
+    }
+}
+
+impl fmt::Display for SupportsCondition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match *self {
+            SupportsCondition::Not(ref c) => write!(f, "not {}", SupportsInParens(c)),
+            SupportsCondition::And(ref cs) => {
+                write!(f, "{}", join(&cs.iter().map(SupportsInParens).collect::<Vec<_>>(), " and "))
+            },
+            SupportsCondition::Or(ref cs) => {
+                write!(f, "{}", join(&cs.iter().map(SupportsInParens).collect::<Vec<_>>(), " or "))
+            },
+            ref c => write!(f, "{}", SupportsInParens(c)),
+        }
+    }
+}
+
+struct SupportsInParens<'a>(&'a SupportsCondition);
+
+impl<'a> fmt::Display for SupportsInParens<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match *self.0 {
+            SupportsCondition::Declaration(ref property, ref value) => write!(f, "({}: {})", property, value),
+            SupportsCondition::Selector(ref s) => write!(f, "selector({})", s),
+            SupportsCondition::Unknown(ref s) => write!(f, "({})", s),
+            ref c => write!(f, "({})", c),
+        }
+    }
+}
+
+fn join<T: fmt::Display>(items: &[T], separator: &str) -> String {
+    return items.iter().map(T::to_string).collect::<Vec<_>>().join(separator)
+}
+
+// Like `join`, but with each item in its pretty form.
+fn join_alternate<T: fmt::Display>(items: &[T], separator: &str) -> String {
+    return items.iter().map(|i| format!("{:#}", i)).collect::<Vec<_>>().join(separator)
+}
+
+fn join_alternate_if<T: fmt::Display>(f: &fmt::Formatter, items: &[T], separator: &str) -> String {
+    return match f.alternate() {
+        true => join_alternate(items, separator),
+        false => join(items, separator),
+    }
+}
+
+// Numbers are rounded to a few decimals, which hides the noise f32 math
+// leaves in converted values.
+pub fn serialize_number(n: f32) -> String {
+    let rounded = (n * 1000.0).round() / 1000.0;
+
+    // Avoids "-0".
+    return match rounded == 0.0 {
+        true => String::from("0"),
+        false => rounded.to_string(),
+    }
+}
+
+// Whether `s` can be written as a custom identifier, like a @keyframes name,
+// without quotes.
+fn is_identifier(s: &str) -> bool {
+    let valid_start = match s.strip_prefix('-').unwrap_or(s).chars().next() {
+        Some(c) => c.is_alphabetic() || c == '_' || !c.is_ascii(),
+        None => false,
+    };
+
+    return valid_start
+        && s.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_' || !c.is_ascii())
+        && !["none", "default", "initial", "inherit", "unset", "revert", "revert-layer"].contains(&s.to_ascii_lowercase().as_str())
+}
+
+// A double-quoted string, escaping quotes, backslashes and control
+// characters, which are written as hex escapes.
+pub fn serialize_string(s: &str) -> String {
+    let mut result = String::from("\"");
+
+    for c in s.chars() {
+        match c {
+            '"' | '\\' => {
+                result.push('\\');
+                result.push(c);
+            },
+            c if c.is_control() => result.push_str(&format!("\\{:x} ", c as u32)),
+            c => result.push(c),
+        }
+    }
+
+    result.push('"');
+
+    return result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::css_parser::CssParser;
+
+    // Serializing, parsing the result and serializing again has to give the
+    // same text, in both the minified and the pretty form.
+    fn assert_round_trips(css: &str) {
+        let sheet = CssParser::new(css).parse_stylesheet();
+
+        let minified = sheet.to_string();
+        assert_eq!(CssParser::new(&minified).parse_stylesheet().to_string(), minified);
+
+        let pretty = format!("{:#}", sheet);
+        assert_eq!(format!("{:#}", CssParser::new(&pretty).parse_stylesheet()), pretty);
+    }
+
+    #[test]
+    fn style_rules_round_trip() {
+        assert_round_trips("div.a#b > p + span ~ em, [href^='http' i]::before { color: red; margin-top: 1em !important }");
+        assert_round_trips("a { --gap: 4px; padding-left: var(--gap, 2px); content: \"x\" attr(title) counter(c, upper-roman) }");
+        assert_round_trips("a { content: \"\\A\\9 \\e900\" \"\\\"\" }");
+    }
+
+    #[test]
+    fn nested_rules_round_trip() {
+        assert_round_trips(".card { color: blue; & .title { color: red; } &:hover > p { width: 10px; } }");
+        assert_round_trips(".card { .title { @media (min-width: 600px) { color: red; } } }");
+    }
+
+    #[test]
+    fn at_rules_round_trip() {
+        assert_round_trips("@import url(\"a.css\") layer(base) supports(display: grid) screen;");
+        assert_round_trips("@media screen and (min-width: 600px), print and not (color) { a { color: red } }");
+        assert_round_trips("@media (400px <= width < 800px) { a { color: red } }");
+        assert_round_trips("@supports (display: block) and (not (display: foo)) { a { color: red } }");
+        assert_round_trips("@layer reset, framework.base; @layer framework.base { a { color: red } } @layer { b { color: blue } }");
+        assert_round_trips("@container card (min-width: 400px) { a { color: red } }");
+        assert_round_trips("@keyframes spin { from { opacity: 0 } 50% { opacity: 0.5 } to { opacity: 1 } }");
+    }
+
+    #[test]
+    fn relational_selectors_round_trip() {
+        assert_round_trips("a:has(> img, + p) { color: red } li:nth-child(2n+1 of .item) { color: blue }");
+        assert_round_trips(":is(ul, ol) :where(li):not(.a, .b):nth-last-of-type(-n+3) { color: red }");
+    }
+
+    #[test]
+    fn values_round_trip() {
+        assert_round_trips("a { width: calc(100% - 2em); height: min(10px, 5vw); margin-left: clamp(1px, 2%, 3em) }");
+        assert_round_trips("a { color: #ff000080; background-color: hsl(120 50% 50% / 0.5); border-color: currentcolor }");
+        assert_round_trips("a { color: color-mix(in oklch, red 40%, blue) }");
+        assert_round_trips("a { color: color-mix(in srgb, red, blue); background-color: color(srgb 0.25 0.5 1 / 0.5) }");
+        assert_round_trips("a { color: hwb(120 10% 20%); background-color: rgb(10.4 20.6 30) }");
+
+        // Only colors written with legacy syntax serialize as rgb().
+        let sheet = CssParser::new("a { color: color-mix(in srgb, red, blue); background-color: rgb(10.4 20.6 30 / 50%); border-color: color(srgb 0.25 0.5 1) }").parse_stylesheet();
+        assert_eq!(sheet.to_string(), "a{color:color(srgb 0.5 0 0.5);background-color:rgba(10, 21, 30, 0.5);border-color:color(srgb 0.25 0.5 1)}");
+        assert_round_trips("a { font-family: \"Font 2\", Open  Sans, serif; font-weight: bolder; font-style: oblique 10deg; font-stretch: 50% }");
+        assert_round_trips("a { font-stretch: condensed; font-weight: 650 }");
+        assert_round_trips("a { transform: translate(10px, 50%) rotate(45deg) scale(2); animation-timing-function: steps(3, jump-both), cubic-bezier(0.1, 0.2, 0.3, 0.4) }");
+    }
+}
@@ -1,14 +1,65 @@
+use crate::color::{
+    self,
+    hsl_to_rgb,
+    hwb_to_rgb,
+    HueInterpolation,
+};
 use crate::css::{
+    AttrOperator,
+    AttributeSelector,
+    CalcNode,
+    CaseSensitivity,
     Color,
+    ColorSpace,
+    ContainerRule,
+    CssRule,
     Declaration,
+    EasingFunction,
+    FontDisplay,
+    FontFaceRule,
+    FontSource,
+    FontStyle,
+    ImportLayer,
+    ImportRule,
+    Keyframe,
+    KeyframesRule,
+    LayerRule,
+    MediaCondition,
+    MediaFeature,
+    MediaQualifier,
+    MediaQuery,
+    MediaRule,
+    MediaValue,
+    ContentItem,
+    Nth,
+    Origin,
+    PseudoClass,
+    PseudoElement,
+    RangeOp,
+    RelativeSelector,
     Rule,
     Selector,
     SimpleSelector,
+    StepPosition,
     Stylesheet,
+    SupportsCondition,
+    SupportsRule,
+    Token,
+    TransformFunction,
     Unit,
-    Value
+    Value,
+    serialize_string,
+    serialize_tokens,
+};
+use crate::loader::StylesheetLoader;
+use crate::properties::{
+    self,
+    Shorthand,
+    Syntax,
+    CSS_WIDE_KEYWORDS,
 };
 use std::iter::Peekable;
+use std::mem;
 use std::str::Chars;
 
 pub struct CssParser<'a> {
@@ -24,27 +75,346 @@ impl<'a> CssParser<'a> {
     }
 
     pub fn parse_stylesheet(&mut self) -> Stylesheet {
-        let mut sheet = Stylesheet::default();
+        return Stylesheet::new(self.parse_rules(true))
+    }
+
+    // For user agent and user stylesheets; `parse_stylesheet` gives author
+    // ones.
+    pub fn parse_stylesheet_with_origin(&mut self, origin: Origin) -> Stylesheet {
+        return Stylesheet::with_origin(self.parse_rules(true), origin)
+    }
 
-        while self.chars.peek().is_some() {
-            let selectors = self.parse_selectors();
-            let styles = self.parse_declarations();
-            let rule = Rule::new(selectors, styles);
+    // Parses the stylesheet at `url` and loads its imports, recursively.
+    pub fn parse_stylesheet_with_loader(
+        &mut self,
+        loader: &mut dyn StylesheetLoader,
+        url: Option<&str>,
+    ) -> Stylesheet {
+        let mut sheet = self.parse_stylesheet();
+        let mut import_stack: Vec<String> = url.into_iter().map(String::from).collect();
 
-            sheet.rules.push(rule);
-        };
+        resolve_imports(&mut sheet.rules, loader, url, &mut import_stack);
 
         return sheet
     }
 
-    fn parse_selectors(&mut self) -> Vec<Selector> {
+    // Parses rules up to the end of input or, inside an at-rule block, up to
+    // and including its closing brace.
+    fn parse_rules(&mut self, top_level: bool) -> Vec<CssRule> {
+        let mut rules = Vec::new();
+        let mut imports_allowed = top_level;
+
+        loop {
+            self.consume_while(char::is_whitespace);
+
+            match self.chars.peek() {
+                None => break,
+                Some(&'}') => {
+                    self.chars.next();
+
+                    if !top_level {
+                        break;
+                    }
+                },
+                Some(&'@') => {
+                    self.chars.next();
+
+                    match self.parse_at_rule(false) {
+                        // @import is only valid before any other rules.
+                        Some(CssRule::Import(_)) if !imports_allowed => {},
+                        Some(rule) => {
+                            match rule {
+                                CssRule::Import(_) | CssRule::LayerStatement(_) => {},
+                                _ => imports_allowed = false,
+                            }
+
+                            rules.push(rule);
+                        },
+                        None => {},
+                    }
+                },
+                Some(_) => {
+                    imports_allowed = false;
+
+                    let selectors = self.parse_selectors(false);
+                    let (declarations, nested) = self.parse_style_block();
+
+                    // A rule with a selector we can't parse is dropped entirely.
+                    if let Some(selectors) = selectors {
+                        rules.push(CssRule::Style(Rule {
+                            selectors,
+                            declarations,
+                            rules: nested,
+                        }));
+                    }
+                },
+            }
+        };
+
+        return rules
+    }
+
+    // Inside a style rule (`nested`), the blocks of conditional rules hold
+    // declarations for the parent's selectors as well as rules.
+    fn parse_at_rule(&mut self, nested: bool) -> Option<CssRule> {
+        let name = self.parse_keyword();
+        let prelude = self.consume_while(|c| c != '{' && c != ';');
+
+        let has_block = self.chars.next() == Some('{');
+
+        return match (name.as_ref(), has_block) {
+            ("media", true) => Some(CssRule::Media(MediaRule {
+                queries: parse_media_query_list(&prelude),
+                rules: self.parse_group_rules(nested),
+            })),
+            ("supports", true) => {
+                let condition = CssParser::new(&prelude).parse_supports_condition();
+                let rules = self.parse_group_rules(nested);
+
+                condition.map(|condition| CssRule::Supports(SupportsRule { condition, rules }))
+            },
+            ("layer", true) => {
+                let name = match prelude.trim() {
+                    "" => Some(None),
+                    n => parse_layer_name(n).map(Some),
+                };
+                let rules = self.parse_group_rules(nested);
+
+                name.map(|name| CssRule::Layer(LayerRule { name, rules }))
+            },
+            ("layer", false) => {
+                let names: Option<Vec<String>> = prelude.split(',').map(parse_layer_name).collect();
+
+                names.map(CssRule::LayerStatement)
+            },
+            ("container", true) => {
+                let prelude = parse_container_prelude(&prelude);
+                let rules = self.parse_group_rules(nested);
+
+                prelude.map(|(name, condition)| CssRule::Container(ContainerRule { name, condition, rules }))
+            },
+            ("import", false) if !nested => parse_import_prelude(&prelude).map(CssRule::Import),
+            ("font-face", true) if !nested => parse_font_face(&self.consume_block()).map(CssRule::FontFace),
+            ("keyframes", true) if !nested => {
+                let keyframes = self.parse_keyframes();
+
+                parse_keyframes_name(&prelude).map(|name| CssRule::Keyframes(KeyframesRule { name, keyframes }))
+            },
+            (_, true) => {
+                self.skip_block();
+
+                None
+            },
+            _ => None,
+        }
+    }
+
+    // The keyframes of a @keyframes block, up to and including its closing
+    // brace. Keyframes with an invalid selector are dropped.
+    fn parse_keyframes(&mut self) -> Vec<Keyframe> {
+        let mut keyframes = Vec::new();
+
+        loop {
+            self.consume_while(char::is_whitespace);
+
+            if self.chars.peek().map_or(true, |&c| c == '}') {
+                self.chars.next();
+
+                break;
+            }
+
+            let selector = self.consume_while(|c| c != '{' && c != '}');
+
+            if self.chars.next() != Some('{') {
+                break;
+            }
+
+            self.consume_while(char::is_whitespace);
+
+            // Animation properties can't be animated, except that the
+            // timing function applies to the keyframe it's in. Important
+            // declarations are ignored.
+            let declarations = self.parse_declarations()
+                .into_iter()
+                .filter(|d| !d.important && (d.property == "animation-timing-function" || !d.property.starts_with("animation-")))
+                .collect();
+
+            let offsets: Option<Vec<f32>> = selector.split(',').map(|s| match s.trim().to_ascii_lowercase().as_ref() {
+                "from" => Some(0.0),
+                "to" => Some(1.0),
+                s => match parse_number(s)? {
+                    (n, "%") if n >= 0.0 && n <= 100.0 => Some(n / 100.0),
+                    _ => None,
+                },
+            }).collect();
+
+            if let Some(offsets) = offsets {
+                keyframes.push(Keyframe { offsets, declarations });
+            }
+        }
+
+        return keyframes
+    }
+
+    fn parse_group_rules(&mut self, nested: bool) -> Vec<CssRule> {
+        if !nested {
+            return self.parse_rules(false)
+        }
+
+        let (declarations, mut rules) = self.parse_style_block();
+
+        if !declarations.is_empty() {
+            rules.insert(0, CssRule::Style(Rule::new(vec![nesting_selector()], declarations)));
+        }
+
+        return rules
+    }
+
+    // The contents of a style rule's block, up to and including its closing
+    // brace: its declarations and any nested rules. Declarations that come
+    // after a nested rule are wrapped in `& {}` so they keep their order.
+    fn parse_style_block(&mut self) -> (Vec<Declaration>, Vec<CssRule>) {
+        let mut declarations = Vec::new();
+        let mut rules = Vec::new();
+        let mut trailing = Vec::new();
+
+        loop {
+            self.consume_while(char::is_whitespace);
+
+            match self.chars.peek().cloned() {
+                None => break,
+                Some('}') => {
+                    self.chars.next();
+
+                    break;
+                },
+                Some('@') => {
+                    self.chars.next();
+
+                    wrap_declarations(&mut trailing, &mut rules);
+
+                    rules.extend(self.parse_at_rule(true));
+                },
+                Some(_) if self.starts_nested_rule() => {
+                    wrap_declarations(&mut trailing, &mut rules);
+
+                    let selectors = self.parse_selectors(true);
+                    let (nested_declarations, nested_rules) = self.parse_style_block();
+
+                    if let Some(selectors) = selectors {
+                        rules.push(CssRule::Style(Rule {
+                            selectors,
+                            declarations: nested_declarations,
+                            rules: nested_rules,
+                        }));
+                    }
+                },
+                Some(_) => {
+                    let declaration = self.parse_declaration().into_iter().flatten();
+
+                    match rules.is_empty() {
+                        true => declarations.extend(declaration),
+                        false => trailing.extend(declaration),
+                    }
+                },
+            }
+        }
+
+        wrap_declarations(&mut trailing, &mut rules);
+
+        return (declarations, rules)
+    }
+
+    // Whether the next thing in a style block is a nested rule rather than a
+    // declaration: a `{` comes before anything that would end a declaration.
+    fn starts_nested_rule(&self) -> bool {
+        let mut lookahead = self.chars.clone();
+        let mut quote = None;
+        let mut depth = 0;
+
+        // Custom properties can have braces in their values.
+        if lookahead.clone().take(2).eq("--".chars()) {
+            return false
+        }
+
+        while let Some(c) = lookahead.next() {
+            match (quote, c) {
+                (Some(q), c) if c == q => quote = None,
+                (Some(_), '\\') => {
+                    lookahead.next();
+                },
+                (Some(_), _) => {},
+                (None, '"') | (None, '\'') => quote = Some(c),
+                (None, '(') | (None, '[') => depth += 1,
+                (None, ')') | (None, ']') => depth -= 1,
+                (None, '{') if depth == 0 => return true,
+                (None, ';') | (None, '}') if depth == 0 => return false,
+                _ => {},
+            }
+        }
+
+        return false
+    }
+
+    // Skips the rest of a block whose opening brace was already consumed.
+    fn skip_block(&mut self) {
+        self.consume_block();
+    }
+
+    // The rest of a block whose opening brace was already consumed, without
+    // the closing brace.
+    fn consume_block(&mut self) -> String {
+        let mut block = String::new();
+        let mut depth = 1;
+
+        while let Some(c) = self.chars.next() {
+            match c {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+
+                    if depth == 0 {
+                        break;
+                    }
+                },
+                _ => {},
+            }
+
+            block.push(c);
+        }
+
+        return block
+    }
+
+    // Nested selectors are made relative to `&`: `> .title` becomes
+    // `& > .title`, and `.title` becomes `& .title`.
+    fn parse_selectors(&mut self, nested: bool) -> Option<Vec<Selector>> {
         let mut selectors = Vec::new();
+        let mut valid = true;
 
         while self.chars.peek().map_or(false, |c| *c != '{') {
-            let sel = self.parse_selector();
+            self.consume_while(char::is_whitespace);
+
+            let combinator = match self.chars.peek() {
+                Some(&c) if nested && is_combinator(c) => {
+                    self.chars.next();
 
-            if sel != Selector::default() {
-                selectors.push(sel);
+                    Some(c)
+                },
+                _ => None,
+            };
+
+            match self.parse_selector() {
+                Some(ref sel) if *sel == Selector::default() && combinator.is_some() => valid = false,
+                Some(mut sel) => if sel != Selector::default() {
+                    if nested && (combinator.is_some() || !sel.contains_nesting()) {
+                        sel.simple.insert(0, nesting_selector().simple.remove(0));
+                        sel.combinators.insert(0, combinator.unwrap_or(' '));
+                    }
+
+                    selectors.push(sel);
+                },
+                None => valid = false,
             }
 
             self.consume_while(char::is_whitespace);
@@ -56,18 +426,62 @@ impl<'a> CssParser<'a> {
 
         self.chars.next();
 
-        return selectors;
+        return if valid { Some(selectors) } else { None }
     }
 
 
-    fn parse_selector(&mut self) -> Selector {
-        let mut simple_sel = SimpleSelector::default();
+    // Compound selectors joined by combinators: ' ' for descendant, then
+    // '>', '+' and '~'.
+    fn parse_selector(&mut self) -> Option<Selector> {
         let mut sel = Selector::default();
 
         self.consume_while(char::is_whitespace);
 
+        if self.chars.peek().map_or(true, |c| *c == ',' || *c == '{') {
+            return Some(sel)
+        }
+
+        loop {
+            match self.parse_simple_selector() {
+                Some((simple, pseudo_element)) => {
+                    sel.simple.push(simple);
+                    sel.pseudo_element = pseudo_element;
+                },
+                None => return self.skip_selector(),
+            }
+
+            let whitespace = !self.consume_while(char::is_whitespace).is_empty();
+
+            match self.chars.peek() {
+                None | Some(',') | Some('{') => break,
+                // Nothing can follow a pseudo-element.
+                Some(_) if sel.pseudo_element.is_some() => return self.skip_selector(),
+                Some(&c) if is_combinator(c) => {
+                    self.chars.next();
+                    self.consume_while(char::is_whitespace);
+
+                    sel.combinators.push(c);
+                },
+                Some(_) if whitespace => sel.combinators.push(' '),
+                Some(_) => return self.skip_selector(),
+            }
+        }
+
+        return Some(sel)
+    }
+
+    fn parse_simple_selector(&mut self) -> Option<(SimpleSelector, Option<PseudoElement>)> {
+        let mut simple_sel = SimpleSelector::default();
+        let mut pseudo_element = None;
+
+        let universal = self.chars.peek() == Some(&'*');
+
+        if universal {
+            self.chars.next();
+        }
+
         simple_sel.tag_name = match self.chars.peek() {
-            Some(&c) if is_valid_start_indent(c) => Some(self.parse_identifier()),
+            Some(&c) if is_valid_start_indent(c) && !universal => Some(self.parse_keyword()),
             _ => None,
         };
 
@@ -75,210 +489,2360 @@ impl<'a> CssParser<'a> {
 
         while self.chars
             .peek()
-            .map_or(false, |c| *c != ',' && *c != '{' && !(*c).is_whitespace())
+            .map_or(false, |c| *c != ',' && *c != '{' && !(*c).is_whitespace() && !is_combinator(*c))
         {
             match self.chars.peek() {
                 Some(&c) if c == '#' => {
                     self.chars.next();
 
-                    if simple_sel.id.is_some() || multiple_ids {
-                        simple_sel.id = None;
+                    if simple_sel.id.is_some() || multiple_ids {
+                        simple_sel.id = None;
+
+                        multiple_ids = true;
+
+                        self.parse_id();
+                    } else {
+                        simple_sel.id = self.parse_id();
+                    }
+                }
+
+                Some(&c) if c == '.' => {
+                    self.chars.next();
+
+                    let class_name = self.parse_identifier();
+
+                    if class_name != String::from("") {
+                        simple_sel.classes.push(class_name);
+                    }
+                }
+
+                Some(&c) if c == '[' => {
+                    self.chars.next();
+
+                    simple_sel.attributes.push(self.parse_attribute_selector()?);
+                }
+
+                Some(&c) if c == '&' => {
+                    self.chars.next();
+
+                    simple_sel.pseudo_classes.push(PseudoClass::Nesting);
+                }
+
+                Some(&c) if c == ':' => {
+                    self.chars.next();
+
+                    let double_colon = self.chars.peek() == Some(&':');
+
+                    if double_colon {
+                        self.chars.next();
+                    }
+
+                    let mut lookahead = CssParser { chars: self.chars.clone() };
+
+                    match (parse_pseudo_element(&lookahead.parse_keyword()), double_colon) {
+                        // CSS 2 pseudo-elements can still be written with one colon.
+                        (Some(p), true) | (Some(p @ PseudoElement::Before), false) | (Some(p @ PseudoElement::After), false)
+                        | (Some(p @ PseudoElement::FirstLine), false) | (Some(p @ PseudoElement::FirstLetter), false) => {
+                            self.chars = lookahead.chars;
+                            pseudo_element = Some(p);
+
+                            break
+                        },
+                        (_, true) => return None,
+                        (_, false) => simple_sel.pseudo_classes.push(self.parse_pseudo_class()?),
+                    }
+                }
+
+                _ => return None,
+            }
+        };
+
+        // Only `*` may stand for "any element"; an empty compound, like the
+        // left side of `> p`, is invalid.
+        if simple_sel == SimpleSelector::default() && !universal && pseudo_element.is_none() {
+            return None
+        }
+
+        return Some((simple_sel, pseudo_element))
+    }
+
+    // [ns|name op "value" i], with the `[` already consumed.
+    fn parse_attribute_selector(&mut self) -> Option<AttributeSelector> {
+        self.consume_while(char::is_whitespace);
+
+        let mut namespace = None;
+
+        let mut name = match self.chars.peek() {
+            Some('*') => {
+                self.chars.next();
+                String::from("*")
+            },
+            Some('|') => String::new(),
+            _ => self.parse_keyword(),
+        };
+
+        // `[a|=b]` is a dash match, not a namespace.
+        let mut lookahead = self.chars.clone();
+
+        if lookahead.next() == Some('|') && lookahead.peek() != Some(&'=') {
+            self.chars.next();
+
+            // Without @namespace every named prefix is undeclared, which
+            // makes the selector invalid.
+            if !name.is_empty() && name != "*" {
+                return None
+            }
+
+            namespace = Some(name);
+            name = self.parse_keyword();
+        }
+
+        if name.is_empty() || name == "*" {
+            return None
+        }
+
+        self.consume_while(char::is_whitespace);
+
+        let operator = match self.chars.next()? {
+            ']' => return Some(AttributeSelector {
+                namespace,
+                name,
+                operator: AttrOperator::Exists,
+                value: String::new(),
+                case: CaseSensitivity::Default,
+            }),
+            '=' => AttrOperator::Equals,
+            c => {
+                let operator = match c {
+                    '~' => AttrOperator::Includes,
+                    '|' => AttrOperator::DashMatch,
+                    '^' => AttrOperator::Prefix,
+                    '$' => AttrOperator::Suffix,
+                    '*' => AttrOperator::Substring,
+                    _ => return None,
+                };
+
+                if self.chars.next() != Some('=') {
+                    return None
+                }
+
+                operator
+            },
+        };
+
+        self.consume_while(char::is_whitespace);
+
+        // Attribute values keep their case, quoted or not.
+        let value = match *self.chars.peek()? {
+            q @ '"' | q @ '\'' => {
+                self.chars.next();
+
+                let mut value = String::new();
+
+                loop {
+                    match self.chars.next()? {
+                        '\\' => value.extend(consume_escape(&mut self.chars)),
+                        c if c == q => break,
+                        c => value.push(c),
+                    }
+                }
+
+                value
+            },
+            c if is_valid_start_indent(c) || c == '-' => self.consume_while(is_valid_indent),
+            _ => return None,
+        };
+
+        self.consume_while(char::is_whitespace);
+
+        let case = match self.chars.peek() {
+            Some(&c) if c != ']' => {
+                let flag = self.parse_keyword();
+
+                self.consume_while(char::is_whitespace);
+
+                match flag.as_ref() {
+                    "i" => CaseSensitivity::Insensitive,
+                    "s" => CaseSensitivity::Sensitive,
+                    _ => return None,
+                }
+            },
+            _ => CaseSensitivity::Default,
+        };
+
+        if self.chars.next() != Some(']') {
+            return None
+        }
+
+        return Some(AttributeSelector {
+            namespace,
+            name,
+            operator,
+            value,
+            case,
+        })
+    }
+
+    // A pseudo-class, with the `:` already consumed.
+    fn parse_pseudo_class(&mut self) -> Option<PseudoClass> {
+        let name = self.parse_keyword();
+
+        if self.chars.peek() != Some(&'(') {
+            return Some(match name.as_ref() {
+                "root" => PseudoClass::Root,
+                "empty" => PseudoClass::Empty,
+                "first-child" => PseudoClass::FirstChild,
+                "last-child" => PseudoClass::LastChild,
+                "only-child" => PseudoClass::OnlyChild,
+                "first-of-type" => PseudoClass::FirstOfType,
+                "last-of-type" => PseudoClass::LastOfType,
+                "only-of-type" => PseudoClass::OnlyOfType,
+                "hover" => PseudoClass::Hover,
+                "active" => PseudoClass::Active,
+                "focus" => PseudoClass::Focus,
+                "checked" => PseudoClass::Checked,
+                "disabled" => PseudoClass::Disabled,
+                "enabled" => PseudoClass::Enabled,
+                "link" => PseudoClass::Link,
+                "visited" => PseudoClass::Visited,
+                _ => return None,
+            })
+        }
+
+        self.chars.next();
+
+        let args = self.parse_function_arguments()?;
+
+        return match name.as_ref() {
+            "nth-child" | "nth-last-child" => {
+                let (nth, of) = match find_of_keyword(&args) {
+                    Some(i) => (parse_nth(&args[..i])?, parse_selector_list(&args[i + 2..])?),
+                    None => (parse_nth(&args)?, Vec::new()),
+                };
+
+                match name.as_ref() {
+                    "nth-child" => Some(PseudoClass::NthChild(nth, of)),
+                    _ => Some(PseudoClass::NthLastChild(nth, of)),
+                }
+            },
+            "nth-of-type" => Some(PseudoClass::NthOfType(parse_nth(&args)?)),
+            "nth-last-of-type" => Some(PseudoClass::NthLastOfType(parse_nth(&args)?)),
+            "not" => Some(PseudoClass::Not(parse_selector_list(&args)?)),
+            // :is() and :where() drop the selectors they can't parse rather
+            // than failing as a whole.
+            "is" => Some(PseudoClass::Is(parse_forgiving_selector_list(&args))),
+            "where" => Some(PseudoClass::Where(parse_forgiving_selector_list(&args))),
+            "has" => {
+                let relative: Option<Vec<RelativeSelector>> = split_top_level(&args, |c| c == ',')
+                    .into_iter()
+                    .map(parse_relative_selector)
+                    .collect();
+
+                Some(PseudoClass::Has(relative?))
+            },
+            _ => None,
+        }
+    }
+
+    // Everything up to the `)` that closes a function whose `(` was already
+    // consumed, which is consumed too.
+    fn parse_function_arguments(&mut self) -> Option<String> {
+        let mut args = String::new();
+        let mut depth = 0;
+
+        loop {
+            let c = self.chars.next()?;
+
+            match c {
+                '(' => depth += 1,
+                ')' if depth == 0 => return Some(args),
+                ')' => depth -= 1,
+                _ => {},
+            }
+
+            args.push(c);
+        }
+    }
+
+    // Drops the rest of an invalid selector.
+    fn skip_selector(&mut self) -> Option<Selector> {
+        self.consume_while(|c| c != ',' && c != '{');
+
+        return None
+    }
+
+    fn parse_identifier(&mut self) -> String {
+        let mut indent = String::new();
+
+        match self.chars.peek() {
+            Some(&c) => if is_valid_start_indent(c) {
+                indent.push_str(&self.consume_while(is_valid_indent))
+            },
+            None => {}
+        }
+
+        return indent
+    }
+
+    // An identifier that is matched ASCII case-insensitively, like an at-rule
+    // name or a pseudo-class.
+    fn parse_keyword(&mut self) -> String {
+        return self.parse_identifier().to_ascii_lowercase()
+    }
+
+    fn parse_id(&mut self) -> Option<String> {
+        return match &self.parse_identifier()[..] {
+            "" => None,
+            s @ _ => Some(s.to_string()),
+        }
+    }
+
+    fn parse_declarations(&mut self) -> Vec<Declaration> {
+        let mut declarations = Vec::<Declaration>::new();
+
+        while self.chars.peek().map_or(false, |c| *c != '}') {
+            declarations.extend(self.parse_declaration().into_iter().flatten());
+        }
+
+        self.chars.next();
+
+        return declarations
+    }
+
+    // One declaration and the `;` after it, as the declarations of its
+    // longhands if it's a shorthand. Invalid ones give `None`.
+    fn parse_declaration(&mut self) -> Option<Vec<Declaration>> {
+        self.consume_while(char::is_whitespace);
+
+        // Custom property names are case-sensitive. Values keep their case
+        // until they're parsed, where only keywords are folded.
+        let mut property = self.consume_while(|x| x != ':' && x != ';' && x != '}').trim_end().to_string();
+
+        if !is_custom_property(&property) {
+            property = property.to_ascii_lowercase();
+        }
+
+        // Anything that isn't `name:` is skipped up to the end of the
+        // declaration.
+        if self.chars.peek() != Some(&':') || !is_property_name(&property) {
+            self.consume_component_values();
+
+            if self.chars.peek() == Some(&';') {
+                self.chars.next();
+            }
+
+            self.consume_while(char::is_whitespace);
+
+            return None
+        }
+
+        self.chars.next();
+
+        self.consume_while(char::is_whitespace);
+
+        let mut value = self.consume_component_values().trim_end().to_string();
+
+        let important = strip_important(&mut value);
+
+        // A value that doesn't match the property's grammar makes the whole
+        // declaration invalid, so it is dropped.
+        let declaration = parse_property(&property, &value, important);
+
+        let terminated = if self.chars.peek().map_or(false, |c| *c == ';') {
+            self.chars.next();
+
+            true
+        } else {
+            self.consume_while(char::is_whitespace);
+
+            // The last declaration may end at the end of input too.
+            self.chars.peek().map_or(true, |c| *c == '}')
+        };
+
+        self.consume_while(char::is_whitespace);
+
+        return declaration.filter(|_| terminated)
+    }
+
+    // A declaration value, up to a `;` or `}` that isn't inside brackets or
+    // a string.
+    fn consume_component_values(&mut self) -> String {
+        let mut value = String::new();
+        let mut quote = None;
+        let mut closers = Vec::new();
+
+        while let Some(&c) = self.chars.peek() {
+            match (quote, c) {
+                (None, ';') | (None, '}') if closers.is_empty() => break,
+                (Some(q), c) if c == q => quote = None,
+                // A newline ends an unterminated string.
+                (Some(_), '\n') => quote = None,
+                (Some(_), '\\') => {
+                    value.push(c);
+                    self.chars.next();
+                    value.extend(self.chars.next());
+
+                    continue
+                },
+                (Some(_), _) => {},
+                (None, '"') | (None, '\'') => quote = Some(c),
+                (None, '(') => closers.push(')'),
+                (None, '[') => closers.push(']'),
+                (None, '{') => closers.push('}'),
+                (None, c) if closers.last() == Some(&c) => {
+                    closers.pop();
+                },
+                _ => {},
+            }
+
+            value.push(c);
+            self.chars.next();
+        }
+
+        return value
+    }
+
+    fn parse_supports_condition(&mut self) -> Option<SupportsCondition> {
+        self.consume_while(char::is_whitespace);
+
+        if self.starts_with_not_keyword() {
+            self.parse_keyword();
+
+            let condition = SupportsCondition::Not(Box::new(self.parse_supports_in_parens()?));
+
+            self.consume_while(char::is_whitespace);
+
+            return match self.chars.peek() {
+                Some(_) => None,
+                None => Some(condition),
+            }
+        }
+
+        let first = self.parse_supports_in_parens()?;
+        let mut rest = Vec::new();
+        let mut combinator = String::new();
+
+        loop {
+            self.consume_while(char::is_whitespace);
+
+            if self.chars.peek().is_none() {
+                break;
+            }
+
+            let ident = self.parse_keyword();
+
+            if (ident != "and" && ident != "or") || (!combinator.is_empty() && combinator != ident) {
+                return None
+            }
+
+            combinator = ident;
+            rest.push(self.parse_supports_in_parens()?);
+        }
+
+        if rest.is_empty() {
+            return Some(first)
+        }
+
+        rest.insert(0, first);
+
+        return match combinator.as_ref() {
+            "and" => Some(SupportsCondition::And(rest)),
+            _ => Some(SupportsCondition::Or(rest)),
+        }
+    }
+
+    fn parse_supports_in_parens(&mut self) -> Option<SupportsCondition> {
+        self.consume_while(char::is_whitespace);
+
+        let function = self.parse_keyword();
+        let inner = self.parse_parenthesized()?;
+
+        if !function.is_empty() {
+            return match function.as_ref() {
+                "selector" => Some(SupportsCondition::Selector(inner.trim().to_string())),
+                _ => Some(SupportsCondition::Unknown(format!("{}({})", function, inner))),
+            }
+        }
+
+        let mut inner_parser = CssParser::new(&inner);
+
+        inner_parser.consume_while(char::is_whitespace);
+
+        if inner_parser.chars.peek() == Some(&'(') || inner_parser.starts_with_not_keyword() {
+            return match inner_parser.parse_supports_condition() {
+                Some(c) => Some(c),
+                None => Some(SupportsCondition::Unknown(inner)),
+            }
+        }
+
+        return match inner.find(':') {
+            Some(colon) => Some(SupportsCondition::Declaration(
+                inner[..colon].trim().to_ascii_lowercase(),
+                inner[colon + 1..].trim().to_string(),
+            )),
+            None => Some(SupportsCondition::Unknown(inner)),
+        }
+    }
+
+    // Consumes a parenthesized block, returning what's inside it.
+    fn parse_parenthesized(&mut self) -> Option<String> {
+        if self.chars.next() != Some('(') {
+            return None
+        }
+
+        let mut inner = String::new();
+        let mut depth = 1;
+
+        while let Some(c) = self.chars.next() {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => {},
+            }
+
+            if depth == 0 {
+                return Some(inner)
+            }
+
+            inner.push(c);
+        }
+
+        return None
+    }
+
+    fn parse_media_query(&mut self) -> Option<MediaQuery> {
+        let mut query = MediaQuery {
+            qualifier: None,
+            media_type: None,
+            condition: None,
+        };
+
+        self.consume_while(char::is_whitespace);
+
+        if self.chars.peek() == Some(&'(') || self.starts_with_not_condition() {
+            query.condition = Some(self.parse_media_condition(true)?);
+        } else {
+            let mut ident = self.parse_keyword();
+
+            match ident.as_ref() {
+                "not" => query.qualifier = Some(MediaQualifier::Not),
+                "only" => query.qualifier = Some(MediaQualifier::Only),
+                _ => {},
+            }
+
+            if query.qualifier.is_some() {
+                self.consume_while(char::is_whitespace);
+
+                ident = self.parse_keyword();
+            }
+
+            match ident.as_ref() {
+                "" | "not" | "only" | "and" | "or" | "layer" => return None,
+                _ => query.media_type = Some(ident),
+            }
+
+            self.consume_while(char::is_whitespace);
+
+            if self.chars.peek().is_some() {
+                if self.parse_keyword() != "and" {
+                    return None
+                }
+
+                query.condition = Some(self.parse_media_condition(false)?);
+            }
+        }
+
+        self.consume_while(char::is_whitespace);
+
+        return match self.chars.peek() {
+            Some(_) => None,
+            None => Some(query),
+        }
+    }
+
+    fn parse_media_condition(&mut self, allow_or: bool) -> Option<MediaCondition> {
+        self.consume_while(char::is_whitespace);
+
+        if self.starts_with_not_condition() {
+            self.parse_keyword();
+
+            return Some(MediaCondition::Not(Box::new(self.parse_media_in_parens()?)))
+        }
+
+        let first = self.parse_media_in_parens()?;
+        let mut rest = Vec::new();
+        let mut combinator = String::new();
+
+        loop {
+            self.consume_while(char::is_whitespace);
+
+            if self.chars.peek() == Some(&')') || self.chars.peek().is_none() {
+                break;
+            }
+
+            let ident = self.parse_keyword();
+
+            match ident.as_ref() {
+                "and" => {},
+                "or" if allow_or => {},
+                _ => return None,
+            }
+
+            // `and` and `or` can't be mixed without parentheses.
+            if !combinator.is_empty() && combinator != ident {
+                return None
+            }
+
+            combinator = ident;
+            rest.push(self.parse_media_in_parens()?);
+        }
+
+        if rest.is_empty() {
+            return Some(first)
+        }
+
+        rest.insert(0, first);
+
+        return match combinator.as_ref() {
+            "and" => Some(MediaCondition::And(rest)),
+            _ => Some(MediaCondition::Or(rest)),
+        }
+    }
+
+    fn parse_media_in_parens(&mut self) -> Option<MediaCondition> {
+        self.consume_while(char::is_whitespace);
+
+        let inner = self.parse_parenthesized()?;
+        let mut inner_parser = CssParser::new(&inner);
+
+        inner_parser.consume_while(char::is_whitespace);
+
+        if inner_parser.chars.peek() == Some(&'(') || inner_parser.starts_with_not_condition() {
+            return match inner_parser.parse_media_condition(true) {
+                Some(c) if inner_parser.chars.peek().is_none() => Some(c),
+                _ => Some(MediaCondition::Unknown(inner)),
+            }
+        }
+
+        return match parse_media_feature(&inner) {
+            Some(f) => Some(MediaCondition::Feature(f)),
+            None => Some(MediaCondition::Unknown(inner)),
+        }
+    }
+
+    fn starts_with_not_keyword(&self) -> bool {
+        let mut lookahead = CssParser { chars: self.chars.clone() };
+
+        return lookahead.parse_keyword() == "not"
+            && lookahead.chars.peek().map_or(false, |c| c.is_whitespace())
+    }
+
+    fn starts_with_not_condition(&self) -> bool {
+        let mut lookahead = CssParser { chars: self.chars.clone() };
+
+        if lookahead.parse_keyword() != "not" {
+            return false
+        }
+
+        lookahead.consume_while(char::is_whitespace);
+
+        return lookahead.chars.peek() == Some(&'(')
+    }
+
+    // The arguments of a math function, up to and including its `)`.
+    fn parse_math_function(&mut self, name: &str) -> Option<CalcNode> {
+        let mut args = vec![self.parse_calc_sum()?];
+
+        self.consume_while(char::is_whitespace);
+
+        while self.chars.peek() == Some(&',') {
+            self.chars.next();
+
+            args.push(self.parse_calc_sum()?);
+
+            self.consume_while(char::is_whitespace);
+        }
+
+        if self.chars.next() != Some(')') {
+            return None
+        }
+
+        return match (name, args.len()) {
+            ("calc", 1) => args.pop(),
+            ("min", _) => simplify_min_max(args, false),
+            ("max", _) => simplify_min_max(args, true),
+            ("clamp", 3) => {
+                let max = args.pop()?;
+                let val = args.pop()?;
+                let min = args.pop()?;
+
+                simplify_clamp(min, val, max)
+            },
+            _ => None,
+        }
+    }
+
+    // Terms joined by `+` or `-`, which need whitespace on both sides.
+    fn parse_calc_sum(&mut self) -> Option<CalcNode> {
+        let mut terms = vec![self.parse_calc_product()?];
+
+        loop {
+            let mut lookahead = CssParser { chars: self.chars.clone() };
+
+            if lookahead.consume_while(char::is_whitespace).is_empty() {
+                break
+            }
+
+            let negate = match lookahead.chars.next() {
+                Some('+') => false,
+                Some('-') => true,
+                _ => break,
+            };
+
+            if lookahead.consume_while(char::is_whitespace).is_empty() {
+                return None
+            }
+
+            self.chars = lookahead.chars;
+
+            let term = self.parse_calc_product()?;
+
+            terms.push(match negate {
+                true => scale_calc(term, -1.0),
+                false => term,
+            });
+        }
+
+        return simplify_sum(terms)
+    }
+
+    // Values joined by `*` or `/`. At least one side of a product, and the
+    // right side of a division, must be a number.
+    fn parse_calc_product(&mut self) -> Option<CalcNode> {
+        let mut product = self.parse_calc_value()?;
+
+        loop {
+            let mut lookahead = CssParser { chars: self.chars.clone() };
+
+            lookahead.consume_while(char::is_whitespace);
+
+            let divide = match lookahead.chars.next() {
+                Some('*') => false,
+                Some('/') => true,
+                _ => break,
+            };
+
+            self.chars = lookahead.chars;
+
+            let rhs = self.parse_calc_value()?;
+
+            product = match (product, rhs, divide) {
+                (lhs, CalcNode::Number(n), true) if n != 0.0 => scale_calc(lhs, 1.0 / n),
+                (lhs, CalcNode::Number(n), false) => scale_calc(lhs, n),
+                (CalcNode::Number(n), rhs, false) => scale_calc(rhs, n),
+                _ => return None,
+            };
+        }
+
+        return Some(product)
+    }
+
+    fn parse_calc_value(&mut self) -> Option<CalcNode> {
+        self.consume_while(char::is_whitespace);
+
+        return match *self.chars.peek()? {
+            '(' => {
+                self.chars.next();
+
+                let sum = self.parse_calc_sum()?;
+
+                self.consume_while(char::is_whitespace);
+
+                match self.chars.next() {
+                    Some(')') => Some(sum),
+                    _ => None,
+                }
+            },
+            c if is_valid_start_indent(c) => {
+                let name = self.parse_keyword();
+
+                match self.chars.next() {
+                    Some('(') => self.parse_math_function(&name),
+                    _ => None,
+                }
+            },
+            _ => {
+                let text = self.consume_while(|c| is_valid_indent(c) || c == '.' || c == '%' || c == '+');
+                let (number, unit) = parse_number(&text)?;
+
+                match unit {
+                    "" => Some(CalcNode::Number(number)),
+                    unit => Some(absolute_to_px(number, parse_unit(unit)?)),
+                }
+            },
+        }
+    }
+
+    fn consume_while<F>(&mut self, condition: F) -> String
+    where
+        F: Fn(char) -> bool,
+    {
+        let mut result = String::new();
+
+        while self.chars.peek().map_or(false, |c| condition(*c)) {
+            result.push(self.chars.next().unwrap());
+        };
+        
+        return result
+    }
+}
+
+// The selector of a nested rule that is just `&`.
+fn nesting_selector() -> Selector {
+    let simple = SimpleSelector {
+        pseudo_classes: vec![PseudoClass::Nesting],
+        ..SimpleSelector::default()
+    };
+
+    return Selector::new(vec![simple], Vec::new())
+}
+
+fn wrap_declarations(declarations: &mut Vec<Declaration>, rules: &mut Vec<CssRule>) {
+    if !declarations.is_empty() {
+        rules.push(CssRule::Style(Rule::new(vec![nesting_selector()], mem::replace(declarations, Vec::new()))));
+    }
+}
+
+// Exactly one rule, as CSSOM's insertRule() takes.
+pub fn parse_rule(css: &str) -> Option<CssRule> {
+    let mut rules = CssParser::new(css).parse_rules(true);
+
+    return match rules.len() {
+        1 => rules.pop(),
+        _ => None,
+    }
+}
+
+// The contents of a declaration block, without the braces.
+pub fn parse_declaration_list(css: &str) -> Vec<Declaration> {
+    return CssParser::new(css).parse_declarations()
+}
+
+pub fn parse_selector_list(selectors: &str) -> Option<Vec<Selector>> {
+    let parsed = CssParser::new(selectors).parse_selectors(false)?;
+
+    return if parsed.is_empty() { None } else { Some(parsed) }
+}
+
+fn parse_forgiving_selector_list(selectors: &str) -> Vec<Selector> {
+    return split_top_level(selectors, |c| c == ',')
+        .into_iter()
+        .filter_map(parse_selector_list)
+        .flatten()
+        .collect()
+}
+
+// `> img` or `+ p` inside :has(); a bare selector means a descendant.
+fn parse_relative_selector(selector: &str) -> Option<RelativeSelector> {
+    let selector = selector.trim();
+
+    let (combinator, rest) = match selector.chars().next()? {
+        c if is_combinator(c) => (c, &selector[1..]),
+        _ => (' ', selector),
+    };
+
+    let mut list = parse_selector_list(rest)?;
+
+    if list.len() != 1 {
+        return None
+    }
+
+    return Some(RelativeSelector {
+        combinator,
+        selector: list.pop()?,
+    })
+}
+
+fn parse_pseudo_element(name: &str) -> Option<PseudoElement> {
+    return match name {
+        "before" => Some(PseudoElement::Before),
+        "after" => Some(PseudoElement::After),
+        "marker" => Some(PseudoElement::Marker),
+        "first-line" => Some(PseudoElement::FirstLine),
+        "first-letter" => Some(PseudoElement::FirstLetter),
+        "placeholder" => Some(PseudoElement::Placeholder),
+        _ => None,
+    }
+}
+
+// The byte index of the `of` in `2n+1 of .item`.
+fn find_of_keyword(args: &str) -> Option<usize> {
+    let lower = args.to_ascii_lowercase();
+
+    return lower.find(" of ").map(|i| i + 1)
+}
+
+// An+B, or `odd` and `even`. Whitespace is allowed around the sign of B.
+fn parse_nth(value: &str) -> Option<Nth> {
+    let value: String = value.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_ascii_lowercase();
+
+    let (a, b) = match value.as_ref() {
+        "odd" => (2, 1),
+        "even" => (2, 0),
+        _ => match value.find('n') {
+            Some(n) => {
+                let a = match &value[..n] {
+                    "" | "+" => 1,
+                    "-" => -1,
+                    a => a.parse().ok()?,
+                };
+
+                let b = match &value[n + 1..] {
+                    "" => 0,
+                    b if b.starts_with('+') || b.starts_with('-') => b.parse().ok()?,
+                    _ => return None,
+                };
+
+                (a, b)
+            },
+            None => (0, value.parse().ok()?),
+        },
+    };
+
+    return Some(Nth { a, b })
+}
+
+// Parses `value` according to the grammar of `property`. Values of properties
+// the engine doesn't know about are kept as they are.
+pub fn parse_declaration_value(property: &str, value: &str) -> Option<Value> {
+    let keyword = value.to_ascii_lowercase();
+
+    if CSS_WIDE_KEYWORDS.contains(&keyword.as_str()) {
+        return Some(Value::Other(keyword))
+    }
+
+    // These can't be checked against the property's grammar until the
+    // variables are known.
+    if is_custom_property(property) || contains_var(value) {
+        return Some(Value::Tokens(tokenize(value)))
+    }
+
+    let property = match properties::lookup(property) {
+        Some(p) => p,
+        None => return Some(Value::Other(value.to_string())),
+    };
+
+    return parse_value(&property.syntax, value)
+}
+
+// The declarations `property: value` stands for: itself, or one for each
+// longhand of a shorthand. `None` when the value doesn't match the grammar.
+pub fn parse_property(property: &str, value: &str, important: bool) -> Option<Vec<Declaration>> {
+    let declaration = |property: &str, value| Declaration { property: property.to_string(), value, important };
+
+    let shorthand = match properties::lookup_shorthand(property) {
+        Some(shorthand) => shorthand,
+        None => return parse_declaration_value(property, value).map(|v| vec![declaration(property, v)]),
+    };
+
+    let keyword = value.trim().to_ascii_lowercase();
+
+    if CSS_WIDE_KEYWORDS.contains(&keyword.as_str()) {
+        return Some(shorthand.longhands.iter().map(|l| declaration(l, Value::Other(keyword.clone()))).collect())
+    }
+
+    // Which longhand each part of the value belongs to isn't known until
+    // the variables are substituted, so the cascade expands it.
+    if contains_var(value) {
+        return Some(vec![declaration(property, Value::Tokens(tokenize(value)))])
+    }
+
+    return expand_shorthand(shorthand, value).map(|values| values.into_iter().map(|(l, v)| declaration(l, v)).collect())
+}
+
+// The value of each longhand of `shorthand`, with the ones left out set to
+// their initial values.
+pub fn expand_shorthand(shorthand: &Shorthand, value: &str) -> Option<Vec<(&'static str, Value)>> {
+    let values = match shorthand.name {
+        // Keywords go to the first longhand they're valid for, so names
+        // come last, and the first time is the duration.
+        "animation" => parse_list_shorthand(value, &[
+            "animation-duration", "animation-timing-function", "animation-delay", "animation-iteration-count",
+            "animation-direction", "animation-fill-mode", "animation-play-state", "animation-name",
+        ])?,
+        "transition" => {
+            let values = parse_list_shorthand(value, &["transition-duration", "transition-timing-function", "transition-delay", "transition-property"])?;
+
+            // `none` can't be one of several transitions.
+            match values.iter().find(|&&(l, _)| l == "transition-property") {
+                Some(&(_, Value::List(ref items))) if items.len() > 1 && items.contains(&Value::Other(String::from("none"))) => return None,
+                _ => values,
+            }
+        },
+        "container" => parse_container_shorthand(value)?,
+        _ => return None,
+    };
+
+    // The CSS-wide keywords can only be the whole value.
+    let is_css_wide_keyword = |v: &Value| match *v {
+        Value::Other(ref keyword) => CSS_WIDE_KEYWORDS.contains(&keyword.as_str()),
+        _ => false,
+    };
+
+    if values.iter().any(|&(_, ref v)| is_css_wide_keyword(v)) {
+        return None
+    }
+
+    return shorthand.longhands
+        .iter()
+        .map(|&l| {
+            let value = match values.iter().find(|&&(name, _)| name == l) {
+                Some(&(_, ref value)) => Some(value.clone()),
+                None => properties::lookup(l).and_then(|p| parse_declaration_value(l, p.initial)),
+            };
+
+            value.map(|v| (l, v))
+        })
+        .collect()
+}
+
+// A comma-separated list of items like `1s ease spin`, where each part is a
+// value of the first of `longhands` it's valid for that the item hasn't set
+// yet. Longhands an item leaves out get their initial value.
+fn parse_list_shorthand(value: &str, longhands: &[&'static str]) -> Option<Vec<(&'static str, Value)>> {
+    let mut lists = vec![Vec::new(); longhands.len()];
+
+    for item in split_top_level(value, |c| c == ',') {
+        let mut values: Vec<Option<Value>> = vec![None; longhands.len()];
+
+        if item.is_empty() {
+            return None
+        }
+
+        for part in split_top_level(item, char::is_whitespace).into_iter().filter(|p| !p.is_empty()) {
+            let (i, value) = longhands
+                .iter()
+                .enumerate()
+                .filter(|&(i, _)| values[i].is_none())
+                .find_map(|(i, &l)| list_item(l, part).map(|v| (i, v)))?;
+
+            values[i] = Some(value);
+        }
+
+        for (i, value) in values.into_iter().enumerate() {
+            let value = value.or_else(|| list_item(longhands[i], properties::lookup(longhands[i])?.initial))?;
+
+            lists[i].push(value);
+        }
+    }
+
+    return Some(longhands.iter().cloned().zip(lists.into_iter().map(Value::List)).collect())
+}
+
+// `value` as the only item of the list property `longhand`.
+fn list_item(longhand: &str, value: &str) -> Option<Value> {
+    return match parse_declaration_value(longhand, value)? {
+        Value::List(mut items) if items.len() == 1 => items.pop(),
+        _ => None,
+    }
+}
+
+// <container-name> [ / <container-type> ]?
+fn parse_container_shorthand(value: &str) -> Option<Vec<(&'static str, Value)>> {
+    let mut parts = value.splitn(2, '/');
+    let name = parse_declaration_value("container-name", parts.next()?.trim())?;
+    let mut values = vec![("container-name", name)];
+
+    if let Some(container_type) = parts.next() {
+        values.push(("container-type", parse_declaration_value("container-type", container_type.trim())?));
+    }
+
+    return Some(values)
+}
+
+fn parse_value(syntax: &Syntax, value: &str) -> Option<Value> {
+    let keyword = value.trim().to_ascii_lowercase();
+
+    // Colors, lengths and keywords are case-insensitive. Strings, counter
+    // names and identifiers aren't, so those grammars fold their keywords
+    // themselves.
+    return match *syntax {
+        Syntax::Color => match keyword.as_ref() {
+            "currentcolor" => Some(Value::Other(keyword)),
+            _ => translate_color(&keyword).map(Value::Color),
+        },
+        Syntax::Length(keywords) => match keywords.contains(&keyword.as_str()) {
+            true => Some(Value::Other(keyword)),
+            false => translate_length(&keyword).or_else(|| translate_math(&keyword)),
+        },
+        Syntax::Keywords(keywords) => match keywords.contains(&keyword.as_str()) {
+            true => Some(Value::Other(keyword)),
+            false => None,
+        },
+        Syntax::Content => match keyword.as_ref() {
+            "normal" | "none" => Some(Value::Other(keyword)),
+            _ => parse_content(value).map(Value::Content),
+        },
+        Syntax::Quotes => match keyword.as_ref() {
+            "auto" | "none" => Some(Value::Other(keyword)),
+            _ => parse_quotes(value).map(Value::Quotes),
+        },
+        Syntax::Counters(default) => match keyword.as_ref() {
+            "none" => Some(Value::Other(keyword)),
+            _ => parse_counters(value, default).map(Value::Counters),
+        },
+        Syntax::Number(keywords) => match keywords.contains(&keyword.as_str()) {
+            true => Some(Value::Other(keyword)),
+            false => match parse_number(&keyword)? {
+                (n, "") => Some(Value::Number(n)),
+                (n, "%") => Some(Value::Number(n / 100.0)),
+                _ => None,
+            },
+        },
+        Syntax::Identifier(keywords) => match keywords.contains(&keyword.as_str()) {
+            true => Some(Value::Other(keyword)),
+            false => parse_custom_ident(value).map(Value::Other),
+        },
+        Syntax::Identifiers(keywords) => match keywords.contains(&keyword.as_str()) {
+            true => Some(Value::Other(keyword)),
+            false => value.split_whitespace()
+                .map(parse_custom_ident)
+                .collect::<Option<Vec<String>>>()
+                .filter(|names| !names.is_empty())
+                .map(|names| Value::Other(names.join(" "))),
+        },
+        Syntax::Time => parse_time(&keyword).map(Value::Time),
+        Syntax::Easing => parse_easing(&keyword).map(Value::Easing),
+        Syntax::Transform => match keyword.as_ref() {
+            "none" => Some(Value::Transform(Vec::new())),
+            _ => parse_transform(&keyword).map(Value::Transform),
+        },
+        // Quoted names stay quoted, since they needn't be identifiers.
+        Syntax::FontFamily => split_top_level(value, |c| c == ',')
+            .into_iter()
+            .map(|family| parse_family_name(family).map(|name| match family.starts_with('"') || family.starts_with('\'') {
+                true => Value::Other(serialize_string(&name)),
+                false => Value::Other(name),
+            }))
+            .collect::<Option<Vec<Value>>>()
+            .map(Value::List),
+        // bolder and lighter depend on the parent's weight, so they're
+        // resolved when the value is computed.
+        Syntax::FontWeight => match keyword.as_ref() {
+            "bolder" | "lighter" => Some(Value::Other(keyword)),
+            _ => parse_font_weight(&keyword).map(Value::Number),
+        },
+        // Only @font-face takes a range of oblique angles.
+        Syntax::FontStyle => match keyword.split_whitespace().count() <= 2 {
+            true => parse_font_style(&keyword).map(|_| Value::Other(keyword.split_whitespace().collect::<Vec<&str>>().join(" "))),
+            false => None,
+        },
+        Syntax::FontStretch => parse_font_stretch(&keyword).map(|n| Value::Length(n, Unit::Pct)),
+        Syntax::List(item) => split_top_level(value, |c| c == ',')
+            .into_iter()
+            .map(|v| parse_value(item, v))
+            .collect::<Option<Vec<Value>>>()
+            .map(Value::List),
+    }
+}
+
+// A name like an animation name, which can't be a CSS-wide keyword or
+// `default`.
+fn parse_custom_ident(value: &str) -> Option<String> {
+    let value = value.trim();
+    let start = value.strip_prefix('-').unwrap_or(value);
+
+    if !start.starts_with(is_valid_start_indent) || !value.chars().all(is_valid_indent) {
+        return None
+    }
+
+    return match value.eq_ignore_ascii_case("default") || CSS_WIDE_KEYWORDS.contains(&value.to_ascii_lowercase().as_str()) {
+        true => None,
+        false => Some(value.to_string()),
+    }
+}
+
+// In seconds.
+fn parse_time(value: &str) -> Option<f32> {
+    return match parse_number(value)? {
+        (n, "s") => Some(n),
+        (n, "ms") => Some(n / 1000.0),
+        _ => None,
+    }
+}
+
+fn parse_easing(value: &str) -> Option<EasingFunction> {
+    let easing = match value {
+        "linear" => EasingFunction::Linear,
+        "ease" => EasingFunction::CubicBezier(0.25, 0.1, 0.25, 1.0),
+        "ease-in" => EasingFunction::CubicBezier(0.42, 0.0, 1.0, 1.0),
+        "ease-out" => EasingFunction::CubicBezier(0.0, 0.0, 0.58, 1.0),
+        "ease-in-out" => EasingFunction::CubicBezier(0.42, 0.0, 0.58, 1.0),
+        "step-start" => EasingFunction::Steps(1, StepPosition::JumpStart),
+        "step-end" => EasingFunction::Steps(1, StepPosition::JumpEnd),
+        _ => {
+            let open = value.find('(')?;
+
+            if find_closing_paren(value, open)? != value.len() - 1 {
+                return None
+            }
+
+            let args: Vec<&str> = split_top_level(&value[open + 1..value.len() - 1], |c| c == ',');
+
+            match (value[..open].trim(), args.as_slice()) {
+                ("cubic-bezier", [x1, y1, x2, y2]) => {
+                    let number = |v: &str| match parse_number(v)? {
+                        (n, "") => Some(n),
+                        _ => None,
+                    };
+                    let (x1, y1, x2, y2) = (number(x1)?, number(y1)?, number(x2)?, number(y2)?);
+
+                    // The curve has to be a function of time.
+                    if x1 < 0.0 || x1 > 1.0 || x2 < 0.0 || x2 > 1.0 {
+                        return None
+                    }
+
+                    EasingFunction::CubicBezier(x1, y1, x2, y2)
+                },
+                ("steps", [steps]) => EasingFunction::Steps(steps.parse().ok().filter(|&n| n > 0)?, StepPosition::JumpEnd),
+                ("steps", [steps, position]) => {
+                    let position = match *position {
+                        "jump-start" | "start" => StepPosition::JumpStart,
+                        "jump-end" | "end" => StepPosition::JumpEnd,
+                        "jump-none" => StepPosition::JumpNone,
+                        "jump-both" => StepPosition::JumpBoth,
+                        _ => return None,
+                    };
+
+                    // jump-none needs two steps to go anywhere.
+                    let min = if position == StepPosition::JumpNone { 2 } else { 1 };
+
+                    EasingFunction::Steps(steps.parse().ok().filter(|&n| n >= min)?, position)
+                },
+                _ => return None,
+            }
+        },
+    };
+
+    return Some(easing)
+}
+
+// Space-separated 2D transform functions.
+fn parse_transform(value: &str) -> Option<Vec<TransformFunction>> {
+    let mut functions = Vec::new();
+    let mut rest = value.trim();
+
+    while !rest.is_empty() {
+        let open = rest.find('(')?;
+        let close = find_closing_paren(rest, open)?;
+        let args = split_top_level(&rest[open + 1..close], |c| c == ',');
+
+        let length = |v: &str| translate_length(v).or_else(|| translate_math(v));
+        let number = |v: &str| match parse_number(v)? {
+            (n, "") => Some(n),
+            _ => None,
+        };
+        let zero = || Value::Length(0.0, Unit::Px);
+
+        let function = match (rest[..open].trim(), args.as_slice()) {
+            ("translate", [x]) => TransformFunction::Translate(length(x)?, zero()),
+            ("translate", [x, y]) => TransformFunction::Translate(length(x)?, length(y)?),
+            ("translatex", [x]) => TransformFunction::Translate(length(x)?, zero()),
+            ("translatey", [y]) => TransformFunction::Translate(zero(), length(y)?),
+            ("scale", [s]) => TransformFunction::Scale(number(s)?, number(s)?),
+            ("scale", [x, y]) => TransformFunction::Scale(number(x)?, number(y)?),
+            ("scalex", [x]) => TransformFunction::Scale(number(x)?, 1.0),
+            ("scaley", [y]) => TransformFunction::Scale(1.0, number(y)?),
+            ("rotate", [angle]) => TransformFunction::Rotate(parse_angle(angle)?),
+            ("skew", [x]) => TransformFunction::Skew(parse_angle(x)?, 0.0),
+            ("skew", [x, y]) => TransformFunction::Skew(parse_angle(x)?, parse_angle(y)?),
+            ("skewx", [x]) => TransformFunction::Skew(parse_angle(x)?, 0.0),
+            ("skewy", [y]) => TransformFunction::Skew(0.0, parse_angle(y)?),
+            ("matrix", [a, b, c, d, e, f]) => {
+                TransformFunction::Matrix([number(a)?, number(b)?, number(c)?, number(d)?, number(e)?, number(f)?])
+            },
+            _ => return None,
+        };
+
+        functions.push(function);
+
+        rest = rest[close + 1..].trim_start();
+    }
+
+    return Some(functions)
+}
+
+// In degrees. Only 0 can be written without a unit.
+fn parse_angle(value: &str) -> Option<f32> {
+    return match parse_number(value.trim())? {
+        (n, "deg") => Some(n),
+        (n, "grad") => Some(n * 0.9),
+        (n, "rad") => Some(n.to_degrees()),
+        (n, "turn") => Some(n * 360.0),
+        (n, "") if n == 0.0 => Some(0.0),
+        _ => None,
+    }
+}
+
+// Strings, attr(), counter(), counters() and the quote keywords.
+fn parse_content(value: &str) -> Option<Vec<ContentItem>> {
+    let tokens = tokenize(value);
+    let mut items = Vec::new();
+    let mut i = 0;
+
+    while i < tokens.len() {
+        let item = match tokens[i] {
+            Token::Whitespace => {
+                i += 1;
+
+                continue
+            },
+            Token::Other(ref s) if s.starts_with('"') || s.starts_with('\'') => ContentItem::String(parse_string(s)?),
+            Token::Ident(ref keyword) => match keyword.to_ascii_lowercase().as_ref() {
+                "open-quote" => ContentItem::OpenQuote,
+                "close-quote" => ContentItem::CloseQuote,
+                "no-open-quote" => ContentItem::NoOpenQuote,
+                "no-close-quote" => ContentItem::NoCloseQuote,
+                _ => return None,
+            },
+            Token::Function(ref name) => {
+                let close = i + tokens[i..].iter().position(|t| *t == Token::CloseParen)?;
+                let args = function_args(&tokens[i + 1..close]);
+
+                i = close;
+
+                match (name.as_ref(), args.as_slice()) {
+                    // Attribute names are case-insensitive in HTML documents.
+                    ("attr", [name]) => ContentItem::Attr(name.to_ascii_lowercase()),
+                    ("counter", [name]) => ContentItem::Counter(name.clone(), String::from("decimal")),
+                    ("counter", [name, style]) => ContentItem::Counter(name.clone(), style.to_ascii_lowercase()),
+                    ("counters", [name, separator]) => {
+                        ContentItem::Counters(name.clone(), parse_string(separator)?, String::from("decimal"))
+                    },
+                    ("counters", [name, separator, style]) => {
+                        ContentItem::Counters(name.clone(), parse_string(separator)?, style.to_ascii_lowercase())
+                    },
+                    _ => return None,
+                }
+            },
+            _ => return None,
+        };
+
+        items.push(item);
+
+        i += 1;
+    }
+
+    return Some(items)
+}
+
+// The comma-separated arguments of a function, without nesting.
+fn function_args(tokens: &[Token]) -> Vec<String> {
+    return tokens
+        .split(|t| *t == Token::Comma)
+        .map(|arg| serialize_tokens(arg).trim().to_string())
+        .collect()
+}
+
+// A quoted string, with its escapes resolved.
+fn parse_string(value: &str) -> Option<String> {
+    let mut chars = value.chars().peekable();
+    let quote = chars.next()?;
+    let mut result = String::new();
+
+    if quote != '"' && quote != '\'' {
+        return None
+    }
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => result.extend(consume_escape(&mut chars)),
+            c if c == quote => return Some(result),
+            c => result.push(c),
+        }
+    }
+
+    return None
+}
+
+// The character an escape stands for, after its backslash: one to six hex
+// digits and an optional whitespace character, or any other character as
+// itself. Escaped newlines, which continue a string onto the next line, and
+// a backslash at the end stand for nothing.
+fn consume_escape<I>(chars: &mut Peekable<I>) -> Option<char>
+where
+    I: Iterator<Item = char>,
+{
+    let first = chars.next()?;
+
+    if first == '\n' {
+        return None
+    }
+
+    let mut code = match first.to_digit(16) {
+        Some(digit) => digit,
+        None => return Some(first),
+    };
+
+    for _ in 1..6 {
+        match chars.peek().and_then(|c| c.to_digit(16)) {
+            Some(digit) => {
+                code = code * 16 + digit;
+                chars.next();
+            },
+            None => break,
+        }
+    }
+
+    if chars.peek().map_or(false, |c| c.is_whitespace()) {
+        chars.next();
+    }
+
+    // char::from_u32 rejects surrogates and code points past U+10FFFF.
+    return match code {
+        0 => Some('\u{fffd}'),
+        code => Some(char::from_u32(code).unwrap_or('\u{fffd}')),
+    }
+}
+
+fn parse_quotes(value: &str) -> Option<Vec<(String, String)>> {
+    let strings: Vec<String> = tokenize(value)
+        .into_iter()
+        .filter(|t| *t != Token::Whitespace)
+        .map(|t| match t {
+            Token::Other(s) => parse_string(&s),
+            _ => None,
+        })
+        .collect::<Option<_>>()?;
+
+    if strings.is_empty() || strings.len() % 2 != 0 {
+        return None
+    }
+
+    return Some(strings.chunks(2).map(|pair| (pair[0].clone(), pair[1].clone())).collect())
+}
+
+// `name [integer]?` pairs, as in `counter-increment: section 2 figure`.
+fn parse_counters(value: &str, default: i32) -> Option<Vec<(String, i32)>> {
+    let mut counters = Vec::new();
+    let mut parts = value.split_whitespace().peekable();
+
+    while let Some(name) = parts.next() {
+        if !name.starts_with(is_valid_start_indent) {
+            return None
+        }
+
+        let n = match parts.peek().and_then(|p| p.parse().ok()) {
+            Some(n) => {
+                parts.next();
+                n
+            },
+            None => default,
+        };
+
+        counters.push((name.to_string(), n));
+    }
+
+    return Some(counters)
+}
+
+pub fn is_custom_property(property: &str) -> bool {
+    return property.starts_with("--")
+}
+
+fn is_property_name(name: &str) -> bool {
+    let start = match is_custom_property(name) {
+        true => &name[2..],
+        false => name.strip_prefix('-').unwrap_or(name),
+    };
+
+    return (is_custom_property(name) || start.starts_with(is_valid_start_indent))
+        && start.chars().all(is_valid_indent)
+}
+
+fn contains_var(value: &str) -> bool {
+    return value.to_ascii_lowercase().contains("var(")
+}
+
+// Splits a value into the tokens var() substitution works with. Function
+// names are lowercased; everything else is kept as written.
+pub fn tokenize(value: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = value.trim().chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => {
+                while chars.peek().map_or(false, |c| c.is_whitespace()) {
+                    chars.next();
+                }
+
+                Token::Whitespace
+            },
+            ',' => Token::Comma,
+            '(' => Token::Function(String::new()),
+            ')' => Token::CloseParen,
+            '"' | '\'' => {
+                let mut string = c.to_string();
+
+                while let Some(next) = chars.next() {
+                    string.push(next);
+
+                    // Escapes are kept as they're written, and resolved when
+                    // the string is parsed.
+                    match next {
+                        '\\' => string.extend(chars.next()),
+                        n if n == c => break,
+                        _ => {},
+                    }
+                }
+
+                Token::Other(string)
+            },
+            c if is_valid_start_indent(c) || (c == '-' && chars.peek().map_or(false, |&n| is_valid_start_indent(n) || n == '-')) => {
+                let mut name = c.to_string();
+
+                while chars.peek().map_or(false, |&n| is_valid_indent(n)) {
+                    name.extend(chars.next());
+                }
+
+                match chars.peek() {
+                    Some('(') => {
+                        chars.next();
+                        Token::Function(name.to_ascii_lowercase())
+                    },
+                    _ => Token::Ident(name),
+                }
+            },
+            c if c.is_digit(10) || c == '.' || c == '#' || c == '+' || c == '-' => {
+                let mut other = c.to_string();
+
+                while chars.peek().map_or(false, |&n| is_valid_indent(n) || n == '.' || n == '%') {
+                    other.extend(chars.next());
+                }
+
+                Token::Other(other)
+            },
+            c => Token::Other(c.to_string()),
+        };
+
+        tokens.push(token);
+    }
+
+    return tokens
+}
+
+// @import <url> [layer | layer(<name>)]? [supports(<condition>)]? <media-query-list>?
+fn parse_import_prelude(prelude: &str) -> Option<ImportRule> {
+    let prelude = prelude.trim();
+
+    let (url, mut rest) = if prelude.starts_with("url(") {
+        let close = prelude.find(')')?;
+
+        (unquote(&prelude[4..close])?, &prelude[close + 1..])
+    } else {
+        let quote = prelude.chars().next()?;
+
+        if quote != '"' && quote != '\'' {
+            return None
+        }
+
+        let close = prelude[1..].find(quote)? + 1;
+
+        (prelude[1..close].to_string(), &prelude[close + 1..])
+    };
+
+    rest = rest.trim_start();
+
+    let layer = if rest.starts_with("layer(") {
+        let close = rest.find(')')?;
+        let name = parse_layer_name(&rest[6..close])?;
+
+        rest = rest[close + 1..].trim_start();
+
+        Some(ImportLayer::Named(name))
+    } else if rest == "layer" || rest.starts_with("layer ") {
+        rest = rest[5..].trim_start();
+
+        Some(ImportLayer::Anonymous)
+    } else {
+        None
+    };
+
+    let supports = if rest.starts_with("supports(") {
+        let close = find_closing_paren(rest, 8)?;
+        let condition = rest[9..close].trim();
+
+        // `supports()` also accepts a bare declaration.
+        let condition = match CssParser::new(condition).parse_supports_condition() {
+            Some(c) => c,
+            None => CssParser::new(&format!("({})", condition)).parse_supports_condition()?,
+        };
+
+        rest = rest[close + 1..].trim_start();
+
+        Some(condition)
+    } else {
+        None
+    };
+
+    return Some(ImportRule {
+        url,
+        layer,
+        supports,
+        media: parse_media_query_list(rest),
+        stylesheet: None,
+    })
+}
+
+// The descriptors of an @font-face block. Invalid descriptors are ignored
+// like invalid declarations, but the rule needs a family and a source.
+fn parse_font_face(block: &str) -> Option<FontFaceRule> {
+    let mut family = None;
+    let mut sources = None;
+    let mut rule = FontFaceRule::new(String::new(), Vec::new());
+
+    for descriptor in split_top_level(block, |c| c == ';') {
+        let colon = match descriptor.find(':') {
+            Some(colon) => colon,
+            None => continue,
+        };
+
+        let value = descriptor[colon + 1..].trim();
+
+        match descriptor[..colon].trim().to_ascii_lowercase().as_ref() {
+            "font-family" => family = parse_family_name(value).or(family),
+            "src" => sources = parse_font_sources(value).or(sources),
+            "font-weight" => if let Some(weight) = parse_font_range(value, parse_font_weight) {
+                rule.weight = weight;
+            },
+            "font-stretch" => if let Some(stretch) = parse_font_range(value, parse_font_stretch) {
+                rule.stretch = stretch;
+            },
+            "font-style" => if let Some(style) = parse_font_style(value) {
+                rule.style = style;
+            },
+            "unicode-range" => if let Some(ranges) = split_top_level(value, |c| c == ',')
+                .into_iter()
+                .map(parse_unicode_range)
+                .collect::<Option<Vec<_>>>()
+            {
+                rule.unicode_range = ranges;
+            },
+            "font-display" => {
+                rule.display = match value.to_ascii_lowercase().as_ref() {
+                    "auto" => FontDisplay::Auto,
+                    "block" => FontDisplay::Block,
+                    "swap" => FontDisplay::Swap,
+                    "fallback" => FontDisplay::Fallback,
+                    "optional" => FontDisplay::Optional,
+                    _ => rule.display,
+                }
+            },
+            _ => {},
+        }
+    }
+
+    rule.family = family?;
+    rule.sources = sources?;
+
+    return Some(rule)
+}
+
+// A quoted string, or identifiers that are joined by single spaces.
+pub fn parse_family_name(value: &str) -> Option<String> {
+    if value.starts_with('"') || value.starts_with('\'') {
+        return parse_string(value)
+    }
+
+    let words: Vec<&str> = value.split_whitespace().collect();
+
+    if words.is_empty() || !words.iter().all(|w| w.starts_with(is_valid_start_indent) && w.chars().all(is_valid_indent)) {
+        return None
+    }
+
+    return Some(words.join(" "))
+}
+
+// Sources that can't be used, like ones with an unknown format(), are
+// dropped.
+fn parse_font_sources(value: &str) -> Option<Vec<FontSource>> {
+    let mut sources = Vec::new();
+
+    for source in split_top_level(value, |c| c == ',') {
+        let lower = source.to_ascii_lowercase();
+        let open = match source.find('(') {
+            Some(open) => open,
+            None => continue,
+        };
+        let close = match find_closing_paren(source, open) {
+            Some(close) => close,
+            None => continue,
+        };
+
+        if lower.starts_with("local(") {
+            sources.extend(parse_family_name(source[open + 1..close].trim()).map(FontSource::Local));
+
+            continue
+        }
+
+        if !lower.starts_with("url(") {
+            continue
+        }
+
+        let url = match unquote(&source[open + 1..close]) {
+            Some(url) => url,
+            None => continue,
+        };
+
+        let rest = source[close + 1..].trim();
+
+        // We only read TrueType and OpenType files, and no font technologies.
+        let format = match rest.find('(') {
+            None if rest.is_empty() => None,
+            Some(open) if rest[..open].trim().eq_ignore_ascii_case("format") && rest.ends_with(')') => {
+                match unquote(&rest[open + 1..rest.len() - 1]).map(|f| f.to_ascii_lowercase()) {
+                    Some(ref f) if f == "truetype" || f == "opentype" || f == "collection" => Some(f.clone()),
+                    _ => continue,
+                }
+            },
+            _ => continue,
+        };
+
+        sources.push(FontSource::Url { url, format });
+    }
+
+    return match sources.is_empty() {
+        true => None,
+        false => Some(sources),
+    }
+}
+
+// One value, or a `min max` range. `auto` is the initial value.
+fn parse_font_range(value: &str, parse: fn(&str) -> Option<f32>) -> Option<(f32, f32)> {
+    let values: Vec<&str> = value.split_whitespace().collect();
+
+    return match values.as_slice() {
+        [auto] if auto.eq_ignore_ascii_case("auto") => Some((parse("normal")?, parse("normal")?)),
+        [value] => parse(value).map(|v| (v, v)),
+        [min, max] => {
+            let (min, max) = (parse(min)?, parse(max)?);
+
+            Some((min.min(max), min.max(max)))
+        },
+        _ => None,
+    }
+}
+
+fn parse_font_weight(value: &str) -> Option<f32> {
+    return match value.to_ascii_lowercase().as_ref() {
+        "normal" => Some(400.0),
+        "bold" => Some(700.0),
+        _ => match parse_number(value)? {
+            (n, "") if n >= 1.0 && n <= 1000.0 => Some(n),
+            _ => None,
+        },
+    }
+}
+
+// As a percentage of the normal width.
+fn parse_font_stretch(value: &str) -> Option<f32> {
+    return match value.to_ascii_lowercase().as_ref() {
+        "ultra-condensed" => Some(50.0),
+        "extra-condensed" => Some(62.5),
+        "condensed" => Some(75.0),
+        "semi-condensed" => Some(87.5),
+        "normal" => Some(100.0),
+        "semi-expanded" => Some(112.5),
+        "expanded" => Some(125.0),
+        "extra-expanded" => Some(150.0),
+        "ultra-expanded" => Some(200.0),
+        _ => match parse_number(value)? {
+            (n, "%") if n >= 0.0 => Some(n),
+            _ => None,
+        },
+    }
+}
+
+pub fn parse_font_style(value: &str) -> Option<FontStyle> {
+    let lower = value.to_ascii_lowercase();
+    let mut words = lower.split_whitespace();
+
+    let style = match words.next()? {
+        "normal" => FontStyle::Normal,
+        "italic" => FontStyle::Italic,
+        // Oblique faces lean 14 degrees unless told otherwise.
+        "oblique" => match (words.next(), words.next()) {
+            (None, _) => FontStyle::Oblique(14.0, 14.0),
+            (Some(angle), None) => {
+                let angle = parse_oblique_angle(angle)?;
+
+                FontStyle::Oblique(angle, angle)
+            },
+            (Some(min), Some(max)) => {
+                let (min, max) = (parse_oblique_angle(min)?, parse_oblique_angle(max)?);
+
+                FontStyle::Oblique(min.min(max), min.max(max))
+            },
+        },
+        _ => return None,
+    };
+
+    return match words.next() {
+        Some(_) => None,
+        None => Some(style),
+    }
+}
+
+// In degrees, between -90 and 90.
+fn parse_oblique_angle(value: &str) -> Option<f32> {
+    let degrees = parse_angle(value)?;
+
+    return match degrees >= -90.0 && degrees <= 90.0 {
+        true => Some(degrees),
+        false => None,
+    }
+}
+
+// `U+26`, `U+0-7F` or `U+4??`, where each `?` is any hex digit.
+fn parse_unicode_range(value: &str) -> Option<(u32, u32)> {
+    let value = value.trim();
+
+    if !value.starts_with("U+") && !value.starts_with("u+") {
+        return None
+    }
+
+    let value = &value[2..];
+
+    let parse_hex = |hex: &str| match hex.len() {
+        1..=6 => u32::from_str_radix(hex, 16).ok(),
+        _ => None,
+    };
+
+    let (start, end) = match value.find('-') {
+        Some(dash) => (parse_hex(&value[..dash])?, parse_hex(&value[dash + 1..])?),
+        None if value.contains('?') => {
+            let digits = value.trim_end_matches('?');
+
+            if digits.contains('?') || value.len() > 6 {
+                return None
+            }
+
+            let wildcards = (value.len() - digits.len()) as u32;
+            let start = if digits.is_empty() { 0 } else { parse_hex(digits)? } << (4 * wildcards);
+
+            (start, start + (1 << (4 * wildcards)) - 1)
+        },
+        None => {
+            let c = parse_hex(value)?;
+
+            (c, c)
+        },
+    };
+
+    return match start <= end && end <= 0x10ffff {
+        true => Some((start, end)),
+        false => None,
+    }
+}
+
+// An identifier other than `none`, or a string.
+fn parse_keyframes_name(prelude: &str) -> Option<String> {
+    let prelude = prelude.trim();
+
+    if prelude.starts_with('"') || prelude.starts_with('\'') {
+        return parse_string(prelude)
+    }
+
+    return parse_custom_ident(prelude).filter(|name| !name.eq_ignore_ascii_case("none"))
+}
+
+// `name? condition?`, where at least one of them is given. The name is a
+// custom identifier, and case-sensitive.
+fn parse_container_prelude(prelude: &str) -> Option<(Option<String>, Option<MediaCondition>)> {
+    let mut parser = CssParser::new(prelude);
+
+    parser.consume_while(char::is_whitespace);
+
+    let name = match parser.chars.peek().cloned() {
+        Some('(') => None,
+        Some(_) if parser.starts_with_not_condition() => None,
+        Some(_) => {
+            let name = parse_custom_ident(&parser.consume_while(|c| !c.is_whitespace() && c != '('))?;
+
+            match name.to_ascii_lowercase().as_ref() {
+                "none" | "and" | "or" | "not" => return None,
+                _ => Some(name),
+            }
+        },
+        None => return None,
+    };
+
+    parser.consume_while(char::is_whitespace);
+
+    let condition = match parser.chars.peek() {
+        Some(_) => Some(parser.parse_media_condition(true)?),
+        None => None,
+    };
+
+    parser.consume_while(char::is_whitespace);
+
+    return match parser.chars.peek() {
+        Some(_) => None,
+        None => Some((name, condition)),
+    }
+}
+
+// Layer names are dot-separated identifiers, like `framework.base`.
+fn parse_layer_name(name: &str) -> Option<String> {
+    let parts: Vec<&str> = name.split('.').map(str::trim).collect();
+
+    if parts.iter().any(|p| !is_media_feature_name(p)) {
+        return None
+    }
+
+    return Some(parts.join("."))
+}
+
+fn resolve_imports(
+    rules: &mut Vec<CssRule>,
+    loader: &mut dyn StylesheetLoader,
+    base: Option<&str>,
+    import_stack: &mut Vec<String>,
+) {
+    for rule in rules.iter_mut() {
+        let import = match *rule {
+            CssRule::Import(ref mut i) => i,
+            _ => continue,
+        };
+
+        let url = loader.resolve(&import.url, base);
+
+        // A stylesheet that (indirectly) imports itself is only applied once.
+        if import_stack.contains(&url) {
+            continue;
+        }
+
+        let text = match loader.load(&url) {
+            Some(t) => t,
+            None => continue,
+        };
+
+        let mut sheet = CssParser::new(&text).parse_stylesheet();
+
+        import_stack.push(url.clone());
+        resolve_imports(&mut sheet.rules, loader, Some(&url), import_stack);
+        import_stack.pop();
+
+        import.stylesheet = Some(sheet);
+    }
+}
+
+fn unquote(value: &str) -> Option<String> {
+    let value = value.trim();
+
+    return match value.chars().next() {
+        Some(q) if q == '"' || q == '\'' => {
+            if value.len() < 2 || !value.ends_with(q) {
+                return None
+            }
+
+            Some(value[1..value.len() - 1].to_string())
+        },
+        _ => Some(value.to_string()),
+    }
+}
+
+// Finds the parenthesis that closes the one at byte index `open`.
+fn find_closing_paren(value: &str, open: usize) -> Option<usize> {
+    let mut depth = 0;
+
+    for (i, c) in value[open..].char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+
+                if depth == 0 {
+                    return Some(open + i)
+                }
+            },
+            _ => {},
+        }
+    }
+
+    return None
+}
+
+pub fn parse_media_query_list(value: &str) -> Vec<MediaQuery> {
+    if value.trim().is_empty() {
+        return Vec::new()
+    }
+
+    // A query that fails to parse becomes `not all` rather than invalidating
+    // the whole list.
+    return split_top_level(value, |c| c == ',')
+        .into_iter()
+        .map(|q| CssParser::new(q).parse_media_query().unwrap_or(MediaQuery {
+            qualifier: Some(MediaQualifier::Not),
+            media_type: Some(String::from("all")),
+            condition: None,
+        }))
+        .collect()
+}
+
+fn parse_media_feature(feature: &str) -> Option<MediaFeature> {
+    let feature = feature.trim().to_ascii_lowercase();
+    let feature = feature.as_str();
+
+    if let Some(colon) = feature.find(':') {
+        let name = feature[..colon].trim();
+
+        if !is_media_feature_name(name) {
+            return None
+        }
+
+        return Some(MediaFeature::Plain(name.to_string(), parse_media_value(&feature[colon + 1..])?))
+    }
+
+    if is_media_feature_name(feature) {
+        return Some(MediaFeature::Boolean(feature.to_string()))
+    }
+
+    let mut parts = Vec::new();
+    let mut ops = Vec::new();
+    let mut rest = feature;
+
+    while let Some(i) = rest.find(|c| c == '<' || c == '>' || c == '=') {
+        parts.push(rest[..i].trim());
+
+        let op_len = if rest[i + 1..].starts_with('=') && !rest[i..].starts_with('=') { 2 } else { 1 };
+
+        ops.push(match &rest[i..i + op_len] {
+            "<" => RangeOp::Lt,
+            "<=" => RangeOp::Le,
+            ">" => RangeOp::Gt,
+            ">=" => RangeOp::Ge,
+            _ => RangeOp::Eq,
+        });
+
+        rest = &rest[i + op_len..];
+    }
+
+    parts.push(rest.trim());
+
+    return match (parts.len(), ops.len()) {
+        (2, 1) if is_media_feature_name(parts[0]) => Some(MediaFeature::Range {
+            name: parts[0].to_string(),
+            start: None,
+            end: Some((ops[0], parse_media_value(parts[1])?)),
+        }),
+        (2, 1) if is_media_feature_name(parts[1]) => Some(MediaFeature::Range {
+            name: parts[1].to_string(),
+            start: Some((parse_media_value(parts[0])?, ops[0])),
+            end: None,
+        }),
+        (3, 2) if is_media_feature_name(parts[1]) => {
+            let is_lt = |op: RangeOp| op == RangeOp::Lt || op == RangeOp::Le;
+            let is_gt = |op: RangeOp| op == RangeOp::Gt || op == RangeOp::Ge;
+
+            if !(is_lt(ops[0]) && is_lt(ops[1])) && !(is_gt(ops[0]) && is_gt(ops[1])) {
+                return None
+            }
+
+            Some(MediaFeature::Range {
+                name: parts[1].to_string(),
+                start: Some((parse_media_value(parts[0])?, ops[0])),
+                end: Some((ops[1], parse_media_value(parts[2])?)),
+            })
+        },
+        _ => None,
+    }
+}
+
+fn is_media_feature_name(name: &str) -> bool {
+    return name.starts_with(is_valid_start_indent) && name.chars().all(is_valid_indent)
+}
+
+fn parse_media_value(value: &str) -> Option<MediaValue> {
+    let value = value.trim();
+
+    if let Some(slash) = value.find('/') {
+        return match (parse_number(value[..slash].trim())?, parse_number(value[slash + 1..].trim())?) {
+            ((a, ""), (b, "")) => Some(MediaValue::Ratio(a, b)),
+            _ => None,
+        }
+    }
+
+    if is_media_feature_name(value) {
+        return Some(MediaValue::Ident(value.to_string()))
+    }
+
+    return match parse_number(value)? {
+        (n, "") => Some(MediaValue::Number(n)),
+        (n, "dppx") | (n, "x") => Some(MediaValue::Resolution(n)),
+        (n, "dpi") => Some(MediaValue::Resolution(n / 96.0)),
+        (n, "dpcm") => Some(MediaValue::Resolution(n * 2.54 / 96.0)),
+        _ => match translate_length(value)? {
+            Value::Length(n, unit) => Some(MediaValue::Length(n, unit)),
+            _ => None,
+        },
+    }
+}
+
+// Removes a trailing `!important` from `value`, returning whether there was one.
+pub fn strip_important(value: &mut String) -> bool {
+    let bang = match value.rfind('!') {
+        Some(i) => i,
+        None => return false,
+    };
+
+    if !value[bang + 1..].trim().eq_ignore_ascii_case("important") {
+        return false
+    }
+
+    let len = value[..bang].trim_end().len();
+
+    value.truncate(len);
+
+    return true
+}
+
+fn translate_length(value: &str) -> Option<Value> {
+    let (number, unit) = parse_number(value.trim())?;
+
+    let unit = match unit {
+        "" if number == 0.0 => Unit::Px,
+        unit => parse_unit(unit)?,
+    };
+
+    return Some(Value::Length(number, unit))
+}
+
+fn parse_unit(unit: &str) -> Option<Unit> {
+    return Some(match unit {
+        "em" => Unit::Em,
+        "ex" => Unit::Ex,
+        "ch" => Unit::Ch,
+        "rem" => Unit::Rem,
+        "vh" => Unit::Vh,
+        "vw" => Unit::Vw,
+        "vmin" => Unit::Vmin,
+        "vmax" => Unit::Vmax,
+        "px" => Unit::Px,
+        "mm" => Unit::Mm,
+        "q" => Unit::Q,
+        "cm" => Unit::Cm,
+        "in" => Unit::In,
+        "pt" => Unit::Pt,
+        "pc" => Unit::Pc,
+        "%" => Unit::Pct,
+        _ => return None,
+    })
+}
 
-                        multiple_ids = true;
+// calc(), min(), max() or clamp() in a length property.
+fn translate_math(value: &str) -> Option<Value> {
+    let mut parser = CssParser::new(value.trim());
 
-                        self.parse_id();
-                    } else {
-                        simple_sel.id = self.parse_id();
-                    }
-                }
+    let name = parser.parse_keyword();
 
-                Some(&c) if c == '.' => {
-                    self.chars.next();
+    if parser.chars.next() != Some('(') {
+        return None
+    }
 
-                    let class_name = self.parse_identifier();
+    let node = parser.parse_math_function(&name)?;
 
-                    if class_name != String::from("") {
-                        simple_sel.classes.push(class_name);
-                    }
-                }
+    if parser.chars.next().is_some() {
+        return None
+    }
 
-                _ => { self.consume_while(|c| c != ',' && c != '{'); }
-            }
-        };
+    return match node {
+        CalcNode::Number(_) => None,
+        CalcNode::Length(n, unit) => Some(Value::Length(n, unit)),
+        node => Some(Value::Calc(node)),
+    }
+}
 
-        if simple_sel != SimpleSelector::default() {
-            sel.simple.push(simple_sel)
-        };
+fn is_number(node: &CalcNode) -> bool {
+    return match *node {
+        CalcNode::Number(_) => true,
+        _ => false,
+    }
+}
 
-        return sel
+// Multiplies `node` by `k`, pushing the factor down where it can go.
+fn scale_calc(node: CalcNode, k: f32) -> CalcNode {
+    return match node {
+        CalcNode::Number(n) => CalcNode::Number(n * k),
+        CalcNode::Length(n, unit) => CalcNode::Length(n * k, unit),
+        CalcNode::Sum(terms) => CalcNode::Sum(terms.into_iter().map(|t| scale_calc(t, k)).collect()),
+        CalcNode::Scale(j, node) => CalcNode::Scale(j * k, node),
+        node => CalcNode::Scale(k, Box::new(node)),
     }
+}
 
-    fn parse_identifier(&mut self) -> String {
-        let mut indent = String::new();
+// Adds up the terms with the same unit. Numbers and lengths can't be mixed.
+fn simplify_sum(terms: Vec<CalcNode>) -> Option<CalcNode> {
+    if terms.iter().any(is_number) != terms.iter().all(is_number) {
+        return None
+    }
 
-        match self.chars.peek() {
-            Some(&c) => if is_valid_start_indent(c) {
-                indent.push_str(&self.consume_while(is_valid_indent))
-            },
-            None => {}
+    let mut simplified = Vec::new();
+
+    for term in terms {
+        match term {
+            CalcNode::Sum(inner) => inner.into_iter().for_each(|t| add_term(&mut simplified, t)),
+            term => add_term(&mut simplified, term),
         }
+    }
 
-        return indent.to_lowercase()
+    return match simplified.len() {
+        1 => simplified.pop(),
+        _ => Some(CalcNode::Sum(simplified)),
     }
+}
 
-    fn parse_id(&mut self) -> Option<String> {
-        return match &self.parse_identifier()[..] {
-            "" => None,
-            s @ _ => Some(s.to_string()),
+fn add_term(terms: &mut Vec<CalcNode>, term: CalcNode) {
+    for existing in terms.iter_mut() {
+        match (existing, &term) {
+            (&mut CalcNode::Number(ref mut m), &CalcNode::Number(n)) => return *m += n,
+            (&mut CalcNode::Length(ref mut m, u), &CalcNode::Length(n, v)) if u == v => return *m += n,
+            _ => {},
         }
     }
 
-    fn parse_declarations(&mut self) -> Vec<Declaration> {
-        let mut declarations = Vec::<Declaration>::new();
+    terms.push(term);
+}
 
-        while self.chars.peek().map_or(false, |c| *c != '}') {
-            self.consume_while(char::is_whitespace);
+// Picks the smallest or largest argument if they all share a unit.
+fn simplify_min_max(args: Vec<CalcNode>, max: bool) -> Option<CalcNode> {
+    if args.iter().any(is_number) != args.iter().all(is_number) {
+        return None
+    }
 
-            let property = self.consume_while(|x| x != ':').to_lowercase();
+    let pick = |a: f32, b: f32| if max { a.max(b) } else { a.min(b) };
 
-            self.chars.next();
+    let folded = args.iter().skip(1).try_fold(args[0].clone(), |acc, arg| match (acc, arg) {
+        (CalcNode::Number(a), &CalcNode::Number(b)) => Some(CalcNode::Number(pick(a, b))),
+        (CalcNode::Length(a, u), &CalcNode::Length(b, v)) if u == v => Some(CalcNode::Length(pick(a, b), u)),
+        _ => None,
+    });
 
-            self.consume_while(char::is_whitespace);
+    return match (folded, max) {
+        (Some(node), _) => Some(node),
+        (None, true) => Some(CalcNode::Max(args)),
+        (None, false) => Some(CalcNode::Min(args)),
+    }
+}
 
-            let value = self.consume_while(|x| x != ';' && x != '\n' && x != '}').to_lowercase();
+// clamp(min, val, max) is max(min, min(val, max)).
+fn simplify_clamp(min: CalcNode, val: CalcNode, max: CalcNode) -> Option<CalcNode> {
+    let inner = simplify_min_max(vec![val, max], false)?;
 
-            let value_enum = match property.as_ref() {
-                "background-color" | "border-color" | "color" => {
-                    Value::Color(translate_color(&value))
-                },
-                "margin-right" |
-                "margin-bottom" |
-                "margin-left" |
-                "margin-top" |
-                "padding-right" |
-                "padding-bottom" |
-                "padding-left" |
-                "padding-top" |
-                "border-right-width" |
-                "border-bottom-width" |
-                "border-left-width" |
-                "border-top-width" |
-                "height" |
-                "width" => translate_length(&value),
-                _ => Value::Other(value)
-            };
+    return match inner {
+        CalcNode::Min(mut args) => {
+            let max = args.pop()?;
+            let val = args.pop()?;
 
-            let declaration = Declaration::new(property, value_enum);
+            if is_number(&min) {
+                return None
+            }
 
-            if self.chars.peek().map_or(false, |c| *c == ';') {
-                declarations.push(declaration);
-                self.chars.next();
-            } else {
-                self.consume_while(char::is_whitespace);
+            Some(CalcNode::Clamp(Box::new(min), Box::new(val), Box::new(max)))
+        },
+        inner => simplify_min_max(vec![min, inner], true),
+    }
+}
 
-                if self.chars.peek().map_or(false, |c| *c == '}') {
-                    declarations.push(declaration);
-                }
-            };
+// Absolute lengths are all the same unit, so they can be added up when parsing.
+fn absolute_to_px(n: f32, unit: Unit) -> CalcNode {
+    let px = match unit {
+        Unit::In => n * 96.0,
+        Unit::Cm => n * 96.0 / 2.54,
+        Unit::Mm => n * 96.0 / 25.4,
+        Unit::Q => n * 96.0 / 101.6,
+        Unit::Pt => n * 96.0 / 72.0,
+        Unit::Pc => n * 16.0,
+        _ => return CalcNode::Length(n, unit),
+    };
 
-            self.consume_while(char::is_whitespace);
-        }
+    return CalcNode::Length(px, Unit::Px)
+}
 
-        self.chars.next();
+// Splits a leading CSS <number> off `value`, returning the rest (the unit).
+// An `e` only starts an exponent when digits follow, so `1em` stays `1` + `em`.
+fn parse_number(value: &str) -> Option<(f32, &str)> {
+    let bytes = value.as_bytes();
+    let mut end = 0;
 
-        return declarations
+    if end < bytes.len() && (bytes[end] == b'+' || bytes[end] == b'-') {
+        end += 1;
     }
 
-    fn consume_while<F>(&mut self, condition: F) -> String
-    where
-        F: Fn(char) -> bool,
-    {
-        let mut result = String::new();
+    let int_start = end;
 
-        while self.chars.peek().map_or(false, |c| condition(*c)) {
-            result.push(self.chars.next().unwrap());
-        };
-        
-        return result
+    while end < bytes.len() && bytes[end].is_ascii_digit() {
+        end += 1;
     }
-}
 
-fn translate_length(value: &str) -> Value {
-    let mut num_string = String::new();
-    let mut unit = String::new();
-    let mut parsing_num = true;
+    let mut has_digits = end > int_start;
 
-    for c in value.chars() {
-        if c.is_numeric() && parsing_num {
-            num_string.push(c);
-        } else {
-            unit.push(c);
-            parsing_num = false;
+    if end + 1 < bytes.len() && bytes[end] == b'.' && bytes[end + 1].is_ascii_digit() {
+        end += 1;
+
+        while end < bytes.len() && bytes[end].is_ascii_digit() {
+            end += 1;
         }
-    };
 
-    let number = num_string.parse().unwrap_or(0.0);
+        has_digits = true;
+    }
+
+    if !has_digits {
+        return None
+    }
+
+    if end < bytes.len() && (bytes[end] == b'e' || bytes[end] == b'E') {
+        let mut exp_end = end + 1;
+
+        if exp_end < bytes.len() && (bytes[exp_end] == b'+' || bytes[exp_end] == b'-') {
+            exp_end += 1;
+        }
+
+        if exp_end < bytes.len() && bytes[exp_end].is_ascii_digit() {
+            while exp_end < bytes.len() && bytes[exp_end].is_ascii_digit() {
+                exp_end += 1;
+            }
 
-    return match unit.as_ref() {
-        "em" => Value::Length(number, Unit::Em),
-        "ex" => Value::Length(number, Unit::Ex),
-        "ch" => Value::Length(number, Unit::Ch),
-        "rem" => Value::Length(number, Unit::Rem),
-        "vh" => Value::Length(number, Unit::Vh),
-        "vw" => Value::Length(number, Unit::Vw),
-        "vmin" => Value::Length(number, Unit::Vmin),
-        "vmax" => Value::Length(number, Unit::Vmax),
-        "px" | "" => Value::Length(number, Unit::Px),
-        "mm" => Value::Length(number, Unit::Mm),
-        "q" => Value::Length(number, Unit::Q),
-        "cm" => Value::Length(number, Unit::Cm),
-        "in" => Value::Length(number, Unit::In),
-        "pt" => Value::Length(number, Unit::Pt),
-        "pc" => Value::Length(number, Unit::Pc),
-        "%" => Value::Length(number, Unit::Pct),
-        _ => Value::Length(number, Unit::Px),
+            end = exp_end;
+        }
+    }
+
+    return match value[..end].parse() {
+        Ok(n) => Some((n, &value[end..])),
+        Err(_) => None,
     }
 }
 
-fn translate_color(color: &str) -> Color {
+fn translate_color(color: &str) -> Option<Color> {
     if color.starts_with("#") {
-        if color.len() == 7 {
-            let red = match u8::from_str_radix(&color[1..3], 16) {
-                Ok(n) => n as f32 / 255.0,
-                Err(_) => 0.0,
-            };
-            let green = match u8::from_str_radix(&color[3..5], 16) {
-                Ok(n) => n as f32 / 255.0,
-                Err(_) => 0.0,
-            };
-            let blue = match u8::from_str_radix(&color[5..7], 16) {
-                Ok(n) => n as f32 / 255.0,
-                Err(_) => 0.0,
-            };
-            return Color::new(red, green, blue, 1.0);
-        } else if color.len() == 4 {
-            let red = match u8::from_str_radix(&color[1..2], 16) {
-                Ok(n) => n as f32 / 15.0,
-                Err(_) => 0.0,
-            };
-            let green = match u8::from_str_radix(&color[2..3], 16) {
-                Ok(n) => n as f32 / 15.0,
-                Err(_) => 0.0,
-            };
-            let blue = match u8::from_str_radix(&color[3..4], 16) {
-                Ok(n) => n as f32 / 15.0,
-                Err(_) => 0.0,
-            };
-            return Color::new(red, green, blue, 1.0);
-        } else {
-            return Color::default();
+        return translate_hex_color(&color[1..])
+    } else if let Some((name, args)) = split_function(color) {
+        return match name {
+            "rgb" | "rgba" => translate_rgb(args),
+            "hsl" | "hsla" => translate_hsl(args),
+            "hwb" => translate_hwb(args),
+            "lab" => translate_lab(args, ColorSpace::Lab),
+            "oklab" => translate_lab(args, ColorSpace::Oklab),
+            "lch" => translate_lch(args, ColorSpace::Lch),
+            "oklch" => translate_lch(args, ColorSpace::Oklch),
+            "color" => translate_color_function(args),
+            "color-mix" => translate_color_mix(args),
+            _ => None,
         }
-    } else if color.starts_with("rgb") {
-        return Color::default();
-    } else if color.starts_with("hsl") {
-        return Color::default();
     } else {
-        return match color {
+        return Some(match color {
+            "transparent" => Color::new(0.0, 0.0, 0.0, 0.0),
             "black" => Color::new(0.0, 0.0, 0.0, 1.0),
             "silver" => Color::new(
                 0.7529411764705882,
@@ -770,9 +3334,388 @@ fn translate_color(color: &str) -> Color {
                 1.0,
             ),
             "rebeccapurple" => Color::new(0.4, 0.2, 0.6, 1.0),
-            _ => Color::new(0.0, 0.0, 0.0, 1.0),
+            _ => return None,
+        });
+    }
+}
+
+fn translate_hex_color(hex: &str) -> Option<Color> {
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None
+    }
+
+    let digits: Vec<f32> = match hex.len() {
+        3 | 4 => hex
+            .chars()
+            .map(|c| c.to_digit(16).unwrap() as f32 / 15.0)
+            .collect(),
+        6 | 8 => (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap() as f32 / 255.0)
+            .collect(),
+        _ => return None,
+    };
+
+    return Some(Color::new(
+        digits[0],
+        digits[1],
+        digits[2],
+        digits.get(3).cloned().unwrap_or(1.0),
+    ))
+}
+
+fn translate_rgb(args: &str) -> Option<Color> {
+    let (channels, alpha, legacy) = split_color_args(args)?;
+    let mut rgb = [0.0; 3];
+
+    for (i, channel) in channels.iter().enumerate() {
+        let value = match parse_color_component(channel, legacy)? {
+            (n, true) => n * 255.0 / 100.0,
+            (n, false) => n,
         };
+
+        rgb[i] = value.max(0.0).min(255.0) / 255.0;
+    }
+
+    // The legacy syntax doesn't allow mixing numbers and percentages.
+    if legacy {
+        let percentages = channels.iter().filter(|c| c.ends_with('%')).count();
+
+        if percentages != 0 && percentages != 3 {
+            return None
+        }
+    }
+
+    return Some(Color::new(rgb[0], rgb[1], rgb[2], parse_alpha(alpha, legacy)?))
+}
+
+fn translate_hsl(args: &str) -> Option<Color> {
+    let (channels, alpha, legacy) = split_color_args(args)?;
+
+    if legacy && !(channels[1].ends_with('%') && channels[2].ends_with('%')) {
+        return None
+    }
+
+    let hue = parse_hue(channels[0], legacy)?;
+    let saturation = parse_color_component(channels[1], legacy)?.0.max(0.0).min(100.0) / 100.0;
+    let lightness = parse_color_component(channels[2], legacy)?.0.max(0.0).min(100.0) / 100.0;
+
+    let (r, g, b) = hsl_to_rgb(hue, saturation, lightness);
+
+    return Some(Color::new(r, g, b, parse_alpha(alpha, legacy)?))
+}
+
+fn translate_hwb(args: &str) -> Option<Color> {
+    let (channels, alpha, legacy) = split_color_args(args)?;
+
+    if legacy {
+        return None
+    }
+
+    let hue = parse_hue(channels[0], false)?;
+    let whiteness = parse_color_component(channels[1], false)?.0.max(0.0).min(100.0) / 100.0;
+    let blackness = parse_color_component(channels[2], false)?.0.max(0.0).min(100.0) / 100.0;
+
+    let (r, g, b) = hwb_to_rgb(hue, whiteness, blackness);
+
+    return Some(Color::new(r, g, b, parse_alpha(alpha, false)?))
+}
+
+fn translate_lab(args: &str, space: ColorSpace) -> Option<Color> {
+    let (channels, alpha, legacy) = split_color_args(args)?;
+
+    if legacy {
+        return None
+    }
+
+    // The reference ranges that 100% maps to for each channel.
+    let (lightness_range, ab_range) = match space {
+        ColorSpace::Lab => (100.0, 125.0),
+        _ => (1.0, 0.4),
+    };
+
+    let lightness = parse_scaled_component(channels[0], lightness_range)?.max(0.0).min(lightness_range);
+    let a = parse_scaled_component(channels[1], ab_range)?;
+    let b = parse_scaled_component(channels[2], ab_range)?;
+
+    return Some(Color::in_space(space, [lightness, a, b], parse_alpha(alpha, false)?))
+}
+
+fn translate_lch(args: &str, space: ColorSpace) -> Option<Color> {
+    let (channels, alpha, legacy) = split_color_args(args)?;
+
+    if legacy {
+        return None
+    }
+
+    let (lightness_range, chroma_range) = match space {
+        ColorSpace::Lch => (100.0, 150.0),
+        _ => (1.0, 0.4),
+    };
+
+    let lightness = parse_scaled_component(channels[0], lightness_range)?.max(0.0).min(lightness_range);
+    let chroma = parse_scaled_component(channels[1], chroma_range)?.max(0.0);
+    let hue = parse_hue(channels[2], false)?;
+
+    return Some(Color::in_space(space, [lightness, chroma, hue], parse_alpha(alpha, false)?))
+}
+
+// color(<colorspace> <c1> <c2> <c3> [/ <alpha>])
+fn translate_color_function(args: &str) -> Option<Color> {
+    let args = args.trim_start();
+    let name_end = args.find(char::is_whitespace)?;
+
+    let space = match &args[..name_end] {
+        "srgb" => ColorSpace::Srgb,
+        "srgb-linear" => ColorSpace::SrgbLinear,
+        "display-p3" => ColorSpace::DisplayP3,
+        "rec2020" => ColorSpace::Rec2020,
+        "xyz" | "xyz-d65" => ColorSpace::XyzD65,
+        "xyz-d50" => ColorSpace::XyzD50,
+        _ => return None,
+    };
+
+    let (channels, alpha, legacy) = split_color_args(&args[name_end..])?;
+
+    if legacy {
+        return None
+    }
+
+    let mut values = [0.0; 3];
+
+    for (i, channel) in channels.iter().enumerate() {
+        values[i] = parse_scaled_component(channel, 1.0)?;
+    }
+
+    return Some(Color::in_space(space, values, parse_alpha(alpha, false)?))
+}
+
+// color-mix(in <colorspace> [<hue-method> hue]?, <color> <percentage>?, <color> <percentage>?)
+fn translate_color_mix(args: &str) -> Option<Color> {
+    let parts = split_top_level(args, |c| c == ',');
+
+    if parts.len() != 3 {
+        return None
+    }
+
+    let mut method = parts[0].split_whitespace();
+
+    if method.next() != Some("in") {
+        return None
+    }
+
+    let space = match method.next()? {
+        "srgb" => ColorSpace::Srgb,
+        "srgb-linear" => ColorSpace::SrgbLinear,
+        "display-p3" => ColorSpace::DisplayP3,
+        "rec2020" => ColorSpace::Rec2020,
+        "lab" => ColorSpace::Lab,
+        "lch" => ColorSpace::Lch,
+        "oklab" => ColorSpace::Oklab,
+        "oklch" => ColorSpace::Oklch,
+        "xyz" | "xyz-d65" => ColorSpace::XyzD65,
+        "xyz-d50" => ColorSpace::XyzD50,
+        "hsl" => ColorSpace::Hsl,
+        "hwb" => ColorSpace::Hwb,
+        _ => return None,
+    };
+
+    let is_polar = match space {
+        ColorSpace::Lch | ColorSpace::Oklch | ColorSpace::Hsl | ColorSpace::Hwb => true,
+        _ => false,
+    };
+
+    let hue_interpolation = match (method.next(), method.next(), method.next()) {
+        (None, None, None) => HueInterpolation::Shorter,
+        (Some(m), Some("hue"), None) if is_polar => match m {
+            "shorter" => HueInterpolation::Shorter,
+            "longer" => HueInterpolation::Longer,
+            "increasing" => HueInterpolation::Increasing,
+            "decreasing" => HueInterpolation::Decreasing,
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    let (from, from_pct) = parse_mix_component(parts[1])?;
+    let (to, to_pct) = parse_mix_component(parts[2])?;
+
+    let (from_pct, to_pct) = match (from_pct, to_pct) {
+        (None, None) => (0.5, 0.5),
+        (Some(p), None) => (p, 1.0 - p),
+        (None, Some(p)) => (1.0 - p, p),
+        (Some(p1), Some(p2)) => (p1, p2),
+    };
+
+    let sum = from_pct + to_pct;
+
+    if sum == 0.0 {
+        return None
+    }
+
+    let mut mixed = color::mix(space, hue_interpolation, &from, &to, to_pct / sum);
+
+    // Percentages that add up to less than 100% leave the result transparent
+    // by the remainder.
+    if sum < 1.0 {
+        mixed = Color::in_space(mixed.space(), mixed.channels(), mixed.alpha() * sum);
+    }
+
+    return Some(mixed)
+}
+
+fn parse_mix_component(value: &str) -> Option<(Color, Option<f32>)> {
+    let parts: Vec<&str> = split_top_level(value, char::is_whitespace)
+        .into_iter()
+        .filter(|p| !p.is_empty())
+        .collect();
+
+    let percentage = |p: &str| match parse_number(p) {
+        Some((n, "%")) if n >= 0.0 && n <= 100.0 => Some(n / 100.0),
+        _ => None,
+    };
+
+    return match parts.len() {
+        1 => Some((translate_color(parts[0])?, None)),
+        2 => match percentage(parts[1]) {
+            Some(p) => Some((translate_color(parts[0])?, Some(p))),
+            None => Some((translate_color(parts[1])?, Some(percentage(parts[0])?))),
+        },
+        _ => None,
+    }
+}
+
+// Returns the name and the raw arguments of a functional notation like
+// `rgb(0 0 0)`.
+fn split_function(value: &str) -> Option<(&str, &str)> {
+    let open = value.find('(')?;
+    let name = &value[..open];
+
+    if name.is_empty() || !name.chars().all(is_valid_indent) || !value.ends_with(')') {
+        return None
+    }
+
+    return Some((name, &value[open + 1..value.len() - 1]))
+}
+
+// Splits color function arguments into the three channels and the alpha.
+// Legacy syntax is comma separated, modern syntax is space separated with
+// the alpha after a slash.
+// Splits on separators that aren't nested inside parentheses.
+fn split_top_level<F>(value: &str, is_separator: F) -> Vec<&str>
+where
+    F: Fn(char) -> bool,
+{
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (i, c) in value.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            c if depth == 0 && is_separator(c) => {
+                parts.push(value[start..i].trim());
+                start = i + c.len_utf8();
+            },
+            _ => {},
+        }
+    }
+
+    parts.push(value[start..].trim());
+
+    return parts
+}
+
+fn split_color_args(args: &str) -> Option<(Vec<&str>, Option<&str>, bool)> {
+    if args.contains(',') {
+        if args.contains('/') {
+            return None
+        }
+
+        let mut parts: Vec<&str> = args.split(',').map(str::trim).collect();
+
+        return match parts.len() {
+            3 => Some((parts, None, true)),
+            4 => {
+                let alpha = parts.pop();
+
+                Some((parts, alpha, true))
+            },
+            _ => None,
+        }
+    }
+
+    let mut halves = args.splitn(2, '/');
+    let channels: Vec<&str> = halves.next()?.split_whitespace().collect();
+
+    let alpha = match halves.next() {
+        Some(a) => match a.trim() {
+            "" => return None,
+            a if a.contains(char::is_whitespace) => return None,
+            a => Some(a),
+        },
+        None => None,
+    };
+
+    if channels.len() != 3 {
+        return None
+    }
+
+    return Some((channels, alpha, false))
+}
+
+// Parses a number or percentage, returning whether it was a percentage.
+// `none` is only allowed in the modern syntax.
+fn parse_color_component(value: &str, legacy: bool) -> Option<(f32, bool)> {
+    if value == "none" {
+        return if legacy { None } else { Some((0.0, false)) }
+    }
+
+    return match parse_number(value)? {
+        (n, "") => Some((n, false)),
+        (n, "%") => Some((n, true)),
+        _ => None,
+    }
+}
+
+// Parses a number, or a percentage of `range`.
+fn parse_scaled_component(value: &str, range: f32) -> Option<f32> {
+    return match parse_color_component(value, false)? {
+        (n, true) => Some(n / 100.0 * range),
+        (n, false) => Some(n),
+    }
+}
+
+fn parse_alpha(value: Option<&str>, legacy: bool) -> Option<f32> {
+    return match value {
+        Some(a) => match parse_color_component(a, legacy)? {
+            (n, true) => Some((n / 100.0).max(0.0).min(1.0)),
+            (n, false) => Some(n.max(0.0).min(1.0)),
+        },
+        None => Some(1.0),
+    }
+}
+
+// Parses a hue into degrees in the range [0, 360).
+fn parse_hue(value: &str, legacy: bool) -> Option<f32> {
+    if value == "none" {
+        return if legacy { None } else { Some(0.0) }
     }
+
+    let degrees = match parse_number(value)? {
+        (n, "") | (n, "deg") => n,
+        (n, "grad") => n * 0.9,
+        (n, "rad") => n.to_degrees(),
+        (n, "turn") => n * 360.0,
+        _ => return None,
+    };
+
+    return Some(degrees.rem_euclid(360.0))
+}
+
+fn is_combinator(c: char) -> bool {
+    return c == '>' || c == '+' || c == '~'
 }
 
 fn is_valid_indent(c: char) -> bool {
@@ -797,4 +3740,90 @@ fn is_lower_letter(c: char) -> bool {
 
 fn is_non_ascii(c: char) -> bool {
     return c >= '\u{0080}'
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn minified(css: &str) -> String {
+        return CssParser::new(css).parse_stylesheet().to_string()
+    }
+
+    #[test]
+    fn custom_property_values_can_hold_blocks() {
+        assert_eq!(
+            minified("a { --x: { a: b }; color: red } b { color: blue }"),
+            "a{--x:{ a: b };color:rgb(255, 0, 0)}b{color:rgb(0, 0, 255)}",
+        );
+    }
+
+    #[test]
+    fn values_can_span_lines() {
+        assert_eq!(
+            minified("a { --shadow: 0 0 1px red,\n 0 0 2px blue; color: red }"),
+            "a{--shadow:0 0 1px red, 0 0 2px blue;color:rgb(255, 0, 0)}",
+        );
+        assert_eq!(minified("a { color: var(--c,\n red) }"), "a{color:var(--c, red)}");
+        assert_eq!(minified("a { width: calc(1px +\n 2px) }"), "a{width:3px}");
+    }
+
+    #[test]
+    fn strings_in_values_are_not_ends_of_declarations() {
+        assert_eq!(minified("a { --s: \"a;}\"; color: red }"), "a{--s:\"a;}\";color:rgb(255, 0, 0)}");
+    }
+
+    #[test]
+    fn string_escapes_are_resolved() {
+        let content = |css: &str| match parse_declaration_value("content", css) {
+            Some(Value::Content(items)) => items,
+            _ => Vec::new(),
+        };
+        let strings = |s: &[&str]| s.iter().map(|s| ContentItem::String(s.to_string())).collect::<Vec<_>>();
+
+        assert_eq!(content(r#""\e900" "\201C" "\A""#), strings(&["\u{e900}", "\u{201c}", "\n"]));
+        assert_eq!(content(r#""\41 B\42\"\\""#), strings(&["ABB\"\\"]));
+        assert_eq!(content(r#""\0" "\d800" "\110000" "\00004142""#), strings(&["\u{fffd}", "\u{fffd}", "\u{fffd}", "A42"]));
+        assert_eq!(content("\"a\\\nb\""), strings(&["ab"]));
+        assert_eq!(minified("[title=\"\\41\"] { color: red }"), "[title=\"A\"]{color:rgb(255, 0, 0)}");
+    }
+
+    #[test]
+    fn only_undeclared_namespace_prefixes_are_invalid() {
+        assert_eq!(minified("[*|href], [|title=a] { color: red }"), "[*|href],[|title=\"a\"]{color:rgb(255, 0, 0)}");
+        assert_eq!(minified("[a|=en] { color: red }"), "[a|=\"en\"]{color:rgb(255, 0, 0)}");
+        assert_eq!(minified("[xlink|href] { color: red } a { color: blue }"), "a{color:rgb(0, 0, 255)}");
+        assert_eq!(minified("svg [xlink|href], a { color: red }"), "");
+    }
+
+    #[test]
+    fn shorthands_are_expanded() {
+        assert_eq!(minified("a { container: card / inline-size }"), "a{container-name:card;container-type:inline-size}");
+        assert_eq!(minified("a { container: a b }"), "a{container-name:a b;container-type:normal}");
+        assert_eq!(minified("a { container: inherit }"), "a{container-name:inherit;container-type:inherit}");
+        assert_eq!(minified("a { container: var(--c) }"), "a{container:var(--c)}");
+        assert_eq!(minified("a { container: card / inherit; container: / size; container: card / block }"), "a{}");
+    }
+
+    #[test]
+    fn list_shorthands_are_expanded() {
+        assert_eq!(
+            minified("a { animation: 1s spin infinite, fade 2s 3s steps(2, end) both }"),
+            "a{animation-name:spin, fade;animation-duration:1s, 2s;animation-timing-function:ease, steps(2);\
+             animation-delay:0s, 3s;animation-iteration-count:infinite, 1;animation-direction:normal, normal;\
+             animation-fill-mode:none, both;animation-play-state:running, running}",
+        );
+        assert_eq!(minified("a { animation: none none }"), "a{animation-name:none;animation-duration:0s;animation-timing-function:ease;\
+             animation-delay:0s;animation-iteration-count:1;animation-direction:normal;animation-fill-mode:none;animation-play-state:running}");
+        assert_eq!(
+            minified("a { transition: width 1s linear 2s, ease 3s }"),
+            "a{transition-property:width, all;transition-duration:1s, 3s;transition-timing-function:linear, ease;transition-delay:2s, 0s}",
+        );
+        assert_eq!(minified("a { transition: 1s 2s 3s; transition: none 1s, width; transition: 1s, }"), "a{}");
+    }
+
+    #[test]
+    fn declarations_without_a_property_name_are_dropped() {
+        assert_eq!(minified("a { 0 0: red; color: blue; foo bar; width: 2px }"), "a{color:rgb(0, 0, 255);width:2px}");
+    }
+}
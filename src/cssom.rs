@@ -0,0 +1,400 @@
+use crate::css::{
+    CssRule,
+    Declaration,
+    Rule,
+    Stylesheet,
+};
+use crate::css_parser::{
+    is_custom_property,
+    parse_declaration_list,
+    parse_property,
+    parse_rule,
+    parse_selector_list,
+    strip_important,
+};
+use std::error::Error;
+use std::fmt;
+use std::mem;
+use std::slice;
+
+// Named after the DOMExceptions the CSSOM methods throw.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum CssomError {
+    // The text isn't a single valid rule, selector list or value.
+    Syntax,
+    // The index is past the end of the rule list.
+    IndexSize,
+    // The rule can't go there, like an @import after a style rule or inside
+    // an @media block.
+    HierarchyRequest,
+    // cssText can't turn a rule into a different kind of rule.
+    InvalidModification,
+    // Only grouping rules like @media have child rules.
+    NotGroupingRule,
+}
+
+impl fmt::Display for CssomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let message = match *self {
+            CssomError::Syntax => "syntax error",
+            CssomError::IndexSize => "index out of range",
+            CssomError::HierarchyRequest => "rule not allowed at this position",
+            CssomError::InvalidModification => "rule can't be changed to a different kind of rule",
+            CssomError::NotGroupingRule => "rule has no child rules",
+        };
+
+        return write!(f, "{}", message)
+    }
+}
+
+impl Error for CssomError {}
+
+impl Stylesheet {
+    // Parses `css` as one rule and inserts it before `index`, returning the
+    // index it ended up at.
+    pub fn insert_rule(&mut self, css: &str, index: usize) -> Result<usize, CssomError> {
+        return insert_rule(&mut self.rules, css, index, false)
+    }
+
+    pub fn delete_rule(&mut self, index: usize) -> Result<CssRule, CssomError> {
+        return delete_rule(&mut self.rules, index)
+    }
+
+    // Every rule, including nested ones and the ones in @media, @supports and
+    // @layer blocks, in document order. Imported stylesheets aren't included.
+    pub fn all_rules(&self) -> AllRules<'_> {
+        return AllRules {
+            stack: vec![self.rules.iter()],
+        }
+    }
+}
+
+impl CssRule {
+    pub fn css_text(&self) -> String {
+        return self.to_string()
+    }
+
+    // Replaces this rule with `css`, which has to be the same kind of rule.
+    pub fn set_css_text(&mut self, css: &str) -> Result<(), CssomError> {
+        let rule = parse_rule(css).ok_or(CssomError::Syntax)?;
+
+        if mem::discriminant(&rule) != mem::discriminant(self) {
+            return Err(CssomError::InvalidModification)
+        }
+
+        *self = rule;
+
+        return Ok(())
+    }
+
+    // The rules inside @media, @supports, @layer and @container blocks, and
+    // the ones nested in style rules.
+    pub fn child_rules(&self) -> Option<&[CssRule]> {
+        return match *self {
+            CssRule::Style(ref r) => Some(&r.rules),
+            CssRule::Media(ref m) => Some(&m.rules),
+            CssRule::Supports(ref s) => Some(&s.rules),
+            CssRule::Layer(ref l) => Some(&l.rules),
+            CssRule::Container(ref c) => Some(&c.rules),
+            _ => None,
+        }
+    }
+
+    fn child_rules_mut(&mut self) -> Option<&mut Vec<CssRule>> {
+        return match *self {
+            CssRule::Style(ref mut r) => Some(&mut r.rules),
+            CssRule::Media(ref mut m) => Some(&mut m.rules),
+            CssRule::Supports(ref mut s) => Some(&mut s.rules),
+            CssRule::Layer(ref mut l) => Some(&mut l.rules),
+            CssRule::Container(ref mut c) => Some(&mut c.rules),
+            _ => None,
+        }
+    }
+
+    pub fn insert_rule(&mut self, css: &str, index: usize) -> Result<usize, CssomError> {
+        let rules = self.child_rules_mut().ok_or(CssomError::NotGroupingRule)?;
+
+        return insert_rule(rules, css, index, true)
+    }
+
+    pub fn delete_rule(&mut self, index: usize) -> Result<CssRule, CssomError> {
+        let rules = self.child_rules_mut().ok_or(CssomError::NotGroupingRule)?;
+
+        return delete_rule(rules, index)
+    }
+
+    pub fn as_style_rule(&self) -> Option<&Rule> {
+        return match *self {
+            CssRule::Style(ref r) => Some(r),
+            _ => None,
+        }
+    }
+
+    pub fn as_style_rule_mut(&mut self) -> Option<&mut Rule> {
+        return match *self {
+            CssRule::Style(ref mut r) => Some(r),
+            _ => None,
+        }
+    }
+}
+
+impl Rule {
+    pub fn selector_text(&self) -> String {
+        let selectors: Vec<String> = self.selectors.iter().map(|s| format!("{:#}", s)).collect();
+
+        return selectors.join(", ")
+    }
+
+    // Like in CSSOM, an invalid selector leaves the rule as it was.
+    pub fn set_selector_text(&mut self, selectors: &str) -> Result<(), CssomError> {
+        self.selectors = parse_selector_list(selectors).ok_or(CssomError::Syntax)?;
+
+        return Ok(())
+    }
+
+    // The declarations, as in the `style` attribute.
+    pub fn style_text(&self) -> String {
+        let declarations: Vec<String> = self.declarations.iter().map(|d| format!("{:#};", d)).collect();
+
+        return declarations.join(" ")
+    }
+
+    // Replaces all declarations. Invalid ones are dropped, as when parsing.
+    pub fn set_style_text(&mut self, css: &str) {
+        self.declarations = parse_declaration_list(css);
+    }
+
+    fn declaration(&self, property: &str) -> Option<&Declaration> {
+        let property = normalize_property(property);
+
+        // If a property is declared more than once, the last one wins.
+        return self.declarations.iter().rev().find(|d| d.property == property)
+    }
+
+    pub fn get_property_value(&self, property: &str) -> Option<String> {
+        return self.declaration(property).map(|d| d.value.to_string())
+    }
+
+    pub fn get_property_priority(&self, property: &str) -> bool {
+        return self.declaration(property).map_or(false, |d| d.important)
+    }
+
+    // Parses `value` for `property` and replaces any declarations of it. An
+    // empty value removes the property, and `!important` in the value is
+    // the same as passing `important`.
+    pub fn set_property(&mut self, property: &str, value: &str, important: bool) -> Result<(), CssomError> {
+        let property = normalize_property(property);
+        let mut value = value.trim().to_string();
+
+        if value.is_empty() {
+            self.remove_property(&property);
+
+            return Ok(())
+        }
+
+        let important = strip_important(&mut value) || important;
+
+        // A shorthand sets each of its longhands.
+        for declaration in parse_property(&property, &value, important).ok_or(CssomError::Syntax)? {
+            // Keeps the position of the first declaration, so the order of
+            // the others doesn't change.
+            match self.declarations.iter().position(|d| d.property == declaration.property) {
+                Some(i) => {
+                    self.declarations.retain(|d| d.property != declaration.property);
+                    self.declarations.insert(i, declaration);
+                },
+                None => self.declarations.push(declaration),
+            }
+        }
+
+        return Ok(())
+    }
+
+    // Removes every declaration of `property`, returning the value that
+    // applied.
+    pub fn remove_property(&mut self, property: &str) -> Option<String> {
+        let value = self.get_property_value(property);
+        let property = normalize_property(property);
+
+        self.declarations.retain(|d| d.property != property);
+
+        return value
+    }
+}
+
+// A depth-first iterator over a stylesheet's rules.
+pub struct AllRules<'a> {
+    stack: Vec<slice::Iter<'a, CssRule>>,
+}
+
+impl<'a> Iterator for AllRules<'a> {
+    type Item = &'a CssRule;
+
+    fn next(&mut self) -> Option<&'a CssRule> {
+        loop {
+            let rule = match self.stack.last_mut()?.next() {
+                Some(rule) => rule,
+                None => {
+                    self.stack.pop();
+
+                    continue
+                },
+            };
+
+            if let Some(children) = rule.child_rules() {
+                self.stack.push(children.iter());
+            }
+
+            return Some(rule)
+        }
+    }
+}
+
+fn insert_rule(rules: &mut Vec<CssRule>, css: &str, index: usize, nested: bool) -> Result<usize, CssomError> {
+    if index > rules.len() {
+        return Err(CssomError::IndexSize)
+    }
+
+    let rule = parse_rule(css).ok_or(CssomError::Syntax)?;
+
+    let allowed = match rule {
+        // @import has to come before everything but other imports and
+        // @layer statements, and only at the top level.
+        CssRule::Import(_) => !nested && rules[..index].iter().all(can_precede_import),
+        CssRule::LayerStatement(_) => true,
+        _ => rules[index..].iter().all(|r| match *r {
+            CssRule::Import(_) => false,
+            _ => true,
+        }),
+    };
+
+    if !allowed {
+        return Err(CssomError::HierarchyRequest)
+    }
+
+    rules.insert(index, rule);
+
+    return Ok(index)
+}
+
+fn delete_rule(rules: &mut Vec<CssRule>, index: usize) -> Result<CssRule, CssomError> {
+    if index >= rules.len() {
+        return Err(CssomError::IndexSize)
+    }
+
+    return Ok(rules.remove(index))
+}
+
+fn can_precede_import(rule: &CssRule) -> bool {
+    return match *rule {
+        CssRule::Import(_) | CssRule::LayerStatement(_) => true,
+        _ => false,
+    }
+}
+
+// Property names are case-insensitive, except for custom properties.
+fn normalize_property(property: &str) -> String {
+    let property = property.trim();
+
+    return match is_custom_property(property) {
+        true => property.to_string(),
+        false => property.to_ascii_lowercase(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css_parser::CssParser;
+
+    fn sheet(css: &str) -> Stylesheet {
+        return CssParser::new(css).parse_stylesheet()
+    }
+
+    fn texts(sheet: &Stylesheet) -> Vec<String> {
+        return sheet.rules.iter().map(CssRule::css_text).collect()
+    }
+
+    #[test]
+    fn imports_only_go_before_other_rules() {
+        let mut s = sheet("@import \"a.css\"; a { color: red }");
+
+        assert_eq!(s.insert_rule("@import \"b.css\";", 2), Err(CssomError::HierarchyRequest));
+        assert_eq!(s.insert_rule("@layer x;", 0), Ok(0));
+        assert_eq!(s.insert_rule("@import \"b.css\";", 1), Ok(1));
+        assert_eq!(s.insert_rule("@import \"c.css\";", 0), Ok(0));
+        assert_eq!(s.insert_rule("b { color: red }", 0), Err(CssomError::HierarchyRequest));
+        assert_eq!(s.insert_rule("b { color: red }", 5), Ok(5));
+        assert_eq!(s.rules.len(), 6);
+
+        let mut media = sheet("@media screen { a { color: red } }");
+
+        assert_eq!(media.rules[0].insert_rule("@import \"a.css\";", 0), Err(CssomError::HierarchyRequest));
+        assert_eq!(media.rules[0].insert_rule("@layer x;", 1), Ok(1));
+    }
+
+    #[test]
+    fn indexes_past_the_end_are_errors() {
+        let mut s = sheet("a { color: red }");
+
+        assert_eq!(s.insert_rule("b { color: red }", 2), Err(CssomError::IndexSize));
+        assert_eq!(s.delete_rule(1).err(), Some(CssomError::IndexSize));
+        assert_eq!(s.rules[0].delete_rule(0).err(), Some(CssomError::IndexSize));
+        assert_eq!(s.insert_rule("b { color: red } c { color: red }", 0), Err(CssomError::Syntax));
+        assert_eq!(s.insert_rule("b { color: red }", 1), Ok(1));
+        assert!(s.delete_rule(0).is_ok());
+        assert_eq!(texts(&s), vec!["b{color:rgb(255, 0, 0)}"]);
+
+        let mut import = sheet("@import \"a.css\";");
+
+        assert_eq!(import.rules[0].insert_rule("a { color: red }", 0), Err(CssomError::NotGroupingRule));
+    }
+
+    #[test]
+    fn css_text_keeps_the_kind_of_rule() {
+        let mut s = sheet("a { color: red } @media print { a { color: red } }");
+
+        assert_eq!(s.rules[0].set_css_text("@media screen { b { color: blue } }"), Err(CssomError::InvalidModification));
+        assert_eq!(s.rules[0].set_css_text("1b { color: blue }"), Err(CssomError::Syntax));
+        assert_eq!(s.rules[0].set_css_text("b { color: blue }"), Ok(()));
+        assert_eq!(s.rules[1].set_css_text("@media screen { b { color: blue } }"), Ok(()));
+        assert_eq!(texts(&s), vec!["b{color:rgb(0, 0, 255)}", "@media screen{b{color:rgb(0, 0, 255)}}"]);
+    }
+
+    #[test]
+    fn set_property_parses_the_value() {
+        let mut s = sheet("a { color: red; width: 1px }");
+        let rule = s.rules[0].as_style_rule_mut().unwrap();
+
+        assert_eq!(rule.set_property("COLOR", "blue !important", false), Ok(()));
+        assert_eq!(rule.get_property_value("color").as_deref(), Some("rgb(0, 0, 255)"));
+        assert!(rule.get_property_priority("color"));
+
+        assert_eq!(rule.set_property("width", "red", false), Err(CssomError::Syntax));
+        assert_eq!(rule.get_property_value("width").as_deref(), Some("1px"));
+
+        assert_eq!(rule.set_property("width", "  ", true), Ok(()));
+        assert_eq!(rule.get_property_value("width"), None);
+
+        assert_eq!(rule.set_property("container", "card / size", false), Ok(()));
+        assert_eq!(rule.style_text(), "color: rgb(0, 0, 255) !important; container-name: card; container-type: size;");
+
+        assert_eq!(rule.remove_property("container-name").as_deref(), Some("card"));
+        assert_eq!(rule.remove_property("container-name"), None);
+    }
+
+    #[test]
+    fn all_rules_goes_in_document_order() {
+        let s = sheet("a { color: red; b { color: red } } @media screen { @supports (color: red) { c { color: red } } d { color: red } } e { color: red }");
+        let kinds: Vec<String> = s.all_rules()
+            .map(|r| match *r {
+                CssRule::Style(ref r) => r.selector_text(),
+                CssRule::Media(_) => String::from("@media"),
+                CssRule::Supports(_) => String::from("@supports"),
+                _ => String::from("other"),
+            })
+            .collect();
+
+        assert_eq!(kinds, vec!["a", "& b", "@media", "@supports", "c", "d", "e"]);
+    }
+}
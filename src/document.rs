@@ -0,0 +1,169 @@
+use crate::css::Stylesheet;
+use crate::css_parser::{
+    parse_media_query_list,
+    CssParser,
+};
+use crate::dom::{
+    ElementData,
+    Node,
+    NodeType,
+};
+use crate::loader::StylesheetLoader;
+use crate::style::{
+    media_query_list_matches,
+    Viewport,
+};
+
+// The author stylesheets of a document: the contents of its <style> elements
+// and the stylesheets its <link rel="stylesheet"> elements refer to, in
+// document order. Relative URLs are resolved against `base`, the URL of the
+// document. Disabled links and stylesheets whose `media` doesn't match
+// `viewport` are left out, so they have to be collected again when the
+// viewport changes.
+pub fn author_stylesheets(
+    document: &Node,
+    viewport: &Viewport,
+    loader: &mut dyn StylesheetLoader,
+    base: Option<&str>,
+) -> Vec<Stylesheet> {
+    let mut sheets = Vec::new();
+
+    collect_stylesheets(document, viewport, loader, base, &mut sheets);
+
+    return sheets
+}
+
+fn collect_stylesheets(
+    node: &Node,
+    viewport: &Viewport,
+    loader: &mut dyn StylesheetLoader,
+    base: Option<&str>,
+    sheets: &mut Vec<Stylesheet>,
+) {
+    if let NodeType::Element(ref e) = node.node_type {
+        sheets.extend(element_stylesheet(node, e, viewport, loader, base));
+    }
+
+    for child in &node.children {
+        collect_stylesheets(child, viewport, loader, base, sheets);
+    }
+}
+
+fn element_stylesheet(
+    node: &Node,
+    element: &ElementData,
+    viewport: &Viewport,
+    loader: &mut dyn StylesheetLoader,
+    base: Option<&str>,
+) -> Option<Stylesheet> {
+    if !is_css(element) {
+        return None
+    }
+
+    let media = parse_media_query_list(element.get_attr("media").map_or("", String::as_str));
+
+    if !media_query_list_matches(&media, viewport) {
+        return None
+    }
+
+    return match element.tag_name.as_ref() {
+        "style" => {
+            let css: String = node.children.iter().filter_map(|c| match c.node_type {
+                NodeType::Text(ref t) => Some(t.as_str()),
+                _ => None,
+            }).collect();
+
+            Some(CssParser::new(&css).parse_stylesheet_with_loader(loader, base))
+        },
+        // Only <link> has a `disabled` attribute.
+        "link" if is_stylesheet_link(element) && element.get_attr("disabled").is_none() => {
+            let href = element.get_attr("href").map_or("", |h| h.trim());
+
+            if href.is_empty() {
+                return None
+            }
+
+            let url = loader.resolve(href, base);
+            let css = loader.load(&url)?;
+
+            Some(CssParser::new(&css).parse_stylesheet_with_loader(loader, Some(&url)))
+        },
+        _ => None,
+    }
+}
+
+// Without a `type`, stylesheets are assumed to be CSS.
+fn is_css(element: &ElementData) -> bool {
+    return match element.get_attr("type") {
+        Some(t) => t.trim().is_empty() || t.trim().eq_ignore_ascii_case("text/css"),
+        None => true,
+    }
+}
+
+// Alternate stylesheets aren't applied unless the user picks them, which
+// isn't supported.
+fn is_stylesheet_link(element: &ElementData) -> bool {
+    let rel = element.get_attr("rel").map_or(String::new(), |r| r.to_ascii_lowercase());
+    let mut keywords = rel.split_whitespace();
+
+    return keywords.clone().any(|k| k == "stylesheet") && !keywords.any(|k| k == "alternate")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::html_parser::HtmlParser;
+    use std::collections::HashMap;
+
+    // Serves stylesheets from memory, by URL.
+    struct Files(HashMap<String, String>);
+
+    impl StylesheetLoader for Files {
+        fn resolve(&self, url: &str, base: Option<&str>) -> String {
+            return match base {
+                Some(base) => format!("{}/{}", base, url),
+                None => url.to_string(),
+            }
+        }
+
+        fn load(&mut self, url: &str) -> Option<String> {
+            return self.0.get(url).cloned()
+        }
+    }
+
+    fn stylesheets(html: &str) -> Vec<String> {
+        let nodes = HtmlParser::new(html).parse_nodes();
+        let mut loader = Files(vec![(String::from("site/a.css"), String::from("a { color: red }"))].into_iter().collect());
+
+        return author_stylesheets(&nodes[0], &Viewport::default(), &mut loader, Some("site"))
+            .iter()
+            .map(Stylesheet::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn stylesheets_are_collected_in_document_order() {
+        let html = "<html><head><style>c { color: red }</style><link rel=\"stylesheet\" href=\"a.css\"></head>\
+                    <body><style>d { color: red }</style></body></html>";
+
+        assert_eq!(stylesheets(html), vec!["c{color:rgb(255, 0, 0)}", "a{color:rgb(255, 0, 0)}", "d{color:rgb(255, 0, 0)}"]);
+    }
+
+    #[test]
+    fn only_applicable_stylesheets_are_collected() {
+        let collected = |element: &str| stylesheets(&format!("<html>{}</html>", element)).len();
+
+        assert_eq!(collected("<style media=\"print\">a {}</style>"), 0);
+        assert_eq!(collected("<style media=\"screen, print\">a {}</style>"), 1);
+        assert_eq!(collected("<style type=\"text/plain\">a {}</style>"), 0);
+        assert_eq!(collected("<style type=\" TEXT/CSS \">a {}</style>"), 1);
+        // Only links can be disabled.
+        assert_eq!(collected("<style disabled>a {}</style>"), 1);
+        assert_eq!(collected("<link rel=\"stylesheet\" href=\"a.css\" disabled>"), 0);
+        assert_eq!(collected("<link rel=\"alternate stylesheet\" href=\"a.css\">"), 0);
+        assert_eq!(collected("<link rel=\"icon\" href=\"a.css\">"), 0);
+        assert_eq!(collected("<link rel=\"Stylesheet\" href=\"a.css\" media=\"screen\">"), 1);
+        assert_eq!(collected("<link rel=\"stylesheet\" href=\"missing.css\">"), 0);
+        assert_eq!(collected("<link rel=\"stylesheet\" href=\" \">"), 0);
+    }
+}
@@ -3,6 +3,10 @@ use std::collections::{
     HashSet,
 };
 use std::fmt;
+use std::sync::atomic::{
+    AtomicUsize,
+    Ordering,
+};
 
 #[derive(PartialEq, Eq, Clone)]
 pub struct Node {
@@ -21,6 +25,27 @@ pub enum NodeType {
 pub struct ElementData {
     pub tag_name: String,
     attrs: AttrMap,
+    pub state: ElementState,
+    id: ElementId,
+}
+
+// Tells elements apart for as long as the program runs, unlike their
+// addresses, which change when the tree moves and are reused once it's
+// dropped. A clone keeps the id, so it's styled as the same element.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub struct ElementId(usize);
+
+static NEXT_ELEMENT_ID: AtomicUsize = AtomicUsize::new(0);
+
+// Interaction state for the dynamic pseudo-classes, set by the host.
+#[derive(PartialEq, Eq, Clone, Copy, Default, Debug)]
+pub struct ElementState {
+    pub hover: bool,
+    pub active: bool,
+    pub focus: bool,
+    pub checked: bool,
+    pub disabled: bool,
+    pub visited: bool,
 }
 
 impl ElementData {
@@ -28,13 +53,27 @@ impl ElementData {
         return ElementData {
             tag_name,
             attrs,
+            state: ElementState::default(),
+            id: ElementId(NEXT_ELEMENT_ID.fetch_add(1, Ordering::Relaxed)),
         }
     }
 
+    pub fn id(&self) -> ElementId {
+        return self.id
+    }
+
     pub fn get_id(&self) -> Option<&String> {
         return self.attrs.get("id")
     }
 
+    pub fn get_attr(&self, name: &str) -> Option<&String> {
+        return self.attrs.get(name)
+    }
+
+    pub fn attrs(&self) -> &AttrMap {
+        return &self.attrs
+    }
+
     pub fn get_classes(&self) -> HashSet<&str> {
         return match self.attrs.get("class") {
             Some(s) => s.split(' ').collect(),
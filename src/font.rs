@@ -0,0 +1,291 @@
+use crate::css::{
+    CssRule,
+    FontFaceRule,
+    FontSource,
+    FontStyle,
+    Stylesheet,
+};
+use crate::loader::FontLoader;
+use crate::sfnt::Font;
+use crate::style::{
+    media_query_list_matches,
+    supports_condition_matches,
+    Viewport,
+};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// A face that fonts are matched against, from an @font-face rule or an
+// installed font. Installed fonts get descriptors without sources.
+pub struct FontFace {
+    pub descriptors: FontFaceRule,
+    pub font: Rc<Font>,
+}
+
+impl FontFace {
+    fn from_font(font: Rc<Font>, family: &str) -> FontFace {
+        let mut descriptors = FontFaceRule::new(family.to_string(), Vec::new());
+
+        descriptors.weight = (font.weight, font.weight);
+        descriptors.style = match (font.italic, font.oblique) {
+            (_, true) => FontStyle::Oblique(14.0, 14.0),
+            (true, false) => FontStyle::Italic,
+            (false, false) => FontStyle::Normal,
+        };
+        descriptors.stretch = (font.stretch, font.stretch);
+
+        return FontFace { descriptors, font }
+    }
+}
+
+// The font-weight, font-style and font-stretch a font is wanted for, with the
+// stretch as a percentage.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct FontQuery {
+    pub weight: f32,
+    pub style: FontStyle,
+    pub stretch: f32,
+}
+
+impl Default for FontQuery {
+    fn default() -> Self {
+        return FontQuery {
+            weight: 400.0,
+            style: FontStyle::Normal,
+            stretch: 100.0,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct FontDatabase {
+    faces: Vec<FontFace>,
+    // Loaded fonts by URL, so a file used by several rules is read once.
+    fonts: HashMap<String, Rc<Font>>,
+    // Loaded the first time a `local()` source or installed font is needed.
+    installed: Option<Vec<Rc<Font>>>,
+}
+
+impl FontDatabase {
+    pub fn new() -> Self {
+        return FontDatabase::default()
+    }
+
+    pub fn faces(&self) -> &[FontFace] {
+        return &self.faces
+    }
+
+    // Adds the faces of the @font-face rules that apply, including the ones
+    // in imported stylesheets. `base` is the URL of the stylesheet.
+    pub fn add_stylesheet(
+        &mut self,
+        sheet: &Stylesheet,
+        viewport: &Viewport,
+        loader: &mut dyn FontLoader,
+        base: Option<&str>,
+    ) {
+        self.add_rules(&sheet.rules, viewport, loader, base);
+    }
+
+    fn add_rules(&mut self, rules: &[CssRule], viewport: &Viewport, loader: &mut dyn FontLoader, base: Option<&str>) {
+        for rule in rules {
+            match *rule {
+                CssRule::FontFace(ref f) => self.add_font_face(f, loader, base),
+                CssRule::Media(ref m) => if media_query_list_matches(&m.queries, viewport) {
+                    self.add_rules(&m.rules, viewport, loader, base);
+                },
+                CssRule::Supports(ref s) => if supports_condition_matches(&s.condition) {
+                    self.add_rules(&s.rules, viewport, loader, base);
+                },
+                CssRule::Layer(ref l) => self.add_rules(&l.rules, viewport, loader, base),
+                CssRule::Import(ref i) => match i.stylesheet {
+                    Some(ref sheet) if media_query_list_matches(&i.media, viewport)
+                        && i.supports.as_ref().map_or(true, supports_condition_matches) =>
+                    {
+                        let url = loader.resolve(&i.url, base);
+
+                        self.add_rules(&sheet.rules, viewport, loader, Some(&url));
+                    },
+                    _ => {},
+                },
+                // Container queries depend on an element, so the fonts in them
+                // can't be loaded up front.
+                CssRule::Style(_) | CssRule::LayerStatement(_) | CssRule::Keyframes(_) | CssRule::Container(_) => {},
+            }
+        }
+    }
+
+    // Uses the first source that loads. A rule without any usable source
+    // adds nothing.
+    fn add_font_face(&mut self, rule: &FontFaceRule, loader: &mut dyn FontLoader, base: Option<&str>) {
+        let font = rule.sources.iter().filter_map(|source| match *source {
+            FontSource::Local(ref name) => self.find_local(name, loader),
+            FontSource::Url { ref url, .. } => self.load(&loader.resolve(url, base), loader),
+        }).next();
+
+        if let Some(font) = font {
+            self.faces.push(FontFace {
+                descriptors: rule.clone(),
+                font,
+            });
+        }
+    }
+
+    // Adds every installed font as a face of its family, as the fallback for
+    // families without @font-face rules.
+    pub fn add_installed_fonts(&mut self, loader: &mut dyn FontLoader) {
+        for font in self.installed(loader) {
+            for family in font.family_names() {
+                let face = FontFace::from_font(font.clone(), family);
+
+                self.faces.push(face);
+            }
+        }
+    }
+
+    fn load(&mut self, url: &str, loader: &mut dyn FontLoader) -> Option<Rc<Font>> {
+        if let Some(font) = self.fonts.get(url) {
+            return Some(font.clone())
+        }
+
+        let font = Rc::new(Font::parse(&loader.load_font(url)?)?);
+
+        self.fonts.insert(url.to_string(), font.clone());
+
+        return Some(font)
+    }
+
+    fn installed(&mut self, loader: &mut dyn FontLoader) -> Vec<Rc<Font>> {
+        if self.installed.is_none() {
+            let fonts = loader.installed_fonts()
+                .iter()
+                .filter_map(|url| loader.load_font(url))
+                .flat_map(|data| Font::parse_collection(&data))
+                .map(Rc::new)
+                .collect();
+
+            self.installed = Some(fonts);
+        }
+
+        return self.installed.clone().unwrap_or_default()
+    }
+
+    // `local()` matches the full or PostScript name of one face, not a
+    // family.
+    fn find_local(&mut self, name: &str, loader: &mut dyn FontLoader) -> Option<Rc<Font>> {
+        return self.installed(loader).into_iter().find(|f| {
+            f.full_name.eq_ignore_ascii_case(name) || f.postscript_name.eq_ignore_ascii_case(name)
+        })
+    }
+
+    // The face to draw `c` with, from the first family in `families` that
+    // has one that covers it, as in the CSS font matching algorithm.
+    pub fn match_font(&self, families: &[String], query: FontQuery, c: char) -> Option<&FontFace> {
+        for family in families {
+            let candidates: Vec<&FontFace> = self.faces
+                .iter()
+                .filter(|f| f.descriptors.family.eq_ignore_ascii_case(family) && f.descriptors.covers(c) && f.font.has_glyph(c))
+                .collect();
+
+            if candidates.is_empty() {
+                continue
+            }
+
+            // Narrower faces are preferred for condensed text, and wider ones
+            // otherwise.
+            let candidates = narrow(candidates, |f| Some(f.descriptors.stretch), query.stretch, query.stretch <= 100.0);
+            let candidates = match_style(candidates, query.style);
+            let candidates = match_weight(candidates, query.weight);
+
+            if let Some(face) = candidates.into_iter().next() {
+                return Some(face)
+            }
+        }
+
+        return None
+    }
+}
+
+// Keeps the faces whose range contains `desired`, or if there are none, the
+// closest value in the preferred direction, or else in the other direction.
+// Faces without a range are dropped.
+fn narrow<'a, F>(faces: Vec<&'a FontFace>, range: F, desired: f32, prefer_lower: bool) -> Vec<&'a FontFace>
+    where F: Fn(&FontFace) -> Option<(f32, f32)>
+{
+    let ranges: Vec<(f32, f32)> = faces.iter().filter_map(|f| range(f)).collect();
+
+    let below = ranges.iter().filter(|r| r.1 < desired).map(|r| r.1).fold(None, |a: Option<f32>, v| Some(a.map_or(v, |a| a.max(v))));
+    let above = ranges.iter().filter(|r| r.0 > desired).map(|r| r.0).fold(None, |a: Option<f32>, v| Some(a.map_or(v, |a| a.min(v))));
+
+    let value = match ranges.iter().any(|r| r.0 <= desired && desired <= r.1) {
+        true => Some(desired),
+        false if prefer_lower => below.or(above),
+        false => above.or(below),
+    };
+
+    return match value {
+        Some(v) => faces.into_iter().filter(|f| range(f).map_or(false, |r| r.0 <= v && v <= r.1)).collect(),
+        None => Vec::new(),
+    }
+}
+
+fn oblique_range(face: &FontFace) -> Option<(f32, f32)> {
+    return match face.descriptors.style {
+        FontStyle::Oblique(min, max) => Some((min, max)),
+        _ => None,
+    }
+}
+
+fn is_style(face: &FontFace, style: FontStyle) -> bool {
+    return match (face.descriptors.style, style) {
+        (FontStyle::Normal, FontStyle::Normal) | (FontStyle::Italic, FontStyle::Italic) => true,
+        _ => false,
+    }
+}
+
+// Italic falls back to oblique and oblique to italic, before normal. Normal
+// falls back to oblique and then italic.
+fn match_style(faces: Vec<&FontFace>, style: FontStyle) -> Vec<&FontFace> {
+    let obliques = || narrow(faces.clone(), oblique_range, match style {
+        FontStyle::Oblique(angle, _) => angle,
+        FontStyle::Italic => 14.0,
+        FontStyle::Normal => 0.0,
+    }, match style {
+        FontStyle::Oblique(angle, _) => angle < 0.0,
+        _ => false,
+    });
+    let of_style = |s| faces.iter().cloned().filter(|f| is_style(f, s)).collect::<Vec<_>>();
+
+    let order = match style {
+        FontStyle::Italic => [Some(FontStyle::Italic), None, Some(FontStyle::Normal)],
+        FontStyle::Oblique(..) => [None, Some(FontStyle::Italic), Some(FontStyle::Normal)],
+        FontStyle::Normal => [Some(FontStyle::Normal), None, Some(FontStyle::Italic)],
+    };
+
+    for &s in order.iter() {
+        let matching = match s {
+            Some(s) => of_style(s),
+            None => obliques(),
+        };
+
+        if !matching.is_empty() {
+            return matching
+        }
+    }
+
+    return faces
+}
+
+// Weights from 400 to 500 look for heavier faces up to 500 first, then
+// lighter ones, then heavier ones past 500.
+fn match_weight(faces: Vec<&FontFace>, weight: f32) -> Vec<&FontFace> {
+    if weight >= 400.0 && weight <= 500.0 && !faces.iter().any(|f| f.descriptors.weight.0 <= weight && weight <= f.descriptors.weight.1) {
+        let up_to_500: Vec<&FontFace> = faces.iter().cloned().filter(|f| f.descriptors.weight.0 > weight && f.descriptors.weight.0 <= 500.0).collect();
+
+        if !up_to_500.is_empty() {
+            return narrow(up_to_500, |f| Some(f.descriptors.weight), weight, false)
+        }
+    }
+
+    return narrow(faces, |f| Some(f.descriptors.weight), weight, weight <= 500.0)
+}
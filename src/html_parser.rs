@@ -36,7 +36,7 @@ impl<'a> HtmlParser<'a> {
 
                     self.consume_while(char::is_whitespace);
 
-                    let close_tag_name = self.consume_while(is_valid_tag_name);
+                    let close_tag_name = self.consume_while(is_valid_tag_name).to_ascii_lowercase();
 
                     self.consume_while(|x| x != '>');
                     self.chars.next();
@@ -77,12 +77,16 @@ impl<'a> HtmlParser<'a> {
 
 
     fn parse_node(&mut self) -> Node {
-        let tagname = self.consume_while(is_valid_tag_name);
+        let tagname = self.consume_while(is_valid_tag_name).to_ascii_lowercase();
         let attrs = self.parse_attrs();
 
-        let elem = ElementData::new(tagname, attrs);
+        let children = match tagname.as_ref() {
+            "style" => self.parse_raw_text(&tagname),
+            t if is_void_element(t) => Vec::new(),
+            _ => self.parse_nodes(),
+        };
 
-        let children = self.parse_nodes();
+        let elem = ElementData::new(tagname, attrs);
 
         return Node::new(NodeType::Element(elem), children);
     }
@@ -109,6 +113,43 @@ impl<'a> HtmlParser<'a> {
     }
 
 
+    // The contents of an element like <style>, which are text up to its end
+    // tag rather than markup, kept as they are.
+    fn parse_raw_text(&mut self, tag_name: &str) -> Vec<Node> {
+        let mut text_content = String::new();
+
+        loop {
+            text_content.push_str(&self.consume_while(|c| c != '<'));
+
+            if self.chars.next().is_none() {
+                break;
+            }
+
+            let mut lookahead = self.chars.clone();
+
+            let is_end_tag = lookahead.next() == Some('/')
+                && tag_name.chars().all(|t| lookahead.next().map_or(false, |c| c.to_ascii_lowercase() == t))
+                && lookahead.peek().map_or(true, |c| *c == '>' || *c == '/' || c.is_whitespace());
+
+            if is_end_tag {
+                self.consume_while(|c| c != '>');
+                self.chars.next();
+
+                self.node_q.push(tag_name.to_string());
+
+                break;
+            }
+
+            text_content.push('<');
+        }
+
+        return match text_content.is_empty() {
+            true => Vec::new(),
+            false => vec![Node::new(NodeType::Text(text_content), Vec::new())],
+        }
+    }
+
+
     fn parse_comment_node(&mut self) -> Node {
         let mut comment_content = String::new();
 
@@ -231,7 +272,19 @@ impl<'a> HtmlParser<'a> {
         while self.chars.peek().map_or(false, |c| *c != '>') {
             self.consume_while(char::is_whitespace);
 
-            let name = self.consume_while(char::is_whitespace);
+            // HTML attribute names are case-insensitive.
+            let name = self.consume_while(is_valid_attr_name).to_ascii_lowercase();
+
+            if name.is_empty() {
+                // Skip whatever can't start a name, like the `/` in `<br/>`.
+                if self.chars.peek().map_or(false, |c| *c != '>') {
+                    self.chars.next();
+                }
+
+                continue
+            }
+
+            self.consume_while(char::is_whitespace);
 
             let value = if self.chars.peek().map_or(false, |c| *c == '=') {
                 self.chars.next();
@@ -294,6 +347,14 @@ impl<'a> HtmlParser<'a> {
     
 }
 
+// Elements that can't have children, and have no end tag.
+fn is_void_element(tag_name: &str) -> bool {
+    return match tag_name {
+        "area" | "base" | "br" | "col" | "embed" | "hr" | "img" | "input" | "link" | "meta" | "source" | "track" | "wbr" => true,
+        _ => false,
+    }
+}
+
 fn is_valid_tag_name(ch: char) -> bool {
     return ch.is_digit(36)
 }
@@ -325,4 +386,44 @@ fn is_valid_attr_value(c: char) -> bool {
         ' ' | '"' | '\'' | '=' | '<' | '>' | '`' => false,
         _ => true,
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The tree as `tag(children)`, with text nodes quoted.
+    fn outline(node: &Node) -> String {
+        let children: Vec<String> = node.children.iter().map(outline).collect();
+
+        return match node.node_type {
+            NodeType::Text(ref t) => format!("{:?}", t),
+            NodeType::Comment(_) => String::from("comment"),
+            NodeType::Element(ref e) if children.is_empty() => e.tag_name.clone(),
+            NodeType::Element(ref e) => format!("{}({})", e.tag_name, children.join(" ")),
+        }
+    }
+
+    fn parse(html: &str) -> Vec<String> {
+        return HtmlParser::new(html).parse_nodes().iter().map(outline).collect()
+    }
+
+    #[test]
+    fn style_contents_are_raw_text() {
+        assert_eq!(
+            parse("<div><style>a > b { content: \"<p>\" } </b></style><p>x</p></div>"),
+            vec!["div(style(\"a > b { content: \\\"<p>\\\" } </b>\") p(\"x\"))"],
+        );
+        assert_eq!(parse("<div><STYLE>a {}</Style ><p></p></div>"), vec!["div(style(\"a {}\") p)"]);
+        assert_eq!(parse("<div><style></style></div>"), vec!["div(style)"]);
+    }
+
+    #[test]
+    fn void_elements_have_no_children() {
+        assert_eq!(
+            parse("<p>a<br>b<img src=\"x.png\"><input value=\"y\">c</p>"),
+            vec!["p(\"a\" br \"b\" img input \"c\")"],
+        );
+        assert_eq!(parse("<div><br><p>a</p></div>"), vec!["div(br p(\"a\"))"]);
+    }
+}
@@ -1,19 +1,44 @@
 use std::fmt;
+use std::ptr;
 
+use crate::animation::Timeline;
 use crate::css::{
-    Unit,
+    PseudoElement,
+    Stylesheet,
     Value,
 };
+use crate::dom::Node;
 use crate::style::{
+    resolve_length,
+    ContainerSizes,
+    ContainerType,
     Display,
+    LengthContext,
     StyledNode,
+    Viewport,
 };
 
+// How many times the tree is restyled for container queries at most. Each
+// pass settles at least one more level of nested containers.
+const MAX_CONTAINER_PASSES: usize = 16;
+
+// There's no text shaping, so every character is taken to be half an em
+// wide, and lines are as tall as `line-height: normal` usually is.
+const CHARACTER_WIDTH: f32 = 0.5;
+const LINE_HEIGHT: f32 = 1.2;
+
 #[derive(Clone)]
 pub struct LayoutBox<'a> {
     pub dimensions: Dimensions,
     box_type: BoxType,
+    context: LengthContext,
     pub styled_node: &'a StyledNode<'a>,
+    // The text of a text fragment.
+    pub text: Option<String>,
+    // The inline-level nodes an anonymous box lays out into lines, and
+    // whether its first line is the first line of the block it's in.
+    inline: Vec<&'a StyledNode<'a>>,
+    first_line: bool,
     pub children: Vec<LayoutBox<'a>>,
 }
 
@@ -48,14 +73,21 @@ pub enum BoxType {
     InlineBlock,
     Inline,
     Anonymous,
+    // A line of an anonymous box, and a run of text in one style on it.
+    Line,
+    Text,
 }
 
 impl<'a> LayoutBox<'a> {
-    pub fn new(box_type: BoxType, styled_node: &'a StyledNode) -> LayoutBox<'a> {
+    pub fn new(box_type: BoxType, styled_node: &'a StyledNode, context: LengthContext) -> LayoutBox<'a> {
         return LayoutBox {
             dimensions: Default::default(),
             box_type,
+            context,
             styled_node,
+            text: None,
+            inline: Vec::new(),
+            first_line: false,
             children: Vec::new(),
         }
     }
@@ -65,7 +97,9 @@ impl<'a> LayoutBox<'a> {
             BoxType::Block => self.layout_block(b_box),
             BoxType::Inline => self.layout_block(b_box),
             BoxType::InlineBlock => self.layout_inline_block(b_box),
-            BoxType::Anonymous => {},
+            BoxType::Anonymous => self.layout_lines(b_box),
+            // Lines and text are placed by the anonymous box they're in.
+            BoxType::Line | BoxType::Text => {},
         }
     }
 
@@ -73,37 +107,25 @@ impl<'a> LayoutBox<'a> {
         self.calculate_width(b_box);
         self.calculate_pos(b_box);
         self.layout_children();
-        self.calculate_height();
+        self.calculate_height(b_box);
     }
 
     fn calculate_width(&mut self, b_box: Dimensions) {
         let s = self.styled_node;
+        let c = &self.context;
         let d = &mut self.dimensions;
 
-        let width = get_abs_num(s, b_box, "width").unwrap_or(0.0);
-        let margin_left = s.value("margin-left");
-        let margin_right = s.value("margin-right");
-
-        let margin_left_num = match margin_left {
-            Some(m) => match **m {
-                Value::Other(ref s) => s.parse().unwrap_or(0.0),
-                _ => 0.0,
-            },
-            None => 0.0,
-        };
+        let width = get_abs_num(s, b_box, c, "width").unwrap_or(0.0);
+        let margin_left = s.value("margin-left").filter(|m| !is_auto(m));
+        let margin_right = s.value("margin-right").filter(|m| !is_auto(m));
 
-        let margin_right_num = match margin_right {
-            Some(m) => match **m {
-                Value::Other(ref s) => s.parse().unwrap_or(0.0),
-                _ => 0.0,
-            },
-            None => 0.0,
-        };
+        let margin_left_num = get_abs_num(s, b_box, c, "margin-left").unwrap_or(0.0);
+        let margin_right_num = get_abs_num(s, b_box, c, "margin-right").unwrap_or(0.0);
 
-        d.border.left = s.num_or("border-left-width", 0.0);
-        d.border.right = s.num_or("border-left-right", 0.0);
-        d.padding.left = s.num_or("padding-left", 0.0);
-        d.padding.right = s.num_or("padding-right", 0.0);
+        d.border.left = get_abs_num(s, b_box, c, "border-left-width").unwrap_or(0.0);
+        d.border.right = get_abs_num(s, b_box, c, "border-right-width").unwrap_or(0.0);
+        d.padding.left = get_abs_num(s, b_box, c, "padding-left").unwrap_or(0.0);
+        d.padding.right = get_abs_num(s, b_box, c, "padding-right").unwrap_or(0.0);
 
         let total = width + margin_left_num + margin_right_num + d.border.left + d.border.right + d.padding.left + d.padding.right;
 
@@ -146,24 +168,28 @@ impl<'a> LayoutBox<'a> {
 
     fn calculate_pos(&mut self, b_box: Dimensions) {
         let s = self.styled_node;
+        let c = &self.context;
         let d = &mut self.dimensions;
 
-        d.margin.top = s.num_or("margin-top", 0.0);
-        d.margin.bottom = s.num_or("margin-bottom", 0.0);
-        d.border.top = s.num_or("border-top-width", 0.0);
-        d.border.bottom = s.num_or("border-bottom-width", 0.0);
-        d.padding.top = s.num_or("padding-top", 0.0);
-        d.padding.bottom = s.num_or("padding-bottom", 0.0);
+        d.margin.top = get_abs_num(s, b_box, c, "margin-top").unwrap_or(0.0);
+        d.margin.bottom = get_abs_num(s, b_box, c, "margin-bottom").unwrap_or(0.0);
+        d.border.top = get_abs_num(s, b_box, c, "border-top-width").unwrap_or(0.0);
+        d.border.bottom = get_abs_num(s, b_box, c, "border-bottom-width").unwrap_or(0.0);
+        d.padding.top = get_abs_num(s, b_box, c, "padding-top").unwrap_or(0.0);
+        d.padding.bottom = get_abs_num(s, b_box, c, "padding-bottom").unwrap_or(0.0);
 
         d.content.x = b_box.content.x + d.margin.left + d.border.left + d.padding.left;
         d.content.y = b_box.content.height + b_box.content.y + d.margin.top + d.border.top + d.padding.top;
     }
 
-    fn calculate_height(&mut self) {
-        self.styled_node.value("height").map_or((), |h| match **h {
-            Value::Length(n, _) => self.dimensions.content.height = n,
-            _ => {},
-        });
+    fn calculate_height(&mut self, b_box: Dimensions) {
+        if let Some(h) = get_abs_num(self.styled_node, b_box, &self.context, "height") {
+            self.dimensions.content.height = h;
+        } else if self.styled_node.container_type() == ContainerType::Size {
+            // Size containment: the contents don't count towards the height,
+            // so queries against it can't change it.
+            self.dimensions.content.height = 0.0;
+        }
     }
 
     fn layout_children(&mut self) {
@@ -175,7 +201,7 @@ impl<'a> LayoutBox<'a> {
         for child in &mut self.children {
             match prevBoxType {
                 BoxType::InlineBlock => match child.box_type {
-                    BoxType::Block => {
+                    BoxType::Block | BoxType::Anonymous => {
                         d.content.height += max_child_height;
                         d.current.x = 0.0;
                     },
@@ -193,7 +219,7 @@ impl<'a> LayoutBox<'a> {
             }
 
             match child.box_type {
-                BoxType::Block => d.content.height += child.dimensions.margin_box().height,
+                BoxType::Block | BoxType::Anonymous => d.content.height += child.dimensions.margin_box().height,
                 BoxType::InlineBlock => {
                     d.current.x += child.dimensions.margin_box().width;
 
@@ -218,36 +244,130 @@ impl<'a> LayoutBox<'a> {
         self.calculate_inline_width(b_box);
         self.calculate_inline_pos(b_box);
         self.layout_children();
-        self.calculate_height();
+        self.calculate_height(b_box);
     }
 
     fn calculate_inline_width(&mut self, b_box: Dimensions) {
         let s = self.styled_node;
+        let c = &self.context;
         let d = &mut self.dimensions;
 
-        d.content.width = get_abs_num(s, b_box, "width").unwrap_or(0.0);
-        d.margin.left = s.num_or("margin-left", 0.0);
-        d.margin.right = s.num_or("margin-right", 0.0);
-        d.padding.left = s.num_or("padding-left", 0.0);
-        d.padding.right = s.num_or("padding-right", 0.0);
-        d.border.left = s.num_or("border-left-width", 0.0);
-        d.border.right = s.num_or("border-right-width", 0.0);
+        d.content.width = get_abs_num(s, b_box, c, "width").unwrap_or(0.0);
+        d.margin.left = get_abs_num(s, b_box, c, "margin-left").unwrap_or(0.0);
+        d.margin.right = get_abs_num(s, b_box, c, "margin-right").unwrap_or(0.0);
+        d.padding.left = get_abs_num(s, b_box, c, "padding-left").unwrap_or(0.0);
+        d.padding.right = get_abs_num(s, b_box, c, "padding-right").unwrap_or(0.0);
+        d.border.left = get_abs_num(s, b_box, c, "border-left-width").unwrap_or(0.0);
+        d.border.right = get_abs_num(s, b_box, c, "border-right-width").unwrap_or(0.0);
     }
 
     fn calculate_inline_pos(&mut self, b_box: Dimensions) {
         let s = self.styled_node;
+        let c = &self.context;
         let d = &mut self.dimensions;
 
-        d.margin.top = s.num_or("margin-top", 0.0);
-        d.margin.bottom = s.num_or("margin-bottom", 0.0);
-        d.padding.top = s.num_or("padding-top", 0.0);
-        d.padding.bottom = s.num_or("padding-bottom", 0.0);
-        d.border.top = s.num_or("border-top-width", 0.0);
-        d.border.bottom = s.num_or("border-bottom-width", 0.0);
+        d.margin.top = get_abs_num(s, b_box, c, "margin-top").unwrap_or(0.0);
+        d.margin.bottom = get_abs_num(s, b_box, c, "margin-bottom").unwrap_or(0.0);
+        d.padding.top = get_abs_num(s, b_box, c, "padding-top").unwrap_or(0.0);
+        d.padding.bottom = get_abs_num(s, b_box, c, "padding-bottom").unwrap_or(0.0);
+        d.border.top = get_abs_num(s, b_box, c, "border-top-width").unwrap_or(0.0);
+        d.border.bottom = get_abs_num(s, b_box, c, "border-bottom-width").unwrap_or(0.0);
 
         d.content.x = b_box.content.x + b_box.current.x + d.margin.left + d.border.left + d.padding.left;
         d.content.y = b_box.content.y + b_box.current.y + d.margin.top + d.border.top + d.padding.top;
     }
+
+    // Breaks the text of the inline-level nodes into lines as wide as the
+    // containing block, at spaces. The first line of a block gets the
+    // styles of its ::first-line and ::first-letter.
+    fn layout_lines(&mut self, b_box: Dimensions) {
+        let block = self.styled_node;
+        let d = &mut self.dimensions;
+
+        d.content.x = b_box.content.x;
+        d.content.y = b_box.content.y + b_box.content.height;
+        d.content.width = b_box.content.width;
+        d.content.height = 0.0;
+
+        let mut words = Vec::new();
+        let mut space = false;
+
+        for &node in &self.inline {
+            collect_words(node, true, &mut words, &mut space);
+        }
+
+        let (first_line, first_letter) = match self.first_line {
+            true => (block.part(PseudoElement::FirstLine), block.part(PseudoElement::FirstLetter)),
+            false => (None, None),
+        };
+
+        if let (Some(first_letter), Some(word)) = (first_letter, words.first_mut()) {
+            split_first_letter(word, first_letter);
+        }
+
+        let mut lines: Vec<LayoutBox<'a>> = Vec::new();
+        let mut line = LayoutBox::new(BoxType::Line, block, self.context);
+        let mut line_style = first_line;
+        let mut x = 0.0;
+
+        for word in words {
+            let mut space = match word.space_before && !line.children.is_empty() {
+                true => word_space(&word, line_style),
+                false => 0.0,
+            };
+
+            if !line.children.is_empty() && x + space + word_width(&word, line_style) > d.content.width {
+                lines.push(finish_line(line, d));
+                line = LayoutBox::new(BoxType::Line, block, self.context);
+                line_style = None;
+                x = 0.0;
+                space = 0.0;
+            }
+
+            x += space;
+
+            for (i, segment) in word.segments.iter().enumerate() {
+                let node = segment_style(segment, line_style);
+                let width = text_width(&segment.text, node);
+                let gap = if i == 0 { space } else { 0.0 };
+
+                // Text next to text in the same style is one fragment.
+                match line.children.last_mut() {
+                    Some(fragment) if ptr::eq(fragment.styled_node, node) => {
+                        let text = fragment.text.get_or_insert_with(String::new);
+
+                        if gap > 0.0 {
+                            text.push(' ');
+                        }
+
+                        text.push_str(&segment.text);
+                        fragment.dimensions.content.width += gap + width;
+                    },
+                    _ => {
+                        let mut fragment = LayoutBox::new(BoxType::Text, node, self.context);
+
+                        fragment.text = Some(segment.text.clone());
+                        fragment.dimensions.content = Rectangle {
+                            x: d.content.x + x,
+                            y: 0.0,
+                            width,
+                            height: node.font_size() * LINE_HEIGHT,
+                        };
+
+                        line.children.push(fragment);
+                    },
+                }
+
+                x += width;
+            }
+        }
+
+        if !line.children.is_empty() {
+            lines.push(finish_line(line, d));
+        }
+
+        self.children = lines;
+    }
 }
 
 impl<'a> fmt::Debug for LayoutBox<'a> {
@@ -327,62 +447,381 @@ impl fmt::Debug for BoxType {
             BoxType::Inline => "inline",
             BoxType::InlineBlock => "inline-block",
             BoxType::Anonymous => "anonymous",
+            BoxType::Line => "line",
+            BoxType::Text => "text",
         };
 
         return write!(f, "{}", display_type)
     }
 }
 
-fn get_abs_num(styled_node: &StyledNode, b_box: Dimensions, prop: &str) -> Option<f32> {
-    return match styled_node.value(prop) {
-        Some(ref v) => match ***v {
-            Value::Length(l, ref u) => match *u {
-                Unit::Px => Some(l),
-                Unit::Pct => Some(l * b_box.content.width / 100.0),
-                _ => panic!("unimplemented css unit length"),
-            },
-            _ => None,
-        },
-        None => None,
+fn is_auto(value: &Value) -> bool {
+    return match *value {
+        Value::Other(ref s) => s == "auto",
+        _ => false,
     }
 }
 
+// Percentages are relative to the containing block's width.
+fn get_abs_num(styled_node: &StyledNode, b_box: Dimensions, context: &LengthContext, prop: &str) -> Option<f32> {
+    let context = LengthContext {
+        percent_base: b_box.content.width,
+        font_size: styled_node.font_size(),
+        ..*context
+    };
+
+    return styled_node.value(prop).and_then(|v| resolve_length(v, &context))
+}
+
 pub fn layout_tree<'a>(root: &'a StyledNode<'a>, mut containing_block: Dimensions) -> LayoutBox<'a> {
+    // The initial containing block is the viewport.
+    let context = LengthContext {
+        percent_base: containing_block.content.width,
+        font_size: root.font_size(),
+        root_font_size: root.font_size(),
+        viewport_width: containing_block.content.width,
+        viewport_height: containing_block.content.height,
+    };
+
     containing_block.content.height = 0.0;
 
-    let mut root_box = build_layout_tree(root);
+    let mut root_box = build_layout_tree(root, context);
     root_box.layout(containing_block);
     
     return root_box
 }
 
-fn build_layout_tree<'a>(node: &'a StyledNode) -> LayoutBox<'a> {
+// Styles the tree with `stylesheets`. Container queries are evaluated
+// against the laid-out size of the container, so the tree is styled and laid
+// out until the container sizes stop changing. The returned tree is ready to
+// be laid out with `layout_tree`.
+pub fn style_with_containers<'a>(
+    node: &'a Node,
+    stylesheets: &[&'a Stylesheet],
+    viewport: &Viewport,
+    timeline: &mut Timeline,
+    containing_block: Dimensions,
+) -> StyledNode<'a> {
+    let mut sizes = ContainerSizes::new();
+
+    for _ in 0..MAX_CONTAINER_PASSES {
+        // The passes in between run on a copy of the timeline, so the
+        // animations already running apply to them, but the styles they throw
+        // away don't start or stop anything.
+        let mut scratch = timeline.clone();
+
+        let styled = StyledNode::with_containers(node, stylesheets, viewport, &mut scratch, &sizes);
+        let new_sizes = container_sizes(&layout_tree(&styled, containing_block));
+
+        if new_sizes == sizes {
+            break;
+        }
+
+        sizes = new_sizes;
+    }
+
+    return StyledNode::with_containers(node, stylesheets, viewport, timeline, &sizes)
+}
+
+// The content-box size of every query container in the tree.
+pub fn container_sizes(root: &LayoutBox) -> ContainerSizes {
+    let mut sizes = ContainerSizes::new();
+
+    add_container_sizes(root, &mut sizes);
+
+    return sizes
+}
+
+fn add_container_sizes(layout_box: &LayoutBox, sizes: &mut ContainerSizes) {
+    let styled_node = layout_box.styled_node;
+
+    // Pseudo-elements share their element's node, and can't be containers.
+    // Neither can the boxes in between an element's box and its text.
+    let element_box = match layout_box.box_type {
+        BoxType::Block | BoxType::InlineBlock | BoxType::Inline => true,
+        BoxType::Anonymous | BoxType::Line | BoxType::Text => false,
+    };
+
+    if element_box && styled_node.pseudo_element.is_none() && styled_node.container_type() != ContainerType::Normal {
+        let content = layout_box.dimensions.content;
+
+        sizes.insert(styled_node, content.width, content.height);
+    }
+
+    for child in &layout_box.children {
+        add_container_sizes(child, sizes);
+    }
+}
+
+fn build_layout_tree<'a>(node: &'a StyledNode<'a>, context: LengthContext) -> LayoutBox<'a> {
     let mut layout_node = LayoutBox::new(
-        match node.get_display() {
-            Display::Block => BoxType::Block,
-            Display::Inline => BoxType::Inline,
-            Display::InlineBlock => BoxType::InlineBlock,
-            Display::None => BoxType::Anonymous,
-        },
+        box_type(node.get_display()).unwrap_or(BoxType::Anonymous),
         node,
+        context,
     );
 
+    // A generated box's own text is inline content inside it.
+    if node.text().is_some() && node.pseudo_element.is_some() {
+        add_inline(&mut layout_node, node, context);
+    }
+
     for child in &node.children {
-        match child.get_display() {
-            Display::Block => layout_node.children.push(build_layout_tree(child)),
-            Display::Inline => layout_node.children.push(build_layout_tree(child)),
-            Display::InlineBlock => layout_node.children.push(build_layout_tree(child)),
-            Display::None => {},
+        match box_type(child.get_display()) {
+            // Runs of inline content are laid out into lines by an anonymous
+            // box around them.
+            Some(BoxType::Inline) if is_inline_content(child) => add_inline(&mut layout_node, child, context),
+            Some(_) => layout_node.children.push(build_layout_tree(child, context)),
+            None => {},
         }
     }
 
     return layout_node
 }
 
+fn add_inline<'a>(layout_node: &mut LayoutBox<'a>, node: &'a StyledNode<'a>, context: LengthContext) {
+    if let Some(last) = layout_node.children.last_mut() {
+        if let BoxType::Anonymous = last.box_type {
+            last.inline.push(node);
+
+            return
+        }
+    }
+
+    let mut anonymous = LayoutBox::new(BoxType::Anonymous, layout_node.styled_node, context);
+
+    anonymous.inline.push(node);
+    anonymous.first_line = layout_node.children.is_empty();
+
+    layout_node.children.push(anonymous);
+}
+
+// Whether the node is inline and so is everything in it. Inline elements
+// with blocks or inline-blocks in them are still laid out as blocks.
+fn is_inline_content(node: &StyledNode) -> bool {
+    return match box_type(node.get_display()) {
+        Some(BoxType::Inline) => node.children.iter().all(|c| box_type(c.get_display()).is_none() || is_inline_content(c)),
+        _ => false,
+    }
+}
+
+// A piece of text in one style that can't be broken.
+struct Segment<'a> {
+    node: &'a StyledNode<'a>,
+    // Text that's a child of the block, and so is styled by ::first-line.
+    in_block: bool,
+    text: String,
+}
+
+// Text between spaces, which lines can break in front of.
+struct Word<'a> {
+    space_before: bool,
+    segments: Vec<Segment<'a>>,
+}
+
+// Splits the text in `node` into words, collapsing whitespace. `space` says
+// whether there was whitespace at the end of the text before.
+fn collect_words<'a>(node: &'a StyledNode<'a>, in_block: bool, words: &mut Vec<Word<'a>>, space: &mut bool) {
+    if box_type(node.get_display()).is_none() {
+        return
+    }
+
+    if let Some(text) = node.text() {
+        let in_block = in_block && node.pseudo_element.is_none();
+
+        for c in text.chars() {
+            if c.is_whitespace() {
+                *space = true;
+
+                continue
+            }
+
+            if *space || words.is_empty() {
+                words.push(Word { space_before: *space, segments: Vec::new() });
+                *space = false;
+            }
+
+            let segments = &mut words.last_mut().unwrap().segments;
+
+            match segments.last_mut() {
+                Some(segment) if ptr::eq(segment.node, node) => segment.text.push(c),
+                _ => segments.push(Segment { node, in_block, text: c.to_string() }),
+            }
+        }
+    }
+
+    for child in &node.children {
+        collect_words(child, false, words, space);
+    }
+}
+
+// Moves the first letter of the first word, with the punctuation in front of
+// it, into a segment styled by ::first-letter.
+fn split_first_letter<'a>(word: &mut Word<'a>, first_letter: &'a StyledNode<'a>) {
+    let segment = &mut word.segments[0];
+    let punctuation = segment.text.chars().take_while(|&c| is_punctuation(c)).count();
+    let end = segment.text.char_indices().nth(punctuation + 1).map_or(segment.text.len(), |(i, _)| i);
+
+    let letter: String = segment.text.drain(..end).collect();
+
+    if segment.text.is_empty() {
+        word.segments.remove(0);
+    }
+
+    word.segments.insert(0, Segment { node: first_letter, in_block: false, text: letter });
+}
+
+fn is_punctuation(c: char) -> bool {
+    return c.is_ascii_punctuation() || "\u{a1}\u{ab}\u{bb}\u{bf}\u{2018}\u{2019}\u{201c}\u{201d}".contains(c)
+}
+
+// The style text is drawn in: the block's ::first-line for text in the
+// block on its first line, or else the text's own. Text in inline elements
+// on the first line keeps the style of its element.
+fn segment_style<'a>(segment: &Segment<'a>, first_line: Option<&'a StyledNode<'a>>) -> &'a StyledNode<'a> {
+    return match first_line {
+        Some(first_line) if segment.in_block => first_line,
+        _ => segment.node,
+    }
+}
+
+fn text_width(text: &str, node: &StyledNode) -> f32 {
+    return text.chars().count() as f32 * node.font_size() * CHARACTER_WIDTH
+}
+
+fn word_width(word: &Word, first_line: Option<&StyledNode>) -> f32 {
+    return word.segments.iter().map(|s| text_width(&s.text, segment_style(s, first_line))).sum()
+}
+
+// Spaces are as wide as a character of the text after them.
+fn word_space(word: &Word, first_line: Option<&StyledNode>) -> f32 {
+    return text_width(" ", segment_style(&word.segments[0], first_line))
+}
+
+// Makes the line as tall as its tallest fragment, with the fragments on the
+// bottom of it in place of baseline alignment, and puts it under the lines
+// before it.
+fn finish_line<'a>(mut line: LayoutBox<'a>, d: &mut Dimensions) -> LayoutBox<'a> {
+    let height = line.children.iter().map(|f| f.dimensions.content.height).fold(0.0, f32::max);
+    let y = d.content.y + d.content.height;
+
+    for fragment in &mut line.children {
+        fragment.dimensions.content.y = y + height - fragment.dimensions.content.height;
+    }
+
+    line.dimensions.content = Rectangle { x: d.content.x, y, width: d.content.width, height };
+    d.content.height += height;
+
+    return line
+}
+
+// The box an element generates, if any. There's no table layout, so the
+// parts of a table are laid out as blocks, and columns generate no boxes.
+fn box_type(display: Display) -> Option<BoxType> {
+    return match display {
+        Display::Block | Display::ListItem => Some(BoxType::Block),
+        Display::Table | Display::TableRowGroup | Display::TableHeaderGroup | Display::TableFooterGroup => Some(BoxType::Block),
+        Display::TableRow | Display::TableCell | Display::TableCaption => Some(BoxType::Block),
+        Display::Inline => Some(BoxType::Inline),
+        Display::InlineBlock | Display::InlineTable => Some(BoxType::InlineBlock),
+        Display::TableColumnGroup | Display::TableColumn | Display::None => None,
+    }
+}
+
 pub fn pretty_print<'a>(n: &'a LayoutBox, level: usize) {
     println!("{}{:?}\n", level, n);
 
     for child in n.children.iter() {
         pretty_print(&child, level + 1);
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css_parser::CssParser;
+    use crate::html_parser::HtmlParser;
+
+    // Lays `html` out 100px wide with `css`, and returns the lines of the
+    // first box inside its root, as text fragments with their pseudo-element
+    // and height.
+    fn lines(html: &str, css: &str) -> Vec<Vec<(String, Option<PseudoElement>, f32)>> {
+        let nodes = HtmlParser::new(html).parse_nodes();
+        let sheet = CssParser::new(css).parse_stylesheet();
+        let styled = StyledNode::new(&nodes[0], &sheet, &Viewport::default());
+
+        let mut containing_block = Dimensions::default();
+        containing_block.content.width = 100.0;
+
+        let root = layout_tree(&styled, containing_block);
+        let anonymous = root.children.iter().find(|c| match c.box_type {
+            BoxType::Anonymous => true,
+            _ => false,
+        });
+
+        return anonymous.map_or(Vec::new(), |a| a.children.iter().map(|line| line.children.iter().map(|f| {
+            (f.text.clone().unwrap_or_default(), f.styled_node.pseudo_element, f.dimensions.content.height)
+        }).collect()).collect())
+    }
+
+    fn text(html: &str, css: &str) -> Vec<Vec<String>> {
+        return lines(html, css).into_iter().map(|l| l.into_iter().map(|f| f.0).collect()).collect()
+    }
+
+    #[test]
+    fn text_breaks_into_lines_at_spaces() {
+        let css = "p { font-size: 20px }";
+
+        assert_eq!(text("<p>aaaa  bbbb\n cccc</p>", css), vec![vec!["aaaa bbbb"], vec!["cccc"]]);
+        assert_eq!(text("<p>aaaa<b>bbbb</b>cc dd</p>", css), vec![vec!["aaaa", "bbbb", "cc"], vec!["dd"]]);
+        assert_eq!(lines("<p>a</p>", css)[0][0].2, 24.0);
+    }
+
+    #[test]
+    fn first_line_styles_the_first_line() {
+        let css = "p { font-size: 20px } p::first-line { font-size: 40px }";
+        let lines = lines("<p>aaaa bbbb cccc</p>", css);
+
+        assert_eq!(lines[0], vec![(String::from("aaaa"), Some(PseudoElement::FirstLine), 48.0)]);
+        assert_eq!(lines[1], vec![(String::from("bbbb cccc"), None, 24.0)]);
+    }
+
+    #[test]
+    fn first_letter_styles_the_first_letter_and_punctuation() {
+        let css = "p { font-size: 10px } p::first-line { color: red } p::first-letter { font-size: 30px }";
+        let lines = lines("<p>\"Hello world</p>", css);
+
+        assert_eq!(lines[0][0], (String::from("\"H"), Some(PseudoElement::FirstLetter), 36.0));
+        assert_eq!(lines[0][1], (String::from("ello world"), Some(PseudoElement::FirstLine), 12.0));
+    }
+
+    #[test]
+    fn first_line_only_applies_to_the_first_line_of_the_block() {
+        let css = "p::first-line { font-size: 40px }";
+
+        assert_eq!(lines("<p><div></div>aa</p>", css), vec![vec![(String::from("aa"), None, 19.2)]]);
+        assert_eq!(lines("<p>aa<div></div>bb</p>", css), vec![vec![(String::from("aa"), Some(PseudoElement::FirstLine), 48.0)]]);
+    }
+
+    #[test]
+    fn placeholders_show_in_empty_inputs() {
+        let css = "input::placeholder { font-size: 10px }";
+
+        assert_eq!(lines("<p><input placeholder=\"Name\"></p>", css), vec![vec![(String::from("Name"), Some(PseudoElement::Placeholder), 12.0)]]);
+        assert_eq!(text("<p><input placeholder=\"Name\" value=\"x\"></p>", css), Vec::<Vec<String>>::new());
+    }
+
+    #[test]
+    fn auto_widths_leave_room_for_both_borders() {
+        let nodes = HtmlParser::new("<div><p></p></div>").parse_nodes();
+        let sheet = CssParser::new("div, p { display: block } p { border-left-width: 5px; border-right-width: 10px }").parse_stylesheet();
+        let styled = StyledNode::new(&nodes[0], &sheet, &Viewport::default());
+
+        let mut containing_block = Dimensions::default();
+        containing_block.content.width = 100.0;
+
+        let root = layout_tree(&styled, containing_block);
+        let p = &root.children[0].dimensions;
+
+        assert_eq!((p.content.width, p.border.left, p.border.right), (85.0, 5.0, 10.0));
+    }
+}
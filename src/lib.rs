@@ -1,6 +1,15 @@
-mod dom;
-mod html_parser;
-mod css;
-mod css_parser;
-mod style;
-mod layout;
\ No newline at end of file
+pub mod dom;
+pub mod html_parser;
+pub mod css;
+mod color;
+pub mod css_parser;
+pub mod cssom;
+pub mod loader;
+pub mod document;
+pub mod sfnt;
+pub mod font;
+mod properties;
+mod user_agent;
+pub mod style;
+pub mod animation;
+pub mod layout;
\ No newline at end of file
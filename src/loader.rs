@@ -0,0 +1,116 @@
+use std::fs;
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+pub trait StylesheetLoader {
+    // Turns `url` into an absolute URL, relative to the stylesheet or
+    // document at `base` if there is one.
+    fn resolve(&self, url: &str, base: Option<&str>) -> String;
+
+    fn load(&mut self, url: &str) -> Option<String>;
+}
+
+// Font files are found the same way as the stylesheets that refer to them.
+pub trait FontLoader: StylesheetLoader {
+    fn load_font(&mut self, url: &str) -> Option<Vec<u8>>;
+
+    // URLs of the fonts installed on the system, which `local()` sources
+    // and fallback fonts come from.
+    fn installed_fonts(&mut self) -> Vec<String>;
+}
+
+// Loads stylesheets from disk, with relative URLs resolved against the
+// directory of the importing file or `root`. Installed fonts are looked for
+// in `font_dirs`.
+pub struct FileSystemLoader {
+    root: PathBuf,
+    font_dirs: Vec<PathBuf>,
+}
+
+impl FileSystemLoader {
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        return FileSystemLoader {
+            root: root.as_ref().to_path_buf(),
+            font_dirs: Vec::new(),
+        }
+    }
+
+    pub fn with_font_dirs<P: AsRef<Path>>(root: P, font_dirs: &[P]) -> Self {
+        return FileSystemLoader {
+            root: root.as_ref().to_path_buf(),
+            font_dirs: font_dirs.iter().map(|d| d.as_ref().to_path_buf()).collect(),
+        }
+    }
+}
+
+impl StylesheetLoader for FileSystemLoader {
+    fn resolve(&self, url: &str, base: Option<&str>) -> String {
+        let url = url.trim_start_matches("file://");
+
+        let dir = match base {
+            Some(b) => Path::new(b.trim_start_matches("file://"))
+                .parent()
+                .map_or(self.root.clone(), Path::to_path_buf),
+            None => self.root.clone(),
+        };
+
+        let path = dir.join(url);
+
+        return fs::canonicalize(&path)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    fn load(&mut self, url: &str) -> Option<String> {
+        return fs::read_to_string(url).ok()
+    }
+}
+
+impl FontLoader for FileSystemLoader {
+    fn load_font(&mut self, url: &str) -> Option<Vec<u8>> {
+        return fs::read(url).ok()
+    }
+
+    fn installed_fonts(&mut self) -> Vec<String> {
+        let mut fonts = Vec::new();
+
+        for dir in &self.font_dirs {
+            find_fonts(dir, &mut fonts);
+        }
+
+        fonts.sort();
+
+        return fonts
+    }
+}
+
+fn find_fonts(dir: &Path, fonts: &mut Vec<String>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    for entry in entries.filter_map(Result::ok) {
+        let path = entry.path();
+
+        if path.is_dir() {
+            find_fonts(&path, fonts);
+
+            continue
+        }
+
+        let extension = path.extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+
+        match extension.as_ref().map(String::as_ref) {
+            Some("ttf") | Some("otf") | Some("ttc") | Some("otc") => {
+                fonts.push(path.to_string_lossy().into_owned());
+            },
+            _ => {},
+        }
+    }
+}
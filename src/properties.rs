@@ -0,0 +1,126 @@
+// The properties this engine understands, and the values each one accepts.
+
+pub struct Property {
+    pub name: &'static str,
+    pub syntax: Syntax,
+    pub inherited: bool,
+    pub initial: &'static str,
+}
+
+pub enum Syntax {
+    Color,
+    // A length or percentage, or one of the keywords.
+    Length(&'static [&'static str]),
+    Keywords(&'static [&'static str]),
+    Content,
+    Quotes,
+    // Counter names, each optionally followed by an integer, which defaults
+    // to the given value.
+    Counters(i32),
+    // A number or percentage, or one of the keywords.
+    Number(&'static [&'static str]),
+    // A custom identifier, or one of the keywords.
+    Identifier(&'static [&'static str]),
+    // Whitespace-separated custom identifiers, or one of the keywords.
+    Identifiers(&'static [&'static str]),
+    Time,
+    Easing,
+    Transform,
+    // Comma-separated family names.
+    FontFamily,
+    // A weight from 1 to 1000, `normal`, `bold`, `bolder` or `lighter`.
+    FontWeight,
+    // `normal`, `italic`, or `oblique` with an optional angle.
+    FontStyle,
+    // A width keyword or a percentage of the normal width, kept as the
+    // percentage.
+    FontStretch,
+    // A comma-separated list of values of the given syntax.
+    List(&'static Syntax),
+}
+
+pub const CSS_WIDE_KEYWORDS: &[&str] = &["inherit", "initial", "unset", "revert", "revert-layer"];
+
+const DISPLAY_KEYWORDS: &[&str] = &[
+    "block", "inline", "inline-block", "list-item", "table", "inline-table", "table-row-group",
+    "table-header-group", "table-footer-group", "table-row", "table-cell", "table-column-group",
+    "table-column", "table-caption", "none",
+];
+
+const PROPERTIES: &[Property] = &[
+    Property { name: "display", syntax: Syntax::Keywords(DISPLAY_KEYWORDS), inherited: false, initial: "inline" },
+    Property { name: "color", syntax: Syntax::Color, inherited: true, initial: "black" },
+    Property { name: "background-color", syntax: Syntax::Color, inherited: false, initial: "transparent" },
+    Property { name: "border-color", syntax: Syntax::Color, inherited: false, initial: "currentcolor" },
+    Property { name: "font-family", syntax: Syntax::FontFamily, inherited: true, initial: "serif" },
+    Property { name: "font-weight", syntax: Syntax::FontWeight, inherited: true, initial: "normal" },
+    Property { name: "font-style", syntax: Syntax::FontStyle, inherited: true, initial: "normal" },
+    Property { name: "font-stretch", syntax: Syntax::FontStretch, inherited: true, initial: "normal" },
+    Property { name: "font-size", syntax: Syntax::Length(&["xx-small", "x-small", "small", "medium", "large", "x-large", "xx-large", "smaller", "larger"]), inherited: true, initial: "medium" },
+    Property { name: "width", syntax: Syntax::Length(&["auto"]), inherited: false, initial: "auto" },
+    Property { name: "height", syntax: Syntax::Length(&["auto"]), inherited: false, initial: "auto" },
+    Property { name: "margin-top", syntax: Syntax::Length(&["auto"]), inherited: false, initial: "0" },
+    Property { name: "margin-right", syntax: Syntax::Length(&["auto"]), inherited: false, initial: "0" },
+    Property { name: "margin-bottom", syntax: Syntax::Length(&["auto"]), inherited: false, initial: "0" },
+    Property { name: "margin-left", syntax: Syntax::Length(&["auto"]), inherited: false, initial: "0" },
+    Property { name: "padding-top", syntax: Syntax::Length(&[]), inherited: false, initial: "0" },
+    Property { name: "padding-right", syntax: Syntax::Length(&[]), inherited: false, initial: "0" },
+    Property { name: "padding-bottom", syntax: Syntax::Length(&[]), inherited: false, initial: "0" },
+    Property { name: "padding-left", syntax: Syntax::Length(&[]), inherited: false, initial: "0" },
+    Property { name: "border-top-width", syntax: Syntax::Length(&[]), inherited: false, initial: "0" },
+    Property { name: "border-right-width", syntax: Syntax::Length(&[]), inherited: false, initial: "0" },
+    Property { name: "border-bottom-width", syntax: Syntax::Length(&[]), inherited: false, initial: "0" },
+    Property { name: "border-left-width", syntax: Syntax::Length(&[]), inherited: false, initial: "0" },
+    Property { name: "content", syntax: Syntax::Content, inherited: false, initial: "normal" },
+    Property { name: "quotes", syntax: Syntax::Quotes, inherited: true, initial: "auto" },
+    Property { name: "counter-reset", syntax: Syntax::Counters(0), inherited: false, initial: "none" },
+    Property { name: "counter-set", syntax: Syntax::Counters(0), inherited: false, initial: "none" },
+    Property { name: "counter-increment", syntax: Syntax::Counters(1), inherited: false, initial: "none" },
+    Property { name: "opacity", syntax: Syntax::Number(&[]), inherited: false, initial: "1" },
+    Property { name: "transform", syntax: Syntax::Transform, inherited: false, initial: "none" },
+    Property { name: "animation-name", syntax: Syntax::List(&Syntax::Identifier(&["none"])), inherited: false, initial: "none" },
+    Property { name: "animation-duration", syntax: Syntax::List(&Syntax::Time), inherited: false, initial: "0s" },
+    Property { name: "animation-timing-function", syntax: Syntax::List(&Syntax::Easing), inherited: false, initial: "ease" },
+    Property { name: "animation-delay", syntax: Syntax::List(&Syntax::Time), inherited: false, initial: "0s" },
+    Property { name: "animation-iteration-count", syntax: Syntax::List(&Syntax::Number(&["infinite"])), inherited: false, initial: "1" },
+    Property { name: "animation-direction", syntax: Syntax::List(&Syntax::Keywords(&["normal", "reverse", "alternate", "alternate-reverse"])), inherited: false, initial: "normal" },
+    Property { name: "animation-fill-mode", syntax: Syntax::List(&Syntax::Keywords(&["none", "forwards", "backwards", "both"])), inherited: false, initial: "none" },
+    Property { name: "animation-play-state", syntax: Syntax::List(&Syntax::Keywords(&["running", "paused"])), inherited: false, initial: "running" },
+    Property { name: "transition-property", syntax: Syntax::List(&Syntax::Identifier(&["none", "all"])), inherited: false, initial: "all" },
+    Property { name: "transition-duration", syntax: Syntax::List(&Syntax::Time), inherited: false, initial: "0s" },
+    Property { name: "transition-timing-function", syntax: Syntax::List(&Syntax::Easing), inherited: false, initial: "ease" },
+    Property { name: "transition-delay", syntax: Syntax::List(&Syntax::Time), inherited: false, initial: "0s" },
+    Property { name: "container-type", syntax: Syntax::Keywords(&["normal", "size", "inline-size"]), inherited: false, initial: "normal" },
+    Property { name: "container-name", syntax: Syntax::Identifiers(&["none"]), inherited: false, initial: "none" },
+    Property { name: "list-style-type", syntax: Syntax::Keywords(&["disc", "circle", "square", "decimal", "lower-alpha", "upper-alpha", "lower-roman", "upper-roman", "none"]), inherited: true, initial: "disc" },
+];
+
+// Properties that set several longhands at once. They're expanded when
+// they're parsed, or by the cascade when they use var().
+pub struct Shorthand {
+    pub name: &'static str,
+    pub longhands: &'static [&'static str],
+}
+
+const SHORTHANDS: &[Shorthand] = &[
+    Shorthand { name: "animation", longhands: &["animation-name", "animation-duration", "animation-timing-function", "animation-delay", "animation-iteration-count", "animation-direction", "animation-fill-mode", "animation-play-state"] },
+    Shorthand { name: "transition", longhands: &["transition-property", "transition-duration", "transition-timing-function", "transition-delay"] },
+    Shorthand { name: "container", longhands: &["container-name", "container-type"] },
+];
+
+pub fn all() -> &'static [Property] {
+    return PROPERTIES
+}
+
+pub fn lookup(name: &str) -> Option<&'static Property> {
+    return PROPERTIES.iter().find(|p| p.name == name)
+}
+
+pub fn lookup_shorthand(name: &str) -> Option<&'static Shorthand> {
+    return SHORTHANDS.iter().find(|s| s.name == name)
+}
+
+// The shorthand that sets `longhand`, if any.
+pub fn shorthand_of(longhand: &str) -> Option<&'static Shorthand> {
+    return SHORTHANDS.iter().find(|s| s.longhands.contains(&longhand))
+}
@@ -0,0 +1,411 @@
+// Reads the tables of TrueType and OpenType fonts that font matching and
+// text layout need. Glyph outlines aren't read, since nothing draws them.
+
+use std::convert::TryFrom;
+
+pub struct Font {
+    pub family: String,
+    // The typographic family from name ID 16, if it's different.
+    pub typographic_family: Option<String>,
+    pub full_name: String,
+    pub postscript_name: String,
+    pub weight: f32,
+    pub italic: bool,
+    pub oblique: bool,
+    // The usWidthClass as a percentage of the normal width.
+    pub stretch: f32,
+    pub units_per_em: u16,
+    pub ascender: i16,
+    pub descender: i16,
+    pub line_gap: i16,
+    num_glyphs: u16,
+    advances: Vec<u16>,
+    // Sorted `(first char, last char, first glyph)` runs.
+    cmap: Vec<(u32, u32, u32)>,
+}
+
+impl Font {
+    // Parses a single font, or the first font of a collection.
+    pub fn parse(data: &[u8]) -> Option<Font> {
+        return match read_tag(data, 0)? {
+            b"ttcf" => Font::parse_collection(data).into_iter().next(),
+            _ => Font::parse_at(data, 0),
+        }
+    }
+
+    // Every font of a TrueType or OpenType collection.
+    pub fn parse_collection(data: &[u8]) -> Vec<Font> {
+        if read_tag(data, 0) != Some(b"ttcf") {
+            return Font::parse(data).into_iter().collect()
+        }
+
+        // The count can't be trusted, so stop at the end of the offsets.
+        let count = read_u32(data, 8).unwrap_or(0) as usize;
+
+        return (0..count)
+            .map_while(|i| read_u32(data, 12 + i * 4))
+            .filter_map(|offset| Font::parse_at(data, offset as usize))
+            .collect()
+    }
+
+    fn parse_at(data: &[u8], offset: usize) -> Option<Font> {
+        match read_u32(data, offset)? {
+            // TrueType outlines, CFF outlines and the old Apple tag.
+            0x00010000 | 0x4f54544f | 0x74727565 => {},
+            _ => return None,
+        }
+
+        let tables = Tables { data, offset, count: read_u16(data, offset + 4)? as usize };
+
+        let head = tables.find(b"head")?;
+        let hhea = tables.find(b"hhea")?;
+        let maxp = tables.find(b"maxp")?;
+
+        let num_glyphs = read_u16(maxp, 4)?;
+        let num_metrics = read_u16(hhea, 34)? as usize;
+        let advances = match tables.find(b"hmtx") {
+            Some(hmtx) => (0..num_metrics).map(|i| read_u16(hmtx, i * 4).unwrap_or(0)).collect(),
+            None => Vec::new(),
+        };
+
+        let mut font = Font {
+            family: String::new(),
+            typographic_family: None,
+            full_name: String::new(),
+            postscript_name: String::new(),
+            weight: 400.0,
+            italic: false,
+            oblique: false,
+            stretch: 100.0,
+            units_per_em: read_u16(head, 18).filter(|&u| u > 0)?,
+            ascender: read_i16(hhea, 4)?,
+            descender: read_i16(hhea, 6)?,
+            line_gap: read_i16(hhea, 8)?,
+            num_glyphs,
+            advances,
+            cmap: tables.find(b"cmap").map(parse_cmap).unwrap_or_default(),
+        };
+
+        if let Some(os2) = tables.find(b"OS/2") {
+            font.weight = read_u16(os2, 4).map_or(400.0, |w| (w as f32).max(1.0).min(1000.0));
+            font.stretch = match read_u16(os2, 6) {
+                Some(1) => 50.0,
+                Some(2) => 62.5,
+                Some(3) => 75.0,
+                Some(4) => 87.5,
+                Some(6) => 112.5,
+                Some(7) => 125.0,
+                Some(8) => 150.0,
+                Some(9) => 200.0,
+                _ => 100.0,
+            };
+
+            let selection = read_u16(os2, 62).unwrap_or(0);
+
+            font.italic = selection & 1 != 0;
+            font.oblique = selection & (1 << 9) != 0;
+        }
+
+        if let Some(name) = tables.find(b"name") {
+            font.family = find_name(name, 1).unwrap_or_default();
+            font.typographic_family = find_name(name, 16).filter(|f| *f != font.family);
+            font.full_name = find_name(name, 4).unwrap_or_default();
+            font.postscript_name = find_name(name, 6).unwrap_or_default();
+        }
+
+        return Some(font)
+    }
+
+    // The names a CSS family name can match.
+    pub fn family_names(&self) -> Vec<&str> {
+        let mut names = vec![self.family.as_ref()];
+
+        names.extend(self.typographic_family.as_deref());
+
+        return names
+    }
+
+    // 0 is the .notdef glyph that missing characters are drawn with.
+    pub fn glyph_index(&self, c: char) -> u32 {
+        let c = c as u32;
+
+        let run = match self.cmap.binary_search_by(|&(start, end, _)| {
+            if end < c {
+                std::cmp::Ordering::Less
+            } else if start > c {
+                std::cmp::Ordering::Greater
+            } else {
+                std::cmp::Ordering::Equal
+            }
+        }) {
+            Ok(i) => self.cmap[i],
+            Err(_) => return 0,
+        };
+
+        return match run.2.checked_add(c - run.0) {
+            Some(glyph) if glyph < self.num_glyphs as u32 => glyph,
+            _ => 0,
+        }
+    }
+
+    pub fn has_glyph(&self, c: char) -> bool {
+        return self.glyph_index(c) != 0
+    }
+
+    // Glyphs past the end of the horizontal metrics share the last advance.
+    pub fn advance_width(&self, glyph: u32, font_size: f32) -> f32 {
+        let advance = self.advances
+            .get(glyph as usize)
+            .or(self.advances.last())
+            .cloned()
+            .unwrap_or(0);
+
+        return self.scale(advance as f32, font_size)
+    }
+
+    // The width of `text` on one line, without kerning or shaping.
+    pub fn text_width(&self, text: &str, font_size: f32) -> f32 {
+        return text.chars()
+            .map(|c| self.advance_width(self.glyph_index(c), font_size))
+            .sum()
+    }
+
+    pub fn ascent(&self, font_size: f32) -> f32 {
+        return self.scale(self.ascender as f32, font_size)
+    }
+
+    // Positive, unlike the descender in the font.
+    pub fn descent(&self, font_size: f32) -> f32 {
+        return self.scale(-(self.descender as f32), font_size)
+    }
+
+    pub fn line_height(&self, font_size: f32) -> f32 {
+        let units = self.ascender as f32 - self.descender as f32 + self.line_gap as f32;
+
+        return self.scale(units, font_size)
+    }
+
+    fn scale(&self, units: f32, font_size: f32) -> f32 {
+        return units * font_size / self.units_per_em as f32
+    }
+}
+
+struct Tables<'a> {
+    data: &'a [u8],
+    offset: usize,
+    count: usize,
+}
+
+impl<'a> Tables<'a> {
+    fn find(&self, tag: &[u8; 4]) -> Option<&'a [u8]> {
+        for i in 0..self.count {
+            let record = self.offset + 12 + i * 16;
+
+            if read_tag(self.data, record)? == tag {
+                let offset = read_u32(self.data, record + 8)? as usize;
+                let length = read_u32(self.data, record + 12)? as usize;
+
+                return self.data.get(offset..offset.checked_add(length)?)
+            }
+        }
+
+        return None
+    }
+}
+
+// Prefers a Unicode subtable with the full repertoire (format 12) over one
+// for the Basic Multilingual Plane (format 4).
+fn parse_cmap(cmap: &[u8]) -> Vec<(u32, u32, u32)> {
+    let count = read_u16(cmap, 2).unwrap_or(0) as usize;
+    let mut best: Option<(u8, &[u8])> = None;
+
+    for i in 0..count {
+        let record = 4 + i * 8;
+
+        let (platform, encoding, offset) = match (read_u16(cmap, record), read_u16(cmap, record + 2), read_u32(cmap, record + 4)) {
+            (Some(p), Some(e), Some(o)) => (p, e, o as usize),
+            _ => break,
+        };
+
+        let rank = match (platform, encoding) {
+            (3, 10) | (0, 4) | (0, 6) => 2,
+            (3, 1) | (0, _) => 1,
+            _ => continue,
+        };
+
+        if let Some(subtable) = cmap.get(offset..) {
+            if best.map_or(true, |(r, _)| rank > r) {
+                best = Some((rank, subtable));
+            }
+        }
+    }
+
+    let subtable = match best {
+        Some((_, subtable)) => subtable,
+        None => return Vec::new(),
+    };
+
+    let mut runs = match read_u16(subtable, 0) {
+        Some(4) => parse_cmap_format_4(subtable),
+        Some(12) => parse_cmap_format_12(subtable),
+        _ => Vec::new(),
+    };
+
+    runs.sort_by_key(|&(start, _, _)| start);
+
+    return runs
+}
+
+fn parse_cmap_format_4(subtable: &[u8]) -> Vec<(u32, u32, u32)> {
+    let segments = read_u16(subtable, 6).unwrap_or(0) as usize / 2;
+    let ends = 14;
+    let starts = ends + segments * 2 + 2;
+    let deltas = starts + segments * 2;
+    let range_offsets = deltas + segments * 2;
+    let mut runs = Vec::new();
+
+    for i in 0..segments {
+        let (end, start, delta, range_offset) = match (
+            read_u16(subtable, ends + i * 2),
+            read_u16(subtable, starts + i * 2),
+            read_u16(subtable, deltas + i * 2),
+            read_u16(subtable, range_offsets + i * 2),
+        ) {
+            (Some(e), Some(s), Some(d), Some(r)) => (e as u32, s as u32, d as u32, r as usize),
+            _ => break,
+        };
+
+        if start > end || start == 0xffff {
+            continue
+        }
+
+        if range_offset == 0 {
+            // The glyph is the character plus the delta, modulo 65536, so a
+            // segment can wrap around to glyph 0 partway.
+            let first = (start + delta) & 0xffff;
+            let wrap = 0x10000 - first;
+
+            if end - start < wrap {
+                runs.push((start, end, first));
+            } else {
+                runs.push((start, start + wrap - 1, first));
+                runs.push((start + wrap, end, 0));
+            }
+
+            continue
+        }
+
+        // Otherwise each character has its own entry in the glyph array.
+        for c in start..=end {
+            let entry = range_offsets + i * 2 + range_offset + (c - start) as usize * 2;
+
+            let glyph = match read_u16(subtable, entry) {
+                Some(0) | None => continue,
+                Some(g) => (g as u32 + delta) & 0xffff,
+            };
+
+            runs.push((c, c, glyph));
+        }
+    }
+
+    return runs
+}
+
+fn parse_cmap_format_12(subtable: &[u8]) -> Vec<(u32, u32, u32)> {
+    let groups = read_u32(subtable, 12).unwrap_or(0) as usize;
+
+    return (0..groups)
+        .map_while(|i| {
+            let group = 16 + i * 12;
+
+            Some((read_u32(subtable, group)?, read_u32(subtable, group + 4)?, read_u32(subtable, group + 8)?))
+        })
+        .filter(|&(start, end, _)| start <= end && end <= 0x10ffff)
+        .collect()
+}
+
+// Prefers English names for Windows, then Unicode, then Macintosh.
+fn find_name(name: &[u8], id: u16) -> Option<String> {
+    let count = read_u16(name, 2)? as usize;
+    let strings = read_u16(name, 4)? as usize;
+    let mut best: Option<(u8, String)> = None;
+
+    for i in 0..count {
+        let record = 6 + i * 12;
+
+        if read_u16(name, record + 6)? != id {
+            continue
+        }
+
+        let platform = read_u16(name, record)?;
+        let language = read_u16(name, record + 4)?;
+        let length = read_u16(name, record + 8)? as usize;
+        let offset = strings + read_u16(name, record + 10)? as usize;
+
+        let bytes = match name.get(offset..offset + length) {
+            Some(bytes) => bytes,
+            None => continue,
+        };
+
+        let (rank, text) = match platform {
+            3 if language == 0x409 => (3, decode_utf16_be(bytes)),
+            3 => (2, decode_utf16_be(bytes)),
+            0 => (1, decode_utf16_be(bytes)),
+            // Mac Roman, which is ASCII for the names that matter.
+            1 => (0, bytes.iter().map(|&b| b as char).collect()),
+            _ => continue,
+        };
+
+        if best.as_ref().map_or(true, |&(r, _)| rank > r) {
+            best = Some((rank, text));
+        }
+    }
+
+    return best.map(|(_, text)| text)
+}
+
+fn decode_utf16_be(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes.chunks(2)
+        .filter(|c| c.len() == 2)
+        .map(|c| u16::from_be_bytes([c[0], c[1]]))
+        .collect();
+
+    return String::from_utf16_lossy(&units)
+}
+
+fn read_tag(data: &[u8], offset: usize) -> Option<&[u8; 4]> {
+    let bytes = data.get(offset..offset + 4)?;
+
+    return <&[u8; 4]>::try_from(bytes).ok()
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+
+    return Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_i16(data: &[u8], offset: usize) -> Option<i16> {
+    return read_u16(data, offset).map(|v| v as i16)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+
+    return Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collection_count_past_the_end_of_the_data_is_ignored() {
+        let mut data = vec![0; 64];
+
+        data[..4].copy_from_slice(b"ttcf");
+        data[8..12].copy_from_slice(&0xfffffff0u32.to_be_bytes());
+
+        assert!(Font::parse_collection(&data).is_empty());
+    }
+}
@@ -1,86 +1,724 @@
-use std::collections::HashMap;
+use std::collections::{
+    HashMap,
+    HashSet,
+};
 use std::{
+    borrow::Cow,
     fmt,
+    iter,
+    ptr,
+    slice,
     str
 };
 
+use crate::animation::Timeline;
 use crate::dom::{
     ElementData,
+    ElementId,
     Node,
     NodeType
 };
 use crate::css::{
+    AttrOperator,
+    AttributeSelector,
+    CalcNode,
+    CaseSensitivity,
+    Color,
+    ContainerRule,
+    ContentItem,
+    CssRule,
+    Declaration,
+    FontStyle,
+    ImportLayer,
+    KeyframesRule,
+    MediaCondition,
+    MediaFeature,
+    MediaQualifier,
+    MediaQuery,
+    MediaValue,
+    Nth,
+    Origin,
+    PseudoClass,
+    PseudoElement,
+    RangeOp,
+    RelativeSelector,
     Selector,
+    SimpleSelector,
     Stylesheet,
-    Value
+    SupportsCondition,
+    Token,
+    Unit,
+    Value,
+    serialize_tokens,
+};
+use crate::css_parser::{
+    is_custom_property,
+    parse_declaration_list,
+    parse_declaration_value,
+    parse_family_name,
+    parse_font_style,
+    parse_property,
+    parse_selector_list,
+    strip_important,
 };
+use crate::font::FontQuery;
+use crate::properties::{
+    self,
+    Shorthand,
+};
+use crate::user_agent;
+
+type PropertyMap = HashMap<String, Value>;
 
-type PropertyMap<'a> = HashMap<&'a str, &'a Value>;
+// HTML attributes whose values selectors compare case-insensitively unless
+// the `s` flag is given.
+const CASE_INSENSITIVE_ATTRIBUTES: &[&str] = &[
+    "accept", "accept-charset", "align", "alink", "axis", "bgcolor", "charset", "checked", "clear",
+    "codetype", "color", "compact", "declare", "defer", "dir", "direction", "disabled", "enctype",
+    "face", "frame", "hreflang", "http-equiv", "lang", "language", "link", "media", "method",
+    "multiple", "nohref", "noresize", "noshade", "nowrap", "readonly", "rel", "rev", "rules",
+    "scope", "scrolling", "selected", "shape", "target", "text", "type", "valign", "valuetype",
+    "vlink",
+];
 
 pub struct StyledNode<'a> {
     node: &'a Node,
-    styles: PropertyMap<'a>,
+    styles: PropertyMap,
+    // Set on the boxes ::before, ::after and ::marker generate, whose `node`
+    // is the element they belong to.
+    pub pseudo_element: Option<PseudoElement>,
+    // The text generated by `content`.
+    pub content: Option<String>,
+    // Pseudo-elements that style part of the element's content instead of
+    // generating a box, like ::first-line.
+    parts: Vec<StyledNode<'a>>,
     pub children: Vec<StyledNode<'a>>,
 }
 
+// The environment media queries are evaluated against.
+pub struct Viewport {
+    pub width: f32,
+    pub height: f32,
+    pub device_pixel_ratio: f32,
+    pub media_type: String,
+    pub color_scheme: ColorScheme,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum ColorScheme {
+    Light,
+    Dark,
+}
+
+// The content-box sizes of query containers from the last layout, by
+// element. Container queries are evaluated against these when the tree is
+// styled again.
+#[derive(PartialEq, Default, Debug)]
+pub struct ContainerSizes {
+    sizes: HashMap<ElementId, (f32, f32)>,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum ContainerType {
+    Normal,
+    Size,
+    InlineSize,
+}
+
+// What relative lengths are resolved against.
+#[derive(Clone, Copy, Debug)]
+pub struct LengthContext {
+    pub percent_base: f32,
+    pub font_size: f32,
+    pub root_font_size: f32,
+    pub viewport_width: f32,
+    pub viewport_height: f32,
+}
+
+// A style rule that applies in the current environment, along with where it
+// came from.
+struct CollectedRule<'a> {
+    // Nested rules have their `&` replaced, so own their selectors.
+    selectors: Cow<'a, [Selector]>,
+    declarations: &'a [Declaration],
+    origin: Origin,
+    layer: Vec<String>,
+    // Position of `layer` in the layer order; unlayered rules come last.
+    layer_order: usize,
+    // The @container rules the rule is in, which all have to match.
+    containers: Vec<&'a ContainerRule>,
+}
+
+// Gathers the applicable rules of one origin and the order of its layers.
+struct RuleCollector<'a> {
+    origin: Origin,
+    rules: Vec<CollectedRule<'a>>,
+    // @keyframes rules and the layer each is in.
+    keyframes: Vec<(&'a KeyframesRule, Vec<String>)>,
+    layers: LayerNode,
+    anonymous_layers: usize,
+}
+
+// The tree of declared layers. Children are kept in declaration order.
+struct LayerNode {
+    name: String,
+    children: Vec<LayerNode>,
+}
+
+// An element along with where it sits in the tree, so selectors can look at
+// its ancestors and earlier siblings.
+#[derive(Clone, Copy)]
+struct ElementRef<'a> {
+    element: &'a ElementData,
+    parent: Option<&'a ElementRef<'a>>,
+    // The children of the parent, and this element's index among them.
+    siblings: &'a [Node],
+    index: usize,
+}
+
+// The @keyframes rules in effect, by name, and the timeline animations and
+// transitions run on.
+struct Animations<'a, 't> {
+    keyframes: HashMap<&'a str, &'a KeyframesRule>,
+    timeline: &'t mut Timeline,
+}
+
+// An ancestor that container queries can be evaluated against.
+struct QueryContainer {
+    names: Vec<String>,
+    container_type: ContainerType,
+    // The content box from the last layout, if there's been one.
+    size: Option<(f32, f32)>,
+    // Font-relative lengths in queries use the container's font size.
+    context: LengthContext,
+}
+
+// The containers around the element being styled, innermost last.
+struct Containers<'s> {
+    sizes: &'s ContainerSizes,
+    stack: Vec<QueryContainer>,
+}
+
+// What media queries and container queries are evaluated against.
+trait QueryEnvironment {
+    // The current value of a feature: a number for range features, an
+    // identifier for discrete ones.
+    fn feature_value(&self, name: &str) -> Option<MediaValue>;
+
+    // Converts a value in a query to the units `feature_value` uses for the
+    // feature.
+    fn value_number(&self, name: &str, value: &MediaValue) -> Option<f32>;
+}
+
+// Counters and quote nesting, which carry across the tree in document order.
+#[derive(Default)]
+struct GeneratedContent {
+    // The instances of each counter in scope, innermost last.
+    counters: HashMap<String, Vec<i32>>,
+    // Every instance created, in order, so they can go out of scope once the
+    // parent of the element that created them is done.
+    created: Vec<String>,
+    quote_depth: usize,
+}
+
+// Resolves the custom properties of one element on demand, so var()
+// references can be followed and cycles noticed.
+struct VariableResolver<'a, 'b> {
+    specified: &'b HashMap<&'a str, &'a Value>,
+    parent: Option<&'b PropertyMap>,
+    // `None` for properties that are invalid at computed-value time.
+    resolved: HashMap<String, Option<Vec<Token>>>,
+    resolving: Vec<String>,
+}
+
+// Where a declaration sits in the cascade, from lowest to highest precedence.
+// Important declarations reverse the order of origins.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+enum CascadeLevel {
+    UserAgentNormal,
+    UserNormal,
+    AuthorNormal,
+    AuthorImportant,
+    UserImportant,
+    UserAgentImportant,
+}
+
 pub enum Display {
     Block,
     Inline,
     InlineBlock,
+    ListItem,
+    Table,
+    InlineTable,
+    TableRowGroup,
+    TableHeaderGroup,
+    TableFooterGroup,
+    TableRow,
+    TableCell,
+    TableColumnGroup,
+    TableColumn,
+    TableCaption,
     None,
 }
 
 
 impl<'a> StyledNode<'a> {
-    pub fn new(node: &'a Node, stylesheet: &'a Stylesheet) -> StyledNode<'a> {
+    pub fn new(node: &'a Node, stylesheet: &'a Stylesheet, viewport: &Viewport) -> StyledNode<'a> {
+        return StyledNode::with_timeline(node, stylesheet, viewport, &mut Timeline::new())
+    }
+
+    // Styles the tree at the timeline's current time. Styling the same tree
+    // again with the same timeline starts transitions for the values that
+    // changed in between, and keeps animations running from where they
+    // started. Elements that are no longer in the tree lose theirs.
+    pub fn with_timeline(
+        node: &'a Node,
+        stylesheet: &'a Stylesheet,
+        viewport: &Viewport,
+        timeline: &mut Timeline,
+    ) -> StyledNode<'a> {
+        return StyledNode::with_containers(node, &[stylesheet], viewport, timeline, &ContainerSizes::new())
+    }
+
+    // Styles the tree with the user agent stylesheet and every stylesheet in
+    // `stylesheets`, in order, and container queries evaluated against
+    // `sizes`, the container sizes from laying out an earlier styling of it.
+    // Containers not in `sizes` match no queries.
+    pub fn with_containers(
+        node: &'a Node,
+        stylesheets: &[&'a Stylesheet],
+        viewport: &Viewport,
+        timeline: &mut Timeline,
+        sizes: &ContainerSizes,
+    ) -> StyledNode<'a> {
+        // The stylesheets of an origin share one set of layers.
+        let mut collectors: Vec<RuleCollector> = Vec::new();
+
+        for stylesheet in iter::once(user_agent::stylesheet()).chain(stylesheets.iter().cloned()) {
+            let index = match collectors.iter().position(|c| c.origin == stylesheet.origin) {
+                Some(i) => i,
+                None => {
+                    collectors.push(RuleCollector::new(stylesheet.origin));
+                    collectors.len() - 1
+                },
+            };
+
+            collect_rules(&stylesheet.rules, &[], None, &[], viewport, &mut collectors[index]);
+        }
+
+        // @keyframes rules of later origins win.
+        collectors.sort_by_key(|c| CascadeLevel::new(c.origin, false));
+
+        let mut rules = Vec::new();
+        let mut keyframes = HashMap::new();
+
+        for collector in collectors {
+            let (origin_rules, origin_keyframes) = collector.finish();
+
+            rules.extend(origin_rules);
+            keyframes.extend(origin_keyframes);
+        }
+
+        let element = match node.node_type {
+            NodeType::Element(ref e) => Some(ElementRef {
+                element: e,
+                parent: None,
+                siblings: slice::from_ref(node),
+                index: 0,
+            }),
+            _ => None,
+        };
+
+        let mut generated = GeneratedContent::default();
+        let mut animations = Animations { keyframes, timeline };
+        let mut containers = Containers { sizes, stack: Vec::new() };
+
+        let styled = StyledNode::build(
+            node,
+            element.as_ref(),
+            &rules,
+            None,
+            LengthContext::new(viewport),
+            &mut generated,
+            &mut animations,
+            &mut containers,
+        );
+
+        animations.timeline.finish();
+
+        return styled
+    }
+
+    fn build(
+        node: &'a Node,
+        element: Option<&ElementRef>,
+        rules: &[CollectedRule],
+        parent: Option<&PropertyMap>,
+        context: LengthContext,
+        generated: &mut GeneratedContent,
+        animations: &mut Animations,
+        containers: &mut Containers,
+    ) -> StyledNode<'a> {
+        let styles = match element {
+            Some(e) => StyledNode::get_styles(e, rules, parent, &context, &containers.stack, None, Some(&mut *animations)),
+            None => PropertyMap::new(),
+        };
+
+        // rem lengths are relative to the root element's font size.
+        let context = match parent {
+            Some(_) => context,
+            None => LengthContext { root_font_size: font_size(Some(&styles)), ..context },
+        };
+
+        // The element is a container for its descendants and its own
+        // pseudo-elements.
+        let names = container_names(&styles);
+        let container = container_type(&styles) != ContainerType::Normal || !names.is_empty();
+
+        if container {
+            containers.stack.push(QueryContainer {
+                names,
+                container_type: container_type(&styles),
+                size: element.and_then(|e| containers.sizes.sizes.get(&e.element.id())).cloned(),
+                context: LengthContext { font_size: font_size(Some(&styles)), ..context },
+            });
+        }
+
         let mut style_children = Vec::new();
 
-        for child in &node.children {
+        if element.is_some() {
+            generated.update_counters(&styles, is_list_item(&styles));
+        }
+
+        // Counters created by the children are in scope until the end of
+        // this element.
+        let scope = generated.created.len();
+
+        let mut parts = Vec::new();
+
+        if let Some(e) = element {
+            for &pseudo_element in &[PseudoElement::Marker, PseudoElement::Before] {
+                style_children.extend(StyledNode::build_pseudo_element(
+                    node,
+                    e,
+                    pseudo_element,
+                    rules,
+                    &styles,
+                    &context,
+                    &containers.stack,
+                    generated,
+                ));
+            }
+
+            style_children.extend(StyledNode::build_placeholder(node, e, rules, &styles, &context, &containers.stack));
+
+            // ::first-letter inherits from ::first-line, since the first
+            // letter is on the first line.
+            if has_pseudo_element_rules(e, rules, PseudoElement::FirstLine) {
+                let first_line = StyledNode::get_styles(e, rules, Some(&styles), &context, &containers.stack, Some(PseudoElement::FirstLine), None);
+
+                parts.push(StyledNode::new_part(node, PseudoElement::FirstLine, first_line));
+            }
+
+            if has_pseudo_element_rules(e, rules, PseudoElement::FirstLetter) {
+                let parent = parts.first().map_or(&styles, |p: &StyledNode| &p.styles);
+                let first_letter = StyledNode::get_styles(e, rules, Some(parent), &context, &containers.stack, Some(PseudoElement::FirstLetter), None);
+
+                parts.push(StyledNode::new_part(node, PseudoElement::FirstLetter, first_letter));
+            }
+        }
+
+        for (index, child) in node.children.iter().enumerate() {
             match child.node_type {
-                NodeType::Element(_) => style_children.push(StyledNode::new(&child, stylesheet)),
-                _ => {},
+                NodeType::Element(ref e) => {
+                    let child_element = ElementRef {
+                        element: e,
+                        parent: element,
+                        siblings: &node.children,
+                        index,
+                    };
+
+                    style_children.push(StyledNode::build(
+                        &child,
+                        Some(&child_element),
+                        rules,
+                        Some(&styles),
+                        context,
+                        generated,
+                        animations,
+                        containers,
+                    ));
+                },
+                // Text only has the properties it inherits.
+                NodeType::Text(_) => style_children.push(StyledNode {
+                    node: child,
+                    styles: compute_values(&HashMap::new(), Some(&styles), &context),
+                    pseudo_element: None,
+                    content: None,
+                    parts: Vec::new(),
+                    children: Vec::new(),
+                }),
+                NodeType::Comment(_) => {},
             }
         }
 
+        if let Some(e) = element {
+            style_children.extend(StyledNode::build_pseudo_element(
+                node,
+                e,
+                PseudoElement::After,
+                rules,
+                &styles,
+                &context,
+                &containers.stack,
+                generated,
+            ));
+        }
+
+        if container {
+            containers.stack.pop();
+        }
+
+        generated.end_scope(scope);
+
         return StyledNode {
             node,
-            styles: match node.node_type {
-                NodeType::Element(ref e) => StyledNode::get_styles(e, stylesheet),
-                _ => PropertyMap::new(),
-            },
+            styles,
+            pseudo_element: None,
+            content: None,
+            parts,
             children: style_children,
         }
     }
 
-    fn get_styles(element: &'a ElementData, stylesheet: &'a Stylesheet) -> PropertyMap<'a> {
-        let mut styles = PropertyMap::new();
+    fn new_part(node: &'a Node, pseudo_element: PseudoElement, styles: PropertyMap) -> StyledNode<'a> {
+        return StyledNode {
+            node,
+            styles,
+            pseudo_element: Some(pseudo_element),
+            content: None,
+            parts: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    // The box for the placeholder text of an empty <input> or <textarea>.
+    fn build_placeholder(
+        node: &'a Node,
+        element: &ElementRef,
+        rules: &[CollectedRule],
+        styles: &PropertyMap,
+        context: &LengthContext,
+        containers: &[QueryContainer],
+    ) -> Option<StyledNode<'a>> {
+        let e = element.element;
+        let empty = match e.tag_name.as_ref() {
+            "input" => e.get_attr("value").map_or(true, |v| v.is_empty()),
+            "textarea" => node.children.is_empty(),
+            _ => false,
+        };
+        let placeholder = e.get_attr("placeholder").filter(|p| empty && !p.is_empty())?;
 
-        for rule in &stylesheet.rules {
-            for selector in &rule.selectors {
-                if selector_matches(element, &selector) {
-                    for decl in &rule.declarations {
-                        styles.insert(&decl.property, &decl.value);
-                    }
-                    
-                    break;
+        let pseudo = StyledNode::get_styles(element, rules, Some(styles), context, containers, Some(PseudoElement::Placeholder), None);
+
+        return Some(StyledNode {
+            node,
+            styles: pseudo,
+            pseudo_element: Some(PseudoElement::Placeholder),
+            content: Some(placeholder.clone()),
+            parts: Vec::new(),
+            children: Vec::new(),
+        })
+    }
+
+    // The box for ::before, ::after or ::marker, if the element has one.
+    fn build_pseudo_element(
+        node: &'a Node,
+        element: &ElementRef,
+        pseudo_element: PseudoElement,
+        rules: &[CollectedRule],
+        styles: &PropertyMap,
+        context: &LengthContext,
+        containers: &[QueryContainer],
+        generated: &mut GeneratedContent,
+    ) -> Option<StyledNode<'a>> {
+        // List items get a marker even without a ::marker rule.
+        let list_item = pseudo_element == PseudoElement::Marker && is_list_item(styles);
+
+        if !list_item && !(pseudo_element != PseudoElement::Marker && has_pseudo_element_rules(element, rules, pseudo_element)) {
+            return None
+        }
+
+        let pseudo = StyledNode::get_styles(element, rules, Some(styles), context, containers, Some(pseudo_element), None);
+
+        if let Some(&Value::Other(ref display)) = pseudo.get("display") {
+            if display == "none" {
+                return None
+            }
+        }
+
+        generated.update_counters(&pseudo, false);
+
+        let content = match pseudo.get("content") {
+            Some(&Value::Content(ref items)) => generated.render(items, element.element, &pseudo),
+            // `normal` means no box for ::before and ::after, and the
+            // list-style-type marker for ::marker.
+            _ if list_item => marker_text(styles.get("list-style-type"), generated.counter("list-item"))?,
+            _ => return None,
+        };
+
+        return Some(StyledNode {
+            node,
+            styles: pseudo,
+            pseudo_element: Some(pseudo_element),
+            content: Some(content),
+            parts: Vec::new(),
+            children: Vec::new(),
+        })
+    }
+
+    fn get_styles(
+        element: &ElementRef,
+        rules: &[CollectedRule],
+        parent: Option<&PropertyMap>,
+        context: &LengthContext,
+        containers: &[QueryContainer],
+        pseudo_element: Option<PseudoElement>,
+        animations: Option<&mut Animations>,
+    ) -> PropertyMap {
+        // The style attribute only styles the element itself.
+        let inline = match pseudo_element {
+            None => element.element.get_attr("style").map(|s| parse_declaration_list(s)).unwrap_or_default(),
+            Some(_) => Vec::new(),
+        };
+
+        let mut specified = HashMap::new();
+        let mut matched = Vec::new();
+
+        for collected in rules {
+            if !collected.containers.iter().all(|c| container_rule_matches(c, containers)) {
+                continue
+            }
+
+            // A rule applies with the specificity of its most specific
+            // matching selector.
+            let specificity = collected.selectors
+                .iter()
+                .filter(|s| s.pseudo_element == pseudo_element && selector_matches(element, s))
+                .map(Selector::specificity)
+                .max();
+
+            if let Some(specificity) = specificity {
+                for decl in collected.declarations {
+                    // Important declarations in earlier layers win.
+                    let layer_order = match decl.important {
+                        true => usize::MAX - collected.layer_order,
+                        false => collected.layer_order,
+                    };
+
+                    matched.push((CascadeLevel::new(collected.origin, decl.important), false, layer_order, specificity, decl));
                 }
             }
         };
 
-        return styles;
+        // Style attribute declarations are author declarations that win over
+        // any rule of the same importance, whatever its layer or specificity.
+        for decl in &inline {
+            matched.push((CascadeLevel::new(Origin::Author, decl.important), true, 0, (0, 0, 0), decl));
+        }
+
+        // The sort is stable, so later declarations still win otherwise.
+        matched.sort_by_key(|&(level, inline, layer_order, specificity, _)| (level, inline, layer_order, specificity));
+
+        let important: HashSet<&str> = matched.iter().filter(|m| m.4.important).map(|m| m.4.property.as_str()).collect();
+
+        // Going from the highest precedence down, the first declaration of
+        // each property wins. `revert` rolls the property back to the
+        // declarations of the origins below its own.
+        let mut reverted: HashMap<&str, Origin> = HashMap::new();
+
+        for &(level, _, _, _, decl) in matched.iter().rev() {
+            // A shorthand that uses var() sets each of its longhands, and is
+            // kept too so they can be expanded from it once it's substituted.
+            let names = match properties::lookup_shorthand(&decl.property) {
+                Some(shorthand) => {
+                    specified.entry(shorthand.name).or_insert(&decl.value);
+
+                    shorthand.longhands.to_vec()
+                },
+                None => vec![decl.property.as_str()],
+            };
+
+            for name in names {
+                if specified.contains_key(name) || reverted.get(name).map_or(false, |&r| level.origin() >= r) {
+                    continue
+                }
+
+                match decl.value {
+                    Value::Other(ref keyword) if keyword == "revert" => {
+                        reverted.insert(name, level.origin());
+                    },
+                    ref value => {
+                        specified.insert(name, value);
+                    },
+                }
+            }
+        }
+
+        let mut styles = compute_values(&specified, parent, context);
+
+        if let Some(animations) = animations {
+            // Keyframe values are computed like a declaration on the element.
+            let compute = |name: &str, value: &Value| {
+                let mut specified = HashMap::new();
+
+                specified.insert(name, value);
+
+                compute_values(&specified, parent, context).remove(name)
+            };
+
+            animations.timeline.update(element.element.id(), &mut styles, &animations.keyframes, &important, &compute);
+        }
+
+        return styles
     }
 
-    pub fn value(&self, name: &str) -> Option<&&Value> {
+    pub fn value(&self, name: &str) -> Option<&Value> {
         return self.styles.get(name)
     }
 
+    // The text of a text node, or the text a pseudo-element generates.
+    pub fn text(&self) -> Option<&str> {
+        return match self.node.node_type {
+            NodeType::Text(ref text) if self.pseudo_element.is_none() => Some(text),
+            _ => self.content.as_deref(),
+        }
+    }
+
+    // The styles of ::first-line or ::first-letter, if any rules apply to
+    // them.
+    pub fn part(&self, pseudo_element: PseudoElement) -> Option<&StyledNode<'a>> {
+        return self.parts.iter().find(|p| p.pseudo_element == Some(pseudo_element))
+    }
+
     pub fn get_display(&self) -> Display {
         return match self.value("display") {
-            Some(s) => match **s {
+            Some(s) => match *s {
                 Value::Other(ref v) => match v.as_ref() {
                     "block" => Display::Block,
                     "none" => Display::None,
                     "inline-block" => Display::InlineBlock,
+                    "list-item" => Display::ListItem,
+                    "table" => Display::Table,
+                    "inline-table" => Display::InlineTable,
+                    "table-row-group" => Display::TableRowGroup,
+                    "table-header-group" => Display::TableHeaderGroup,
+                    "table-footer-group" => Display::TableFooterGroup,
+                    "table-row" => Display::TableRow,
+                    "table-cell" => Display::TableCell,
+                    "table-column-group" => Display::TableColumnGroup,
+                    "table-column" => Display::TableColumn,
+                    "table-caption" => Display::TableCaption,
                     _ => Display::Inline,
                 },
                 _ => Display::Inline,
@@ -89,9 +727,55 @@ impl<'a> StyledNode<'a> {
         }
     }
 
+    pub fn color(&self, name: &str) -> Option<Color> {
+        return match self.value(name) {
+            Some(v) => match *v {
+                Value::Color(ref c) => Some(c.clone()),
+                Value::Other(ref o) if o == "currentcolor" && name != "color" => self.color("color"),
+                _ => None,
+            },
+            None => None,
+        }
+    }
+
+    // The computed font size in px.
+    pub fn font_size(&self) -> f32 {
+        return font_size(Some(&self.styles))
+    }
+
+    pub fn container_type(&self) -> ContainerType {
+        return container_type(&self.styles)
+    }
+
+    // The families to look for fonts in, in order, and what the font should
+    // be like, for FontDatabase::match_font.
+    pub fn font_query(&self) -> (Vec<String>, FontQuery) {
+        let families = match self.value("font-family") {
+            Some(&Value::List(ref families)) => families.iter().filter_map(|f| match *f {
+                Value::Other(ref name) => parse_family_name(name),
+                _ => None,
+            }).collect(),
+            _ => Vec::new(),
+        };
+
+        let query = FontQuery {
+            weight: font_weight(Some(&self.styles)),
+            style: match self.value("font-style") {
+                Some(&Value::Other(ref style)) => parse_font_style(style).unwrap_or(FontStyle::Normal),
+                _ => FontStyle::Normal,
+            },
+            stretch: match self.value("font-stretch") {
+                Some(&Value::Length(n, Unit::Pct)) => n,
+                _ => 100.0,
+            },
+        };
+
+        return (families, query)
+    }
+
     pub fn num_or(&self, name: &str, default: f32) -> f32 {
         return match self.value(name) {
-            Some(v) => match **v {
+            Some(v) => match *v {
                 Value::Length(n, _) => n,
                 _ => default,
             },
@@ -100,58 +784,1574 @@ impl<'a> StyledNode<'a> {
     }
 }
 
-impl<'a> fmt::Debug for StyledNode<'a> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        return write!(f, "{:?}: {:?}", self.node, self.styles)
+impl Default for Viewport {
+    fn default() -> Self {
+        return Viewport {
+            width: 1024.0,
+            height: 768.0,
+            device_pixel_ratio: 1.0,
+            media_type: String::from("screen"),
+            color_scheme: ColorScheme::Light,
+        }
     }
 }
 
-fn selector_matches(element: &ElementData, selector: &Selector) -> bool {
-    for simple in &selector.simple {
-        let mut selector_match = true;
+impl ContainerSizes {
+    pub fn new() -> Self {
+        return ContainerSizes::default()
+    }
 
-        match simple.tag_name {
-            Some(ref t) => if *t != element.tag_name {
-                continue;
-            },
-            None => {},
+    // Records the size of the content box of a container's box.
+    pub fn insert(&mut self, container: &StyledNode, width: f32, height: f32) {
+        if let NodeType::Element(ref element) = container.node.node_type {
+            self.sizes.insert(element.id(), (width, height));
+        }
+    }
+}
+
+impl<'a> ElementRef<'a> {
+    fn node(&self) -> &'a Node {
+        return &self.siblings[self.index]
+    }
+
+    fn previous_sibling(&self) -> Option<ElementRef<'a>> {
+        return self.sibling_elements().into_iter().rev().find(|s| s.index < self.index)
+    }
+
+    // Every element among the parent's children, including this one.
+    fn sibling_elements(&self) -> Vec<ElementRef<'a>> {
+        return self.siblings.iter().enumerate().filter_map(|(index, node)| match node.node_type {
+            NodeType::Element(ref e) => Some(ElementRef {
+                element: e,
+                parent: self.parent,
+                siblings: self.siblings,
+                index,
+            }),
+            _ => None,
+        }).collect()
+    }
+
+    fn is(&self, other: &ElementRef) -> bool {
+        return ptr::eq(self.element, other.element)
+    }
+}
+
+impl GeneratedContent {
+    fn instantiate(&mut self, name: &str, value: i32) {
+        self.counters.entry(name.to_string()).or_insert_with(Vec::new).push(value);
+        self.created.push(name.to_string());
+    }
+
+    // Applies counter-reset, then counter-increment, then counter-set. List
+    // items also increment `list-item` unless told otherwise.
+    fn update_counters(&mut self, styles: &PropertyMap, list_item: bool) {
+        let counters = |name| match styles.get(name) {
+            Some(&Value::Counters(ref c)) => c.clone(),
+            _ => Vec::new(),
+        };
+
+        for (name, value) in counters("counter-reset") {
+            self.instantiate(&name, value);
+        }
+
+        let mut increments = counters("counter-increment");
+
+        if list_item && !increments.iter().any(|&(ref name, _)| name == "list-item") {
+            increments.push((String::from("list-item"), 1));
+        }
+
+        for (name, value) in increments {
+            if let Some(counter) = self.current(&name) {
+                *counter += value;
+            }
+        }
+
+        for (name, value) in counters("counter-set") {
+            if let Some(counter) = self.current(&name) {
+                *counter = value;
+            }
+        }
+    }
+
+    // The innermost instance of a counter, created if there's none in scope.
+    fn current(&mut self, name: &str) -> Option<&mut i32> {
+        if self.counters.get(name).map_or(true, Vec::is_empty) {
+            self.instantiate(name, 0);
+        }
+
+        return self.counters.get_mut(name).and_then(|c| c.last_mut())
+    }
+
+    fn counter(&self, name: &str) -> i32 {
+        return self.counters.get(name).and_then(|c| c.last()).cloned().unwrap_or(0)
+    }
+
+    // Removes the counter instances created after `scope`.
+    fn end_scope(&mut self, scope: usize) {
+        for name in self.created.split_off(scope) {
+            self.counters.get_mut(&name).map(Vec::pop);
+        }
+    }
+
+    fn render(&mut self, items: &[ContentItem], element: &ElementData, styles: &PropertyMap) -> String {
+        let quotes = match styles.get("quotes") {
+            Some(&Value::Quotes(ref q)) => q.clone(),
+            Some(&Value::Other(ref none)) if none == "none" => Vec::new(),
+            _ => vec![
+                (String::from("\u{201c}"), String::from("\u{201d}")),
+                (String::from("\u{2018}"), String::from("\u{2019}")),
+            ],
         };
 
-        match element.get_id() {
-            Some(i) => match simple.id {
-                Some(ref id) => if *i != *id {
-                    continue;
+        // Deeper nesting than the list covers reuses the last pair.
+        let quote = |depth: usize| quotes.get(depth.min(quotes.len().saturating_sub(1)));
+
+        let mut text = String::new();
+
+        for item in items {
+            match *item {
+                ContentItem::String(ref s) => text.push_str(s),
+                ContentItem::Attr(ref name) => text.push_str(element.get_attr(name).map_or("", String::as_str)),
+                ContentItem::Counter(ref name, ref style) => text.push_str(&format_counter(self.counter(name), style)),
+                ContentItem::Counters(ref name, ref separator, ref style) => {
+                    let instances: Vec<String> = match self.counters.get(name) {
+                        Some(values) if !values.is_empty() => values.iter().map(|&v| format_counter(v, style)).collect(),
+                        _ => vec![format_counter(0, style)],
+                    };
+
+                    text.push_str(&instances.join(separator));
                 },
-                None => {},
-            },
-            None => match simple.id {
-                Some(_) => {
-                    continue;
+                ContentItem::OpenQuote => {
+                    text.push_str(quote(self.quote_depth).map_or("", |q| q.0.as_str()));
+
+                    self.quote_depth += 1;
                 },
-                _ => {},
+                ContentItem::CloseQuote => if self.quote_depth > 0 {
+                    self.quote_depth -= 1;
+
+                    text.push_str(quote(self.quote_depth).map_or("", |q| q.1.as_str()));
+                },
+                ContentItem::NoOpenQuote => self.quote_depth += 1,
+                ContentItem::NoCloseQuote => self.quote_depth = self.quote_depth.saturating_sub(1),
             }
-        };
+        }
 
-        let element_classes = element.get_classes();
+        return text
+    }
+}
 
-        for class in &simple.classes {
-            selector_match &= element_classes.contains::<str>(class);
+impl LengthContext {
+    pub fn new(viewport: &Viewport) -> Self {
+        return LengthContext {
+            percent_base: 0.0,
+            font_size: 16.0,
+            root_font_size: 16.0,
+            viewport_width: viewport.width,
+            viewport_height: viewport.height,
         }
+    }
+}
 
-        if selector_match {
-            return true;
+impl<'a> RuleCollector<'a> {
+    fn new(origin: Origin) -> Self {
+        return RuleCollector {
+            origin,
+            rules: Vec::new(),
+            keyframes: Vec::new(),
+            layers: LayerNode::new(String::new()),
+            anonymous_layers: 0,
         }
-    };
+    }
+
+    // Returns the full path of a layer named `name` inside `parent`, declaring
+    // it if this is the first time it's seen.
+    fn declare_layer(&mut self, parent: &[String], name: Option<&str>) -> Vec<String> {
+        let mut path = parent.to_vec();
+
+        match name {
+            Some(n) => path.extend(n.split('.').map(String::from)),
+            None => {
+                // Anonymous layers can't be referred to again, so give each a
+                // name no stylesheet can use.
+                self.anonymous_layers += 1;
+                path.push(format!(" anonymous {}", self.anonymous_layers));
+            },
+        }
+
+        let mut node = &mut self.layers;
+
+        for part in &path {
+            let index = match node.children.iter().position(|c| c.name == *part) {
+                Some(i) => i,
+                None => {
+                    node.children.push(LayerNode::new(part.clone()));
+                    node.children.len() - 1
+                },
+            };
+
+            node = &mut node.children[index];
+        }
+
+        return path
+    }
 
-    return false;
+    // The rules with their layer order, and the @keyframes rule for each
+    // name. Of several with the same name, the one in the last layer wins,
+    // then the last one.
+    fn finish(mut self) -> (Vec<CollectedRule<'a>>, HashMap<&'a str, &'a KeyframesRule>) {
+        let mut order = HashMap::new();
+
+        self.layers.assign_order(&mut Vec::new(), &mut order);
+
+        for rule in &mut self.rules {
+            rule.layer_order = order[&rule.layer];
+        }
+
+        self.keyframes.sort_by_key(|&(_, ref layer)| order[layer]);
+
+        let keyframes = self.keyframes.iter().map(|&(rule, _)| (rule.name.as_str(), rule)).collect();
+
+        return (self.rules, keyframes)
+    }
 }
 
-pub fn pretty_print(n: &Node, indent_size: usize) {
-    let indent = (0..indent_size).map(|_| " ").collect::<String>();
+impl LayerNode {
+    fn new(name: String) -> Self {
+        return LayerNode {
+            name,
+            children: Vec::new(),
+        }
+    }
 
-    println!("{}{:?}", indent, n);
+    // Sub-layers come before the rules directly in their parent layer, so
+    // the order is a post-order walk of the tree.
+    fn assign_order(&self, path: &mut Vec<String>, order: &mut HashMap<Vec<String>, usize>) {
+        for child in &self.children {
+            path.push(child.name.clone());
+            child.assign_order(path, order);
+            path.pop();
+        }
 
-    for child in n.children.iter() {
-        pretty_print(&child, indent_size + 4);
+        let next = order.len();
+
+        order.insert(path.clone(), next);
     }
-}
\ No newline at end of file
+}
+
+impl<'a, 'b> VariableResolver<'a, 'b> {
+    fn new(specified: &'b HashMap<&'a str, &'a Value>, parent: Option<&'b PropertyMap>) -> Self {
+        return VariableResolver {
+            specified,
+            parent,
+            resolved: HashMap::new(),
+            resolving: Vec::new(),
+        }
+    }
+
+    fn resolve(&mut self, name: &str) -> Option<Vec<Token>> {
+        if let Some(tokens) = self.resolved.get(name) {
+            return tokens.clone()
+        }
+
+        // Every property in a cycle is invalid at computed-value time.
+        if let Some(start) = self.resolving.iter().position(|n| n == name) {
+            for n in &self.resolving[start..] {
+                self.resolved.insert(n.clone(), None);
+            }
+
+            return None
+        }
+
+        let specified = self.specified;
+
+        let tokens = match specified.get(name) {
+            Some(&&Value::Tokens(ref tokens)) => tokens,
+            // Custom properties have no initial value, and inherit otherwise.
+            Some(&&Value::Other(ref keyword)) if keyword == "initial" => return None,
+            _ => return self.inherited(name),
+        };
+
+        self.resolving.push(name.to_string());
+
+        let substituted = self.substitute(tokens);
+
+        self.resolving.pop();
+
+        let result = match self.resolved.get(name) {
+            Some(_) => None,
+            None => substituted,
+        };
+
+        self.resolved.insert(name.to_string(), result.clone());
+
+        return result
+    }
+
+    fn inherited(&self, name: &str) -> Option<Vec<Token>> {
+        return match self.parent.and_then(|p| p.get(name)) {
+            Some(&Value::Tokens(ref tokens)) => Some(tokens.clone()),
+            _ => None,
+        }
+    }
+
+    // Replaces every var() in `tokens`, or returns `None` if one refers to an
+    // invalid property and has no fallback.
+    fn substitute(&mut self, tokens: &[Token]) -> Option<Vec<Token>> {
+        let mut result = Vec::new();
+        let mut i = 0;
+
+        while i < tokens.len() {
+            match tokens[i] {
+                Token::Function(ref name) if name == "var" => {
+                    let close = closing_paren(tokens, i);
+
+                    result.extend(self.substitute_var(&tokens[i + 1..close])?);
+
+                    i = close + 1;
+                },
+                ref token => {
+                    result.push(token.clone());
+
+                    i += 1;
+                },
+            }
+        }
+
+        return Some(result)
+    }
+
+    // var(<custom-property-name>, <fallback>?)
+    fn substitute_var(&mut self, args: &[Token]) -> Option<Vec<Token>> {
+        let mut args = args.iter().skip_while(|t| **t == Token::Whitespace);
+
+        let name = match args.next() {
+            Some(&Token::Ident(ref name)) if is_custom_property(name) => name,
+            _ => return None,
+        };
+
+        let rest: Vec<Token> = args.skip_while(|t| **t == Token::Whitespace).cloned().collect();
+
+        let fallback = match rest.split_first() {
+            Some((&Token::Comma, fallback)) => Some(fallback),
+            Some(_) => return None,
+            None => None,
+        };
+
+        return match self.resolve(name) {
+            Some(tokens) => Some(tokens),
+            None => self.substitute(fallback?),
+        }
+    }
+}
+
+impl CascadeLevel {
+    fn new(origin: Origin, important: bool) -> Self {
+        return match (origin, important) {
+            (Origin::UserAgent, false) => CascadeLevel::UserAgentNormal,
+            (Origin::User, false) => CascadeLevel::UserNormal,
+            (Origin::Author, false) => CascadeLevel::AuthorNormal,
+            (Origin::Author, true) => CascadeLevel::AuthorImportant,
+            (Origin::User, true) => CascadeLevel::UserImportant,
+            (Origin::UserAgent, true) => CascadeLevel::UserAgentImportant,
+        }
+    }
+
+    fn origin(self) -> Origin {
+        return match self {
+            CascadeLevel::UserAgentNormal | CascadeLevel::UserAgentImportant => Origin::UserAgent,
+            CascadeLevel::UserNormal | CascadeLevel::UserImportant => Origin::User,
+            CascadeLevel::AuthorNormal | CascadeLevel::AuthorImportant => Origin::Author,
+        }
+    }
+}
+
+impl<'a> fmt::Debug for StyledNode<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self.pseudo_element {
+            Some(p) => write!(f, "{:?}{:?} {:?}: {:?}", self.node, p, self.content, self.styles),
+            None => write!(f, "{:?}: {:?}", self.node, self.styles),
+        }
+    }
+}
+
+fn container_type(styles: &PropertyMap) -> ContainerType {
+    return match styles.get("container-type") {
+        Some(&Value::Other(ref t)) => match t.as_ref() {
+            "size" => ContainerType::Size,
+            "inline-size" => ContainerType::InlineSize,
+            _ => ContainerType::Normal,
+        },
+        _ => ContainerType::Normal,
+    }
+}
+
+fn container_names(styles: &PropertyMap) -> Vec<String> {
+    return match styles.get("container-name") {
+        Some(&Value::Other(ref names)) if names != "none" => names.split_whitespace().map(String::from).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn is_list_item(styles: &PropertyMap) -> bool {
+    return match styles.get("display") {
+        Some(&Value::Other(ref display)) => display == "list-item",
+        _ => false,
+    }
+}
+
+fn has_pseudo_element_rules(element: &ElementRef, rules: &[CollectedRule], pseudo_element: PseudoElement) -> bool {
+    return rules.iter().any(|r| {
+        r.selectors.iter().any(|s| s.pseudo_element == Some(pseudo_element) && selector_matches(element, s))
+    })
+}
+
+// The default ::marker text for a list item.
+fn marker_text(list_style_type: Option<&Value>, counter: i32) -> Option<String> {
+    let style = match list_style_type {
+        Some(&Value::Other(ref style)) => style.as_str(),
+        _ => "disc",
+    };
+
+    return match style {
+        "none" => None,
+        "disc" | "circle" | "square" => Some(format!("{} ", format_counter(counter, style))),
+        _ => Some(format!("{}. ", format_counter(counter, style))),
+    }
+}
+
+fn format_counter(value: i32, style: &str) -> String {
+    return match style {
+        "none" => String::new(),
+        "disc" => String::from("\u{2022}"),
+        "circle" => String::from("\u{25e6}"),
+        "square" => String::from("\u{25aa}"),
+        "lower-alpha" | "lower-latin" if value > 0 => alphabetic(value),
+        "upper-alpha" | "upper-latin" if value > 0 => alphabetic(value).to_uppercase(),
+        "lower-roman" if value > 0 && value < 4000 => roman(value),
+        "upper-roman" if value > 0 && value < 4000 => roman(value).to_uppercase(),
+        _ => value.to_string(),
+    }
+}
+
+// a, b, ..., z, aa, ab, ...
+fn alphabetic(mut value: i32) -> String {
+    let mut letters = Vec::new();
+
+    while value > 0 {
+        value -= 1;
+        letters.push((b'a' + (value % 26) as u8) as char);
+        value /= 26;
+    }
+
+    return letters.iter().rev().collect()
+}
+
+fn roman(mut value: i32) -> String {
+    const NUMERALS: &[(i32, &str)] = &[
+        (1000, "m"), (900, "cm"), (500, "d"), (400, "cd"), (100, "c"), (90, "xc"),
+        (50, "l"), (40, "xl"), (10, "x"), (9, "ix"), (5, "v"), (4, "iv"), (1, "i"),
+    ];
+
+    let mut result = String::new();
+
+    for &(n, numeral) in NUMERALS {
+        while value >= n {
+            result.push_str(numeral);
+            value -= n;
+        }
+    }
+
+    return result
+}
+
+// The shorthand a longhand's value comes from, when it was set by a shorthand
+// that uses var().
+fn pending_shorthand(specified: &HashMap<&str, &Value>, name: &str, value: &Value) -> Option<&'static Shorthand> {
+    return properties::shorthand_of(name).filter(|s| specified.get(s.name).map_or(false, |&v| ptr::eq(v, value)))
+}
+
+// Turns the winning declarations of an element into computed values, which
+// means substituting var() once the custom properties are known.
+fn compute_values(
+    specified: &HashMap<&str, &Value>,
+    parent: Option<&PropertyMap>,
+    context: &LengthContext,
+) -> PropertyMap {
+    let mut variables = VariableResolver::new(specified, parent);
+    let mut styles = PropertyMap::new();
+
+    // Custom properties always inherit.
+    if let Some(parent) = parent {
+        for (name, value) in parent.iter().filter(|&(n, _)| is_custom_property(n)) {
+            styles.insert(name.clone(), value.clone());
+        }
+    }
+
+    for (&name, &value) in specified {
+        if is_custom_property(name) {
+            match variables.resolve(name) {
+                Some(tokens) => styles.insert(name.to_string(), Value::Tokens(tokens)),
+                None => styles.remove(name),
+            };
+
+            continue
+        }
+
+        // Its longhands are computed from it.
+        if properties::lookup_shorthand(name).is_some() {
+            continue
+        }
+
+        let computed = match *value {
+            Value::Tokens(ref tokens) => variables.substitute(tokens)
+                .and_then(|t| {
+                    let text = serialize_tokens(&t);
+
+                    match pending_shorthand(specified, name, value) {
+                        Some(shorthand) => parse_property(shorthand.name, text.trim(), false)?
+                            .into_iter()
+                            .find(|d| d.property == name)
+                            .map(|d| d.value),
+                        None => parse_declaration_value(name, text.trim()),
+                    }
+                })
+                // Invalid at computed-value time, so it acts as `unset`.
+                .or_else(|| unset_value(name, parent)),
+            ref v => Some(v.clone()),
+        };
+
+        styles.extend(computed.map(|v| (name.to_string(), v)));
+    }
+
+    // Inherited properties fall back to the parent's value, and the CSS-wide
+    // keywords become real values.
+    for property in properties::all() {
+        let name = property.name;
+
+        let computed = match styles.get(name) {
+            None if property.inherited => parent.and_then(|p| p.get(name)).cloned(),
+            Some(&Value::Other(ref keyword)) => match keyword.as_ref() {
+                "inherit" => parent.and_then(|p| p.get(name)).cloned()
+                    .or_else(|| parse_declaration_value(name, property.initial)),
+                "initial" => parse_declaration_value(name, property.initial),
+                "unset" | "revert" | "revert-layer" => unset_value(name, parent),
+                _ => continue,
+            },
+            _ => continue,
+        };
+
+        match computed {
+            Some(value) => styles.insert(name.to_string(), value),
+            None => styles.remove(name),
+        };
+    }
+
+    // Other lengths can be relative to the font size, so it's always
+    // computed to px, and inherited.
+    let parent_font_size = font_size(parent);
+
+    let computed_font_size = match styles.get("font-size") {
+        Some(value) => font_size_to_px(value, parent_font_size, context),
+        None => parent_font_size,
+    };
+
+    styles.insert(String::from("font-size"), Value::Length(computed_font_size, Unit::Px));
+
+    let computed_font_weight = match styles.get("font-weight") {
+        Some(&Value::Other(ref keyword)) => Some(relative_font_weight(keyword, font_weight(parent))),
+        _ => None,
+    };
+
+    if let Some(weight) = computed_font_weight {
+        styles.insert(String::from("font-weight"), Value::Number(weight));
+    }
+
+    return styles
+}
+
+fn font_weight(styles: Option<&PropertyMap>) -> f32 {
+    return match styles.and_then(|s| s.get("font-weight")) {
+        Some(&Value::Number(n)) => n,
+        _ => 400.0,
+    }
+}
+
+// `bolder` and `lighter`, from the table in CSS Fonts.
+fn relative_font_weight(keyword: &str, parent_weight: f32) -> f32 {
+    return match (keyword, parent_weight) {
+        ("bolder", w) if w < 350.0 => 400.0,
+        ("bolder", w) if w < 550.0 => 700.0,
+        ("bolder", w) if w < 900.0 => 900.0,
+        ("lighter", w) if w < 100.0 => w,
+        ("lighter", w) if w < 550.0 => 100.0,
+        ("lighter", w) if w < 750.0 => 400.0,
+        ("lighter", _) => 700.0,
+        (_, w) => w,
+    }
+}
+
+fn font_size(styles: Option<&PropertyMap>) -> f32 {
+    return match styles.and_then(|s| s.get("font-size")) {
+        Some(&Value::Length(n, Unit::Px)) => n,
+        _ => 16.0,
+    }
+}
+
+// Percentages and em lengths are relative to the parent's font size.
+fn font_size_to_px(value: &Value, parent_font_size: f32, context: &LengthContext) -> f32 {
+    let context = LengthContext {
+        percent_base: parent_font_size,
+        font_size: parent_font_size,
+        ..*context
+    };
+
+    if let Some(px) = resolve_length(value, &context) {
+        return px
+    }
+
+    return match *value {
+        Value::Other(ref keyword) => match keyword.as_ref() {
+            "xx-small" => 9.0,
+            "x-small" => 10.0,
+            "small" => 13.0,
+            "large" => 18.0,
+            "x-large" => 24.0,
+            "xx-large" => 32.0,
+            "smaller" => parent_font_size / 1.2,
+            "larger" => parent_font_size * 1.2,
+            "inherit" | "unset" => parent_font_size,
+            _ => 16.0,
+        },
+        _ => 16.0,
+    }
+}
+
+// Resolves a length, percentage or math function to px.
+pub fn resolve_length(value: &Value, context: &LengthContext) -> Option<f32> {
+    return match *value {
+        Value::Length(n, unit) => Some(length_to_px(n, unit, context)),
+        Value::Calc(ref node) => Some(resolve_calc(node, context)),
+        _ => None,
+    }
+}
+
+pub fn length_to_px(n: f32, unit: Unit, context: &LengthContext) -> f32 {
+    let vw = context.viewport_width;
+    let vh = context.viewport_height;
+
+    return match unit {
+        Unit::Px => n,
+        Unit::Em => n * context.font_size,
+        Unit::Rem => n * context.root_font_size,
+        Unit::Ex | Unit::Ch => n * context.font_size / 2.0,
+        Unit::Vw => n * vw / 100.0,
+        Unit::Vh => n * vh / 100.0,
+        Unit::Vmin => n * vw.min(vh) / 100.0,
+        Unit::Vmax => n * vw.max(vh) / 100.0,
+        Unit::In => n * 96.0,
+        Unit::Cm => n * 96.0 / 2.54,
+        Unit::Mm => n * 96.0 / 25.4,
+        Unit::Q => n * 96.0 / 101.6,
+        Unit::Pt => n * 96.0 / 72.0,
+        Unit::Pc => n * 16.0,
+        Unit::Pct => n * context.percent_base / 100.0,
+    }
+}
+
+fn resolve_calc(node: &CalcNode, context: &LengthContext) -> f32 {
+    let resolve = |n: &CalcNode| resolve_calc(n, context);
+
+    return match *node {
+        CalcNode::Number(n) => n,
+        CalcNode::Length(n, unit) => length_to_px(n, unit, context),
+        CalcNode::Sum(ref terms) => terms.iter().map(resolve).sum(),
+        CalcNode::Scale(k, ref node) => k * resolve(node),
+        CalcNode::Min(ref args) => args.iter().map(resolve).fold(f32::INFINITY, f32::min),
+        CalcNode::Max(ref args) => args.iter().map(resolve).fold(f32::NEG_INFINITY, f32::max),
+        CalcNode::Clamp(ref min, ref val, ref max) => resolve(min).max(resolve(val).min(resolve(max))),
+    }
+}
+
+fn unset_value(property: &str, parent: Option<&PropertyMap>) -> Option<Value> {
+    let property = properties::lookup(property)?;
+
+    if property.inherited {
+        if let Some(value) = parent.and_then(|p| p.get(property.name)) {
+            return Some(value.clone())
+        }
+    }
+
+    return parse_declaration_value(property.name, property.initial)
+}
+
+// The index of the `)` matching the function at `open`, or the end of
+// `tokens` if it was never closed.
+fn closing_paren(tokens: &[Token], open: usize) -> usize {
+    let mut depth = 0;
+
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        match *token {
+            Token::Function(_) => depth += 1,
+            Token::CloseParen => {
+                depth -= 1;
+
+                if depth == 0 {
+                    return i
+                }
+            },
+            _ => {},
+        }
+    }
+
+    return tokens.len()
+}
+
+// Flattens the style rules that apply under `viewport`, in source order.
+// `parent` holds the selectors of the style rule `rules` are nested in, and
+// `containers` the @container rules they're in.
+fn collect_rules<'a>(
+    rules: &'a [CssRule],
+    layer: &[String],
+    parent: Option<&[Selector]>,
+    containers: &[&'a ContainerRule],
+    viewport: &Viewport,
+    collector: &mut RuleCollector<'a>,
+) {
+    for rule in rules {
+        match *rule {
+            CssRule::Style(ref r) => {
+                let selectors = match parent {
+                    Some(p) => Cow::Owned(r.selectors.iter().map(|s| s.resolve_nesting(p)).collect()),
+                    None => Cow::Borrowed(&r.selectors[..]),
+                };
+
+                // Nested rules come after their parent's declarations.
+                let nested_parent = match r.rules.is_empty() {
+                    true => None,
+                    false => Some(selectors.to_vec()),
+                };
+
+                if !r.declarations.is_empty() {
+                    collector.rules.push(CollectedRule {
+                        selectors,
+                        declarations: &r.declarations,
+                        origin: collector.origin,
+                        layer: layer.to_vec(),
+                        layer_order: 0,
+                        containers: containers.to_vec(),
+                    });
+                }
+
+                if let Some(ref p) = nested_parent {
+                    collect_rules(&r.rules, layer, Some(p), containers, viewport, collector);
+                }
+            },
+            CssRule::Media(ref m) => if media_query_list_matches(&m.queries, viewport) {
+                collect_rules(&m.rules, layer, parent, containers, viewport, collector);
+            },
+            CssRule::Supports(ref s) => if supports_condition_matches(&s.condition) {
+                collect_rules(&s.rules, layer, parent, containers, viewport, collector);
+            },
+            CssRule::Layer(ref l) => {
+                let path = collector.declare_layer(layer, l.name.as_ref().map(String::as_str));
+
+                collect_rules(&l.rules, &path, parent, containers, viewport, collector);
+            },
+            // Whether these apply depends on the element, so they're checked
+            // when matching.
+            CssRule::Container(ref c) => {
+                let mut containers = containers.to_vec();
+
+                containers.push(c);
+
+                collect_rules(&c.rules, layer, parent, &containers, viewport, collector);
+            },
+            CssRule::LayerStatement(ref names) => for name in names {
+                collector.declare_layer(layer, Some(name));
+            },
+            // Loaded into a `FontDatabase` instead.
+            CssRule::FontFace(_) => {},
+            CssRule::Keyframes(ref k) => collector.keyframes.push((k, layer.to_vec())),
+            CssRule::Import(ref i) => match i.stylesheet {
+                Some(ref sheet) if media_query_list_matches(&i.media, viewport)
+                    && i.supports.as_ref().map_or(true, supports_condition_matches) =>
+                {
+                    let path = match i.layer {
+                        Some(ImportLayer::Named(ref n)) => collector.declare_layer(layer, Some(n)),
+                        Some(ImportLayer::Anonymous) => collector.declare_layer(layer, None),
+                        None => layer.to_vec(),
+                    };
+
+                    collect_rules(&sheet.rules, &path, None, containers, viewport, collector);
+                },
+                _ => {},
+            },
+        }
+    }
+}
+
+// A feature is supported if the engine can parse it, so authors' fallbacks
+// pick the branch we can actually render.
+pub fn supports_condition_matches(condition: &SupportsCondition) -> bool {
+    return match *condition {
+        SupportsCondition::Declaration(ref property, ref value) => {
+            let mut value = value.clone();
+
+            strip_important(&mut value);
+
+            let known = properties::lookup(property).is_some() || properties::lookup_shorthand(property).is_some();
+
+            (known || is_custom_property(property)) && parse_property(property, &value, false).is_some()
+        },
+        SupportsCondition::Selector(ref s) => parse_selector_list(s).is_some(),
+        SupportsCondition::Not(ref c) => !supports_condition_matches(c),
+        SupportsCondition::And(ref cs) => cs.iter().all(supports_condition_matches),
+        SupportsCondition::Or(ref cs) => cs.iter().any(supports_condition_matches),
+        SupportsCondition::Unknown(_) => false,
+    }
+}
+
+pub fn media_query_list_matches(queries: &[MediaQuery], viewport: &Viewport) -> bool {
+    return queries.is_empty() || queries.iter().any(|q| media_query_matches(q, viewport))
+}
+
+fn media_query_matches(query: &MediaQuery, viewport: &Viewport) -> bool {
+    let type_matches = match query.media_type {
+        Some(ref t) => t == "all" || *t == viewport.media_type,
+        None => true,
+    };
+
+    let condition_matches = match query.condition {
+        Some(ref c) => condition_matches(c, viewport).unwrap_or(false),
+        None => true,
+    };
+
+    let matches = type_matches && condition_matches;
+
+    return match query.qualifier {
+        Some(MediaQualifier::Not) => !matches,
+        _ => matches,
+    }
+}
+
+// Queries the nearest container with the rule's name. Size queries also need
+// a container with size containment; without a layout they don't match.
+fn container_rule_matches(rule: &ContainerRule, containers: &[QueryContainer]) -> bool {
+    let container = containers.iter().rev().find(|c| {
+        rule.name.as_ref().map_or(true, |n| c.names.contains(n))
+            && (rule.condition.is_none() || c.container_type != ContainerType::Normal)
+    });
+
+    return match (container, rule.condition.as_ref()) {
+        (Some(c), Some(condition)) => condition_matches(condition, c).unwrap_or(false),
+        (Some(_), None) => true,
+        (None, _) => false,
+    }
+}
+
+// Media and container conditions use three-valued logic: `None` means
+// unknown, which a `not` can't turn into a match.
+fn condition_matches(condition: &MediaCondition, environment: &dyn QueryEnvironment) -> Option<bool> {
+    return match *condition {
+        MediaCondition::Feature(ref f) => feature_matches(f, environment),
+        MediaCondition::Not(ref c) => condition_matches(c, environment).map(|m| !m),
+        MediaCondition::And(ref cs) => {
+            let results: Vec<Option<bool>> = cs.iter().map(|c| condition_matches(c, environment)).collect();
+
+            if results.contains(&Some(false)) {
+                Some(false)
+            } else if results.contains(&None) {
+                None
+            } else {
+                Some(true)
+            }
+        },
+        MediaCondition::Or(ref cs) => {
+            let results: Vec<Option<bool>> = cs.iter().map(|c| condition_matches(c, environment)).collect();
+
+            if results.contains(&Some(true)) {
+                Some(true)
+            } else if results.contains(&None) {
+                None
+            } else {
+                Some(false)
+            }
+        },
+        MediaCondition::Unknown(_) => None,
+    }
+}
+
+fn feature_matches(feature: &MediaFeature, environment: &dyn QueryEnvironment) -> Option<bool> {
+    return match *feature {
+        // True unless the value is zero or `none`.
+        MediaFeature::Boolean(ref name) => match environment.feature_value(name)? {
+            MediaValue::Number(n) => Some(n != 0.0),
+            MediaValue::Ident(ref i) => Some(i != "none"),
+            _ => Some(true),
+        },
+        MediaFeature::Plain(ref name, ref value) => {
+            let (prefix, name) = if name.starts_with("min-") {
+                (Some(RangeOp::Ge), &name[4..])
+            } else if name.starts_with("max-") {
+                (Some(RangeOp::Le), &name[4..])
+            } else {
+                (None, &name[..])
+            };
+
+            match (environment.feature_value(name)?, value) {
+                (MediaValue::Ident(ref actual), &MediaValue::Ident(ref expected)) if prefix.is_none() => {
+                    Some(actual == expected)
+                },
+                (MediaValue::Number(actual), v) => {
+                    Some(compare(actual, prefix.unwrap_or(RangeOp::Eq), environment.value_number(name, v)?))
+                },
+                _ => None,
+            }
+        },
+        MediaFeature::Range { ref name, ref start, ref end } => {
+            let actual = match environment.feature_value(name)? {
+                MediaValue::Number(n) => n,
+                _ => return None,
+            };
+
+            let start_matches = match *start {
+                Some((ref v, op)) => compare(environment.value_number(name, v)?, op, actual),
+                None => true,
+            };
+
+            let end_matches = match *end {
+                Some((op, ref v)) => compare(actual, op, environment.value_number(name, v)?),
+                None => true,
+            };
+
+            Some(start_matches && end_matches)
+        },
+    }
+}
+
+impl QueryEnvironment for Viewport {
+    fn feature_value(&self, name: &str) -> Option<MediaValue> {
+        return match name {
+            "width" => Some(MediaValue::Number(self.width)),
+            "height" => Some(MediaValue::Number(self.height)),
+            "aspect-ratio" => Some(MediaValue::Number(self.width / self.height)),
+            "resolution" => Some(MediaValue::Number(self.device_pixel_ratio)),
+            "color" => Some(MediaValue::Number(8.0)),
+            "monochrome" | "grid" => Some(MediaValue::Number(0.0)),
+            "orientation" => Some(MediaValue::Ident(String::from(
+                if self.height >= self.width { "portrait" } else { "landscape" }
+            ))),
+            "prefers-color-scheme" => Some(MediaValue::Ident(String::from(match self.color_scheme {
+                ColorScheme::Light => "light",
+                ColorScheme::Dark => "dark",
+            }))),
+            _ => None,
+        }
+    }
+
+    // Relative units in media queries are based on the initial font size.
+    fn value_number(&self, name: &str, value: &MediaValue) -> Option<f32> {
+        return match (name, value) {
+            ("width", v) | ("height", v) => query_length_to_px(v, &LengthContext::new(self)),
+            ("aspect-ratio", &MediaValue::Ratio(a, b)) => Some(a / b),
+            ("aspect-ratio", &MediaValue::Number(n)) => Some(n),
+            ("resolution", &MediaValue::Resolution(n)) => Some(n),
+            ("color", &MediaValue::Number(n)) | ("monochrome", &MediaValue::Number(n)) | ("grid", &MediaValue::Number(n)) => Some(n),
+            _ => None,
+        }
+    }
+}
+
+// The size features. An inline-size container can only answer queries about
+// its width, since its height still depends on its contents.
+impl QueryEnvironment for QueryContainer {
+    fn feature_value(&self, name: &str) -> Option<MediaValue> {
+        let (width, height) = self.size?;
+        let sized = self.container_type == ContainerType::Size;
+
+        return match name {
+            "width" | "inline-size" => Some(MediaValue::Number(width)),
+            "height" | "block-size" if sized => Some(MediaValue::Number(height)),
+            "aspect-ratio" if sized => Some(MediaValue::Number(width / height)),
+            "orientation" if sized => Some(MediaValue::Ident(String::from(
+                if height >= width { "portrait" } else { "landscape" }
+            ))),
+            _ => None,
+        }
+    }
+
+    fn value_number(&self, name: &str, value: &MediaValue) -> Option<f32> {
+        return match (name, value) {
+            ("width", v) | ("height", v) | ("inline-size", v) | ("block-size", v) => query_length_to_px(v, &self.context),
+            ("aspect-ratio", &MediaValue::Ratio(a, b)) => Some(a / b),
+            ("aspect-ratio", &MediaValue::Number(n)) => Some(n),
+            _ => None,
+        }
+    }
+}
+
+// A length in a query in px. Percentages have nothing to refer to.
+fn query_length_to_px(value: &MediaValue, context: &LengthContext) -> Option<f32> {
+    return match *value {
+        MediaValue::Length(_, Unit::Pct) => None,
+        MediaValue::Length(n, unit) => Some(length_to_px(n, unit, context)),
+        MediaValue::Number(n) if n == 0.0 => Some(0.0),
+        _ => None,
+    }
+}
+
+fn compare(a: f32, op: RangeOp, b: f32) -> bool {
+    return match op {
+        RangeOp::Lt => a < b,
+        RangeOp::Le => a <= b,
+        RangeOp::Gt => a > b,
+        RangeOp::Ge => a >= b,
+        RangeOp::Eq => a == b,
+    }
+}
+
+fn selector_matches(element: &ElementRef, selector: &Selector) -> bool {
+    return match selector.simple.len() {
+        0 => false,
+        n => complex_selector_matches(element, selector, n - 1, None),
+    }
+}
+
+// Matches `selector.simple[..=i]` right to left, trying every ancestor or
+// earlier sibling the combinator allows. Inside :has(), the leftmost
+// compound must also be related to the `anchor` element by its combinator.
+fn complex_selector_matches(
+    element: &ElementRef,
+    selector: &Selector,
+    i: usize,
+    anchor: Option<(&ElementRef, char)>,
+) -> bool {
+    if !simple_selector_matches(element, &selector.simple[i]) {
+        return false
+    }
+
+    let combinator = match i {
+        0 => match anchor {
+            Some((_, c)) => c,
+            None => return true,
+        },
+        _ => selector.combinators[i - 1],
+    };
+
+    // Either the next compound to the left, or the anchor itself.
+    let matches_left = |e: &ElementRef| match (i, anchor) {
+        (0, Some((a, _))) => e.is(a),
+        _ => complex_selector_matches(e, selector, i - 1, anchor),
+    };
+
+    match combinator {
+        '>' => return element.parent.map_or(false, |p| matches_left(p)),
+        '+' => return element.previous_sibling().map_or(false, |s| matches_left(&s)),
+        ' ' => {
+            let mut ancestor = element.parent;
+
+            while let Some(a) = ancestor {
+                if matches_left(a) {
+                    return true
+                }
+
+                ancestor = a.parent;
+            }
+        },
+        '~' => {
+            let mut sibling = element.previous_sibling();
+
+            while let Some(s) = sibling {
+                if matches_left(&s) {
+                    return true
+                }
+
+                sibling = s.previous_sibling();
+            }
+        },
+        _ => {},
+    }
+
+    return false
+}
+
+fn simple_selector_matches(element_ref: &ElementRef, simple: &SimpleSelector) -> bool {
+    let element = element_ref.element;
+
+    match simple.tag_name {
+        Some(ref t) => if *t != element.tag_name {
+            return false;
+        },
+        None => {},
+    };
+
+    match simple.id {
+        Some(ref id) => if element.get_id() != Some(id) {
+            return false;
+        },
+        None => {},
+    };
+
+    let element_classes = element.get_classes();
+
+    return simple.classes.iter().all(|class| element_classes.contains::<str>(class))
+        && simple.attributes.iter().all(|a| attribute_selector_matches(element, a))
+        && simple.pseudo_classes.iter().all(|p| pseudo_class_matches(element_ref, p))
+}
+
+fn pseudo_class_matches(element_ref: &ElementRef, pseudo_class: &PseudoClass) -> bool {
+    let element = element_ref.element;
+    let any = |_: &ElementRef| true;
+    let same_type = |s: &ElementRef| s.element.tag_name == element.tag_name;
+
+    return match *pseudo_class {
+        // Outside a nested rule, `&` is the scoping root.
+        PseudoClass::Nesting | PseudoClass::Root => element_ref.parent.is_none(),
+        PseudoClass::Empty => element_ref.node().children.iter().all(|c| match c.node_type {
+            NodeType::Comment(_) => true,
+            _ => false,
+        }),
+        PseudoClass::FirstChild => nth_position(element_ref, false, &any) == Some(1),
+        PseudoClass::LastChild => nth_position(element_ref, true, &any) == Some(1),
+        PseudoClass::OnlyChild => element_ref.sibling_elements().len() == 1,
+        PseudoClass::FirstOfType => nth_position(element_ref, false, &same_type) == Some(1),
+        PseudoClass::LastOfType => nth_position(element_ref, true, &same_type) == Some(1),
+        PseudoClass::OnlyOfType => element_ref.sibling_elements().iter().filter(|s| same_type(s)).count() == 1,
+        PseudoClass::NthChild(nth, ref of) | PseudoClass::NthLastChild(nth, ref of) => {
+            let from_end = match *pseudo_class {
+                PseudoClass::NthLastChild(..) => true,
+                _ => false,
+            };
+
+            let filter = |s: &ElementRef| of.is_empty() || of.iter().any(|sel| selector_matches(s, sel));
+
+            nth_position(element_ref, from_end, &filter).map_or(false, |p| nth_matches(nth, p))
+        },
+        PseudoClass::NthOfType(nth) => nth_position(element_ref, false, &same_type).map_or(false, |p| nth_matches(nth, p)),
+        PseudoClass::NthLastOfType(nth) => nth_position(element_ref, true, &same_type).map_or(false, |p| nth_matches(nth, p)),
+        PseudoClass::Not(ref selectors) => !selectors.iter().any(|s| selector_matches(element_ref, s)),
+        PseudoClass::Is(ref selectors) | PseudoClass::Where(ref selectors) => {
+            selectors.iter().any(|s| selector_matches(element_ref, s))
+        },
+        PseudoClass::Has(ref relative) => relative.iter().any(|r| relative_selector_matches(element_ref, r)),
+        PseudoClass::Hover => element.state.hover,
+        PseudoClass::Active => element.state.active,
+        PseudoClass::Focus => element.state.focus,
+        PseudoClass::Checked => element.state.checked || match element.tag_name.as_ref() {
+            "input" => element.get_attr("checked").is_some(),
+            "option" => element.get_attr("selected").is_some(),
+            _ => false,
+        },
+        PseudoClass::Disabled => is_form_control(element) && is_disabled(element),
+        PseudoClass::Enabled => is_form_control(element) && !is_disabled(element),
+        PseudoClass::Link => is_link(element) && !element.state.visited,
+        PseudoClass::Visited => is_link(element) && element.state.visited,
+    }
+}
+
+// The 1-based position of the element among its siblings that pass
+// `filter`, or `None` if the element itself doesn't.
+fn nth_position(element: &ElementRef, from_end: bool, filter: &dyn Fn(&ElementRef) -> bool) -> Option<usize> {
+    let siblings: Vec<ElementRef> = element.sibling_elements().into_iter().filter(|s| filter(s)).collect();
+    let index = siblings.iter().position(|s| s.index == element.index)?;
+
+    return match from_end {
+        true => Some(siblings.len() - index),
+        false => Some(index + 1),
+    }
+}
+
+// Whether `position` is `a * n + b` for some n >= 0.
+fn nth_matches(nth: Nth, position: usize) -> bool {
+    let offset = position as i32 - nth.b;
+
+    return match nth.a {
+        0 => offset == 0,
+        a => offset % a == 0 && offset / a >= 0,
+    }
+}
+
+// Looks for an element the relative selector reaches from `anchor`: inside
+// it for descendant and child combinators, after it for sibling ones.
+fn relative_selector_matches(anchor: &ElementRef, relative: &RelativeSelector) -> bool {
+    let selector = &relative.selector;
+
+    if selector.simple.is_empty() {
+        return false
+    }
+
+    let last = selector.simple.len() - 1;
+    let mut matches = |e: &ElementRef| complex_selector_matches(e, selector, last, Some((anchor, relative.combinator)));
+
+    return match relative.combinator {
+        ' ' | '>' => any_descendant(anchor, &mut matches),
+        _ => anchor.sibling_elements()
+            .iter()
+            .filter(|s| s.index > anchor.index)
+            .any(|s| matches(s) || any_descendant(s, &mut matches)),
+    }
+}
+
+fn any_descendant(element: &ElementRef, f: &mut dyn FnMut(&ElementRef) -> bool) -> bool {
+    let node = element.node();
+
+    for (index, child) in node.children.iter().enumerate() {
+        if let NodeType::Element(ref e) = child.node_type {
+            let child = ElementRef {
+                element: e,
+                parent: Some(element),
+                siblings: &node.children,
+                index,
+            };
+
+            if f(&child) || any_descendant(&child, f) {
+                return true
+            }
+        }
+    }
+
+    return false
+}
+
+fn is_form_control(element: &ElementData) -> bool {
+    return match element.tag_name.as_ref() {
+        "button" | "input" | "select" | "textarea" | "optgroup" | "option" | "fieldset" => true,
+        _ => false,
+    }
+}
+
+fn is_disabled(element: &ElementData) -> bool {
+    return element.state.disabled || element.get_attr("disabled").is_some()
+}
+
+fn is_link(element: &ElementData) -> bool {
+    return match element.tag_name.as_ref() {
+        "a" | "area" => element.get_attr("href").is_some(),
+        _ => false,
+    }
+}
+
+// The DOM has no namespaced attributes, so `[|name]` and `[*|name]` match the
+// same attributes as `[name]`.
+fn attribute_selector_matches(element: &ElementData, selector: &AttributeSelector) -> bool {
+    return element.attrs().iter().any(|(attr, value)| *attr == selector.name && attribute_value_matches(attr, value, selector))
+}
+
+fn attribute_value_matches(attr: &str, actual: &str, selector: &AttributeSelector) -> bool {
+    let insensitive = match selector.case {
+        CaseSensitivity::Insensitive => true,
+        CaseSensitivity::Sensitive => false,
+        CaseSensitivity::Default => CASE_INSENSITIVE_ATTRIBUTES.contains(&attr),
+    };
+
+    let (actual, expected) = match insensitive {
+        true => (actual.to_ascii_lowercase(), selector.value.to_ascii_lowercase()),
+        false => (actual.to_string(), selector.value.clone()),
+    };
+
+    return match selector.operator {
+        AttrOperator::Exists => true,
+        AttrOperator::Equals => actual == expected,
+        AttrOperator::Includes => !expected.is_empty()
+            && !expected.contains(char::is_whitespace)
+            && actual.split_whitespace().any(|v| v == expected),
+        AttrOperator::DashMatch => actual == expected || actual.starts_with(&format!("{}-", expected)),
+        AttrOperator::Prefix => !expected.is_empty() && actual.starts_with(&expected),
+        AttrOperator::Suffix => !expected.is_empty() && actual.ends_with(&expected),
+        AttrOperator::Substring => !expected.is_empty() && actual.contains(&expected),
+    }
+}
+
+pub fn pretty_print(n: &Node, indent_size: usize) {
+    let indent = (0..indent_size).map(|_| " ").collect::<String>();
+
+    println!("{}{:?}", indent, n);
+
+    for child in n.children.iter() {
+        pretty_print(&child, indent_size + 4);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css_parser::CssParser;
+    use crate::html_parser::HtmlParser;
+
+    // The computed `property` of the first element in `html`, styled with the
+    // user agent stylesheet and `sheets`.
+    fn computed(html: &str, sheets: &[(Origin, &str)], property: &str) -> Option<String> {
+        let nodes = HtmlParser::new(html).parse_nodes();
+        let sheets: Vec<Stylesheet> = sheets.iter().map(|&(origin, css)| CssParser::new(css).parse_stylesheet_with_origin(origin)).collect();
+        let sheets: Vec<&Stylesheet> = sheets.iter().collect();
+
+        let styled = StyledNode::with_containers(&nodes[0], &sheets, &Viewport::default(), &mut Timeline::new(), &ContainerSizes::new());
+
+        return styled.value(property).map(Value::to_string)
+    }
+
+    fn width(html: &str, sheets: &[(Origin, &str)]) -> Option<String> {
+        return computed(html, sheets, "width")
+    }
+
+    // The ids of the elements under `root` that `selector` matches, in
+    // document order.
+    fn matching(root: &Node, selector: &str) -> Vec<String> {
+        fn collect(styled: &StyledNode, ids: &mut Vec<String>) {
+            if let NodeType::Element(ref e) = styled.node.node_type {
+                if styled.pseudo_element.is_none() && styled.value("width").map(Value::to_string).as_deref() == Some("1px") {
+                    ids.extend(e.get_id().cloned());
+                }
+            }
+
+            for child in &styled.children {
+                collect(child, ids);
+            }
+        }
+
+        let sheet = CssParser::new(&format!("{} {{ width: 1px }}", selector)).parse_stylesheet();
+        let mut ids = Vec::new();
+
+        collect(&StyledNode::new(root, &sheet, &Viewport::default()), &mut ids);
+
+        return ids
+    }
+
+    fn matching_html(html: &str, selector: &str) -> Vec<String> {
+        return matching(&HtmlParser::new(html).parse_nodes()[0], selector)
+    }
+
+    #[test]
+    fn later_origins_win() {
+        let user = (Origin::User, "p { display: inline; width: 1px }");
+        let author = (Origin::Author, "p { width: 2px }");
+
+        assert_eq!(computed("<p></p>", &[user], "display").as_deref(), Some("inline"));
+        assert_eq!(computed("<p></p>", &[], "display").as_deref(), Some("block"));
+        assert_eq!(width("<p></p>", &[author, user]).as_deref(), Some("2px"));
+    }
+
+    #[test]
+    fn important_declarations_reverse_the_origins() {
+        let user = (Origin::User, "p { width: 1px !important }");
+        let author = (Origin::Author, "#x { width: 2px }");
+        let important_author = (Origin::Author, "#x { width: 3px !important }");
+
+        assert_eq!(width("<p id=\"x\"></p>", &[user, author]).as_deref(), Some("1px"));
+        assert_eq!(width("<p id=\"x\"></p>", &[user, important_author]).as_deref(), Some("1px"));
+        assert_eq!(width("<p id=\"x\"></p>", &[(Origin::User, "p { width: 1px }"), important_author]).as_deref(), Some("3px"));
+    }
+
+    #[test]
+    fn style_attributes_beat_rules_of_the_same_importance() {
+        let author = (Origin::Author, "#x { width: 2px }");
+        let important_author = (Origin::Author, "#x { width: 3px !important }");
+
+        assert_eq!(width("<p id=\"x\" style=\"width: 4px\"></p>", &[author]).as_deref(), Some("4px"));
+        assert_eq!(width("<p id=\"x\" style=\"width: 4px\"></p>", &[important_author]).as_deref(), Some("3px"));
+        assert_eq!(width("<p id=\"x\" style=\"width: 4px !important\"></p>", &[important_author]).as_deref(), Some("4px"));
+    }
+
+    #[test]
+    fn layers_are_ordered_by_first_declaration() {
+        let layered = "@layer a, b; @layer b { #x { width: 1px } } @layer a { #x { width: 2px } }";
+
+        assert_eq!(width("<p id=\"x\"></p>", &[(Origin::Author, layered)]).as_deref(), Some("1px"));
+        assert_eq!(
+            width("<p id=\"x\"></p>", &[(Origin::Author, &format!("{} p {{ width: 3px }}", layered))]).as_deref(),
+            Some("3px"),
+        );
+    }
+
+    #[test]
+    fn important_declarations_in_earlier_layers_win() {
+        let css = "@layer a, b; @layer a { p { width: 1px !important } } @layer b { p { width: 2px !important } } p { width: 3px !important }";
+
+        assert_eq!(width("<p></p>", &[(Origin::Author, css)]).as_deref(), Some("1px"));
+    }
+
+    #[test]
+    fn more_specific_selectors_win() {
+        let css = "#x { width: 1px } .c { width: 2px } p { width: 3px }";
+
+        assert_eq!(width("<p id=\"x\" class=\"c\"></p>", &[(Origin::Author, css)]).as_deref(), Some("1px"));
+        assert_eq!(width("<p class=\"c\"></p>", &[(Origin::Author, css)]).as_deref(), Some("2px"));
+    }
+
+    #[test]
+    fn later_declarations_win_otherwise() {
+        let css = "p { width: 1px } p { width: 2px; width: 3px }";
+
+        assert_eq!(width("<p></p>", &[(Origin::Author, css)]).as_deref(), Some("3px"));
+    }
+
+    #[test]
+    fn font_queries_come_from_the_font_properties() {
+        let nodes = HtmlParser::new("<p><b></b></p>").parse_nodes();
+        let sheet = CssParser::new("p { font-family: \"Font 2\", Open Sans, serif; font-weight: 600; font-stretch: 50% } b { font-weight: bolder; font-style: oblique 10deg }").parse_stylesheet();
+        let styled = StyledNode::new(&nodes[0], &sheet, &Viewport::default());
+
+        let (families, query) = styled.children[0].font_query();
+
+        assert_eq!(families, vec!["Font 2", "Open Sans", "serif"]);
+        assert_eq!(query, FontQuery { weight: 900.0, style: FontStyle::Oblique(10.0, 10.0), stretch: 50.0 });
+    }
+
+    #[test]
+    fn revert_rolls_back_to_earlier_origins() {
+        let user = (Origin::User, "p { width: 1px }");
+        let author = (Origin::Author, "p { width: 2px } p { width: revert; display: revert }");
+
+        assert_eq!(width("<p></p>", &[user, author]).as_deref(), Some("1px"));
+        assert_eq!(computed("<p></p>", &[user, author], "display").as_deref(), Some("block"));
+        assert_eq!(width("<p></p>", &[author]), None);
+    }
+
+    #[test]
+    fn namespace_prefixes_on_attributes() {
+        let html = "<a href=\"x\" xlink:href=\"y\"></a>";
+        let width = |css: &str| width(html, &[(Origin::Author, css)]);
+
+        assert_eq!(width("[*|href=x] { width: 1px }").as_deref(), Some("1px"));
+        assert_eq!(width("[|href=x] { width: 1px }").as_deref(), Some("1px"));
+        assert_eq!(width("[*|href=y] { width: 1px }"), None);
+        // An undeclared prefix doesn't match `prefix:name` attributes.
+        assert_eq!(width("[xlink|href=y] { width: 1px }"), None);
+    }
+
+    #[test]
+    fn shorthands_set_their_longhands() {
+        let container = |css: &str| {
+            let sheet = [(Origin::Author, css)];
+
+            (computed("<p></p>", &sheet, "container-name"), computed("<p></p>", &sheet, "container-type"))
+        };
+        let both = |name: &str, container_type: &str| (Some(name.to_string()), Some(container_type.to_string()));
+
+        assert_eq!(container("p { container: card / inline-size }"), both("card", "inline-size"));
+        assert_eq!(container("p { container-type: size; container: card }"), both("card", "normal"));
+        assert_eq!(container("p { container: card / size; container-type: inline-size }"), both("card", "inline-size"));
+        assert_eq!(container("p { --c: a b / size; container: var(--c) }"), both("a b", "size"));
+        assert_eq!(container("p { container: var(--c); container-type: size }"), both("none", "size"));
+        // Invalid once substituted, so both longhands are unset.
+        assert_eq!(container("p { --c: a / b; container: card / size; container: var(--c) }"), both("none", "normal"));
+    }
+
+    #[test]
+    fn container_sizes_follow_elements_not_addresses() {
+        let html = HtmlParser::new("<div><p></p></div>").parse_nodes();
+        let sheet = CssParser::new("div { container-type: inline-size } @container (min-width: 50px) { p { width: 1px } }").parse_stylesheet();
+        let mut sizes = ContainerSizes::new();
+
+        sizes.insert(&StyledNode::new(&html[0], &sheet, &Viewport::default()), 100.0, 0.0);
+
+        let moved = html.clone();
+        let styled = StyledNode::with_containers(&moved[0], &[&sheet], &Viewport::default(), &mut Timeline::new(), &sizes);
+        assert_eq!(styled.children[0].value("width").map(Value::to_string).as_deref(), Some("1px"));
+    }
+
+    #[test]
+    fn combinators_match_right_to_left() {
+        let html = "<div id=\"root\"><section id=\"s\"><p id=\"p1\"></p><div id=\"d\"><p id=\"p2\"></p></div><p id=\"p3\"></p>\
+                    <span id=\"x\"></span><p id=\"p4\"></p></section></div>";
+        let ids = |selector: &str| matching_html(html, selector);
+
+        assert_eq!(ids("section p"), vec!["p1", "p2", "p3", "p4"]);
+        assert_eq!(ids("section > p"), vec!["p1", "p3", "p4"]);
+        assert_eq!(ids("#root > section > div > p"), vec!["p2"]);
+        assert_eq!(ids("p + div"), vec!["d"]);
+        assert_eq!(ids("div + p"), vec!["p3"]);
+        assert_eq!(ids("p + p"), Vec::<String>::new());
+        assert_eq!(ids("div ~ p"), vec!["p3", "p4"]);
+        assert_eq!(ids("#root div > p"), vec!["p2"]);
+        assert_eq!(ids("section > div p, span + p"), vec!["p2", "p4"]);
+        // A descendant combinator keeps looking further up after a failed
+        // match.
+        assert_eq!(ids("section div p"), vec!["p2"]);
+        assert_eq!(ids("#root > div p"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn structural_pseudo_classes() {
+        let html = "<ul id=\"root\"><li id=\"a\" class=\"x\"></li><li id=\"b\"><!-- --></li><p id=\"c\" class=\"x\">text</p>\
+                    <li id=\"d\" class=\"x\"></li><li id=\"e\"><b id=\"f\"></b></li></ul>";
+        let ids = |selector: &str| matching_html(html, selector);
+
+        assert_eq!(ids(":root"), vec!["root"]);
+        assert_eq!(ids(":empty"), vec!["a", "b", "d", "f"]);
+        // An element without a parent is its only child.
+        assert_eq!(ids("li:first-child, li:last-child, :only-child"), vec!["root", "a", "e", "f"]);
+        assert_eq!(ids("li:first-of-type, li:last-of-type, p:only-of-type"), vec!["a", "c", "e"]);
+        assert_eq!(ids("ul > :nth-child(2n+1)"), vec!["a", "c", "e"]);
+        assert_eq!(ids("ul > :nth-child(-n+2)"), vec!["a", "b"]);
+        assert_eq!(ids("ul > :nth-last-child(2)"), vec!["d"]);
+        assert_eq!(ids(":nth-child(even of .x)"), vec!["c"]);
+        assert_eq!(ids(":nth-last-child(1 of .x)"), vec!["d"]);
+        assert_eq!(ids("li:nth-of-type(3)"), vec!["d"]);
+        assert_eq!(ids("li:nth-last-of-type(odd)"), vec!["b", "e"]);
+    }
+
+    #[test]
+    fn logical_pseudo_classes() {
+        let html = "<div id=\"root\"><p id=\"a\" class=\"x\"><b id=\"b\"></b></p><p id=\"c\"></p><span id=\"d\" class=\"x\"></span></div>";
+        let ids = |selector: &str| matching_html(html, selector);
+
+        assert_eq!(ids("div > :not(.x)"), vec!["c"]);
+        assert_eq!(ids("div > :not(p, span)"), Vec::<String>::new());
+        assert_eq!(ids(":is(p, span).x"), vec!["a", "d"]);
+        assert_eq!(ids(":where(div) > p"), vec!["a", "c"]);
+        assert_eq!(ids(":has(b)"), vec!["root", "a"]);
+        assert_eq!(ids(":has(> .x)"), vec!["root"]);
+        assert_eq!(ids("p:has(+ p)"), vec!["a"]);
+        assert_eq!(ids("p:has(~ .x)"), vec!["a", "c"]);
+        assert_eq!(ids("p:not(:has(b))"), vec!["c"]);
+
+        // :where() adds nothing to the specificity, and :is() the most
+        // specific of its arguments.
+        let p = "<p id=\"c\"></p>";
+
+        assert_eq!(width(p, &[(Origin::Author, "p:where(#c) { width: 1px } p { width: 2px }")]).as_deref(), Some("2px"));
+        assert_eq!(width(p, &[(Origin::Author, "#c { width: 1px } :is(p, #c) { width: 2px }")]).as_deref(), Some("2px"));
+    }
+
+    #[test]
+    fn state_pseudo_classes_follow_the_host() {
+        fn set_state(node: &mut Node, id: &str, set: &dyn Fn(&mut crate::dom::ElementState)) {
+            if let NodeType::Element(ref mut e) = node.node_type {
+                if e.get_id().map(String::as_str) == Some(id) {
+                    set(&mut e.state);
+                }
+            }
+
+            for child in &mut node.children {
+                set_state(child, id, set);
+            }
+        }
+
+        let html = "<form id=\"root\"><input id=\"a\"><input id=\"b\" disabled><input id=\"c\" type=\"checkbox\" checked>\
+                    <a id=\"d\" href=\"x\"></a><a id=\"e\"></a><p id=\"f\"></p></form>";
+        let mut root = HtmlParser::new(html).parse_nodes().remove(0);
+
+        assert_eq!(matching(&root, ":disabled"), vec!["b"]);
+        assert_eq!(matching(&root, "input:enabled"), vec!["a", "c"]);
+        assert_eq!(matching(&root, ":checked"), vec!["c"]);
+        assert_eq!(matching(&root, ":link"), vec!["d"]);
+        assert_eq!(matching(&root, ":hover, :focus, :active, :visited"), Vec::<String>::new());
+
+        set_state(&mut root, "f", &|s| s.hover = true);
+        set_state(&mut root, "a", &|s| {
+            s.focus = true;
+            s.checked = true;
+        });
+        set_state(&mut root, "b", &|s| s.active = true);
+        set_state(&mut root, "d", &|s| s.visited = true);
+
+        // The host sets the flags of every element they apply to.
+        assert_eq!(matching(&root, ":hover"), vec!["f"]);
+        assert_eq!(matching(&root, ":focus"), vec!["a"]);
+        assert_eq!(matching(&root, ":checked"), vec!["a", "c"]);
+        assert_eq!(matching(&root, ":active"), vec!["b"]);
+        assert_eq!(matching(&root, ":link"), Vec::<String>::new());
+        assert_eq!(matching(&root, ":visited"), vec!["d"]);
+    }
+}
@@ -0,0 +1,20 @@
+use std::sync::OnceLock;
+
+use crate::css::{
+    Origin,
+    Stylesheet,
+};
+use crate::css_parser::CssParser;
+
+// The default styles for HTML documents, after the rendering section of the
+// HTML spec, limited to the properties the engine supports.
+const USER_AGENT_CSS: &str = include_str!("user_agent.css");
+
+// Parsed the first time it's needed.
+pub fn stylesheet() -> &'static Stylesheet {
+    static STYLESHEET: OnceLock<Stylesheet> = OnceLock::new();
+
+    return STYLESHEET.get_or_init(|| {
+        CssParser::new(USER_AGENT_CSS).parse_stylesheet_with_origin(Origin::UserAgent)
+    })
+}